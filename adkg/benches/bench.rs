@@ -1,7 +1,10 @@
 extern crate criterion;
 
 use criterion::*;
-use adkg::run;
+use adkg::{run, run_batch, run_with_params};
+use util::committee::Committee;
+use util::params::ProtocolParams;
+use util::SECURITY_BITS;
 
 fn bench_run(c: &mut Criterion) {
     // n 从 4 到 64，步长为 4
@@ -14,9 +17,41 @@ fn bench_run(c: &mut Criterion) {
     }
 }
 
+fn bench_terminate_round(c: &mut Criterion) {
+    // 对每个 n 输出不同 terminate_round 下的证明大小和验证开销，并比较缺省设置与最优设置的运行时间
+    let mut group = c.benchmark_group("terminate_round");
+    for n in (7..=65).step_by(8) {
+        let f = (n-1)/3;
+        let params = ProtocolParams::new(&Committee::uniform(n, f, f), SECURITY_BITS);
+        for cost in params.proof_costs() {
+            println!("n: {}, terminate_round: {}, proof bytes: {}, hashes: {}, field ops: {}",
+                n, cost.terminate_round, cost.proof_bytes, cost.hashes, cost.field_ops);
+        }
+        let optimal = params.optimal_terminate_round();
+        println!("n: {}, optimal terminate_round: {}", n, optimal);
+        for r in [params.terminate_round(), optimal] {
+            let params = params.clone().with_terminate_round(r);
+            group.bench_with_input(BenchmarkId::new(format!("n={}", n), r), &params, |b, params| b.iter(|| run_with_params(params)));
+        }
+    }
+}
+
+fn bench_batch(c: &mut Criterion) {
+    // 一次运行生成不同个数的密钥，按密钥个数计算吞吐量，即每个密钥的平摊开销
+    let mut group = c.benchmark_group("batch");
+    for n in (7..=31).step_by(8) {
+        let f = (n-1)/3;
+        let params = ProtocolParams::new(&Committee::uniform(n, f, f), SECURITY_BITS);
+        for count in [1, 4, 16] {
+            group.throughput(Throughput::Elements(count as u64));
+            group.bench_with_input(BenchmarkId::new(format!("n={}", n), count), &count, |b, &count| b.iter(|| run_batch(&params, count)));
+        }
+    }
+}
+
 criterion_group!(
     name = benches;
     config = Criterion::default().sample_size(10);
-    targets = bench_run
+    targets = bench_run, bench_terminate_round, bench_batch
 );
 criterion_main!(benches);
\ No newline at end of file
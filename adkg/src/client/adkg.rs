@@ -1,17 +1,35 @@
 // use sha256::digest;
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::Arc;
 use util::vec_check::{is_invector, is_subset};
-use super::avss::AvssNode;
+use util::committee::Committee;
+use util::params::ProtocolParams;
+use util::algebra::field::{as_bytes_vec, Field};
+use util::algebra::field::mersenne61_ext::Mersenne61Ext;
+use util::algebra::lattice::Lattice;
+use util::algebra::reed_solomon::{Decoded, OnlineDecoder, ReedSolomon};
+use util::merkle_tree::MERKLE_ROOT_SIZE;
+use util::envelope::Envelope;
+use util::kem::{DecapsulationKey, EncapsulationKey};
+use util::share::{Domain, Share};
+use util::signature::{Signature, SigningKey, VerifyingKey};
+use avss::avss::party::{Complaint, ShareProof};
+use util::algebra::polynomial::Polynomial;
+use super::avss::{key_index, open_proof, open_revealed, seal_proof, AvssNode, KEY_SECRET, SHARE_KEM};
+use super::beacon::{BeaconTranscript, SignedSums};
+use super::triple::{challenge, Dealing, TripleNode};
 use crate::msg::result::AdkgResult;
-use crate::msg::message::Message;
+use crate::msg::message::{decode_values, encode_values, Message};
 use crate::msg::message::MessageType;
 
 
 pub struct AdkgNode {
     id: usize,
     state: usize,
-    n: usize,
-    f: usize,
+    committee: Committee,
+    params: ProtocolParams<Mersenne61Ext>,
     set_dealer: Vec<usize>,
     set_prop: Vec<usize>,
     hash_prop: HashMap<usize, Vec<usize>>,
@@ -19,18 +37,53 @@ pub struct AdkgNode {
     fin: bool,
     set_fin: Vec<usize>,
     hash_fin: HashMap<usize, u64>,
-    avss: AvssNode,
+    avss: Rc<RefCell<AvssNode>>,
     pub res: Option<AdkgResult>,
-    
+    allocation: Vec<Vec<usize>>,
+    /// 每个 Dealer 在自己的每个求值点上分发的份额，重新分享时为 Dealer 的每个旧份额的份额
+    shares: HashMap<usize, Vec<Vec<Share<Mersenne61Ext>>>>,
+    sent: bool,
+    /// 公开的值的每个分量各用一个译码器，格上的密钥为公钥的每个系数
+    decoders: Vec<OnlineDecoder<Mersenne61Ext>>,
+    decoded: Vec<Option<Decoded<Mersenne61Ext>>>,
+    commitments: HashMap<usize, HashMap<usize, [u8; MERKLE_ROOT_SIZE]>>,
+    /// Dealer 广播的发给每个参与方的信封，投诉中的信封必须与它相同
+    envelopes: HashMap<usize, HashMap<usize, Envelope>>,
+    /// 先于 Dealer 的消息到达的投诉，以及投诉的是否为门限 2t 的分发
+    complaints: HashMap<usize, Vec<(Complaint<Mersenne61Ext>, bool)>>,
+    disqualified: Vec<usize>,
+    /// 发给自己的份额无法使用但不能投诉的 Dealer：没有份额或者密文无法解密，门限 2t 的分发验证失败
+    unusable: Vec<usize>,
+    /// 每个 Dealer 发给自己的求值点的解密后的证明，用于帮助 Dealer 没有发送份额的节点恢复
+    opened: HashMap<usize, Vec<ShareProof<Mersenne61Ext>>>,
+    /// 还没有回复的恢复请求，每项为请求者和 Dealer
+    recover_requests: Vec<(usize, usize)>,
+    /// 恢复份额时收到的同一列的求值点上通过验证的份额，以 Dealer 为键，每项为求值点和各个秘密的份额
+    recovery: HashMap<usize, Vec<(usize, Vec<Mersenne61Ext>)>>,
+    /// 因为缺少 set_fin 中 Dealer 的份额而没有发送份额之和，恢复之后再发送
+    withheld: bool,
+    /// 还不能处理的份额之和或检查值，见 `holding_sums`
+    pending: Vec<Message>,
+    /// 自己的求值点上的密钥份额，格上的密钥按求值点依次排列 s 的全部系数的份额，批量生成时依次排列每个密钥的份额
+    key_shares: Option<Vec<Mersenne61Ext>>,
+    /// 运行模式和模式各自的状态
+    mode: Mode,
+    /// 加密分发时自己的 KEM 私钥，`run_*` 中的节点总是加密分发
+    /// 没有设置时份额以明文广播，任何节点都能读到，只用于本地的单元测试
+    decapsulation: Option<DecapsulationKey>,
+    /// 每个节点的 KEM 公钥，用于检查投诉
+    keys: Vec<EncapsulationKey>,
 }
 
 impl AdkgNode {
-    pub fn new(id: usize, state: usize, n: usize, f: usize) -> AdkgNode {
+    /// `avss` 为与 VABA 共享的批量分发节点
+    pub fn new(id: usize, state: usize, params: &ProtocolParams<Mersenne61Ext>, avss: Rc<RefCell<AvssNode>>) -> AdkgNode {
+        let code = ReedSolomon::new(&params.coset_y(), params.t());
         AdkgNode {
             id,
             state,
-            n,
-            f,
+            committee: params.committee().clone(),
+            params: params.clone(),
             set_dealer: Vec::new(),
             set_prop: Vec::new(),
             hash_prop: HashMap::new(),
@@ -38,9 +91,224 @@ impl AdkgNode {
             fin: false,
             set_fin: Vec::new(),
             hash_fin: HashMap::new(),
-            avss: AvssNode::new(id, log_2_n(n), 1),
+            avss,
             res: None,
+            allocation: params.committee().allocate(),
+            shares: HashMap::new(),
+            sent: false,
+            decoders: vec![OnlineDecoder::new(code, Self::max_errors(params.committee()))],
+            decoded: vec![None],
+            commitments: HashMap::new(),
+            envelopes: HashMap::new(),
+            complaints: HashMap::new(),
+            disqualified: Vec::new(),
+            unusable: Vec::new(),
+            opened: HashMap::new(),
+            recover_requests: Vec::new(),
+            recovery: HashMap::new(),
+            withheld: false,
+            pending: Vec::new(),
+            key_shares: None,
+            mode: Mode::Generate { batch: 1 },
+            decapsulation: None,
+            keys: Vec::new(),
+        }
+    }
+
+    /// 一次生成 `count` 个独立的密钥：Dealer 在同一次批量分发中分发 `count` 个秘密，
+    /// 协商出的同一个 Dealer 集合用于所有密钥，每个密钥各用一个译码器
+    pub fn batch(
+        id: usize,
+        state: usize,
+        params: &ProtocolParams<Mersenne61Ext>,
+        avss: Rc<RefCell<AvssNode>>,
+        count: usize,
+    ) -> AdkgNode {
+        if count == 0 {
+            panic!("must generate at least one key");
+        }
+        let mut node = Self::new(id, state, params, avss);
+        let code = ReedSolomon::new(&params.coset_y(), params.t());
+        node.decoders = (0..count)
+            .map(|_| OnlineDecoder::new(code.clone(), Self::max_errors(params.committee())))
+            .collect();
+        node.decoded = vec![None; count];
+        node.mode = Mode::Generate { batch: count };
+        node
+    }
+
+    /// 生成 `count` 个乘法三元组：只有以门限 t 和以门限 2t 的参数 `double` 进行的两次分发都验证通过的
+    /// Dealer 才加入 set_dealer，协商出 Dealer 集合后由 `TripleNode` 检查两次分发是否一致并完成乘法
+    pub fn triples(
+        id: usize,
+        state: usize,
+        params: &ProtocolParams<Mersenne61Ext>,
+        avss: Rc<RefCell<AvssNode>>,
+        double: &ProtocolParams<Mersenne61Ext>,
+        count: usize,
+    ) -> AdkgNode {
+        let mut node = Self::new(id, state, params, avss);
+        node.mode = Mode::Triples(TripleMode {
+            double: double.clone(),
+            count,
+            commitments: HashMap::new(),
+            envelopes: HashMap::new(),
+            shares: HashMap::new(),
+            challenges: HashMap::new(),
+        });
+        node
+    }
+
+    /// 生成格上的密钥：Dealer 分发小范数的 s_i 和 e_i 的中心二项分布的比特，协商出 Dealer 集合后，
+    /// 节点先公开每个 Dealer 的比特检查值 Σ ρ^(i+1)·(a_i^2 - a_i) + x·r1 + x^t·r2 的份额，它是 2t 次多项式，
+    /// 所有 a_i 都是比特时秘密为 0，r1 和 r2 是 Dealer 分发的随机数，使它与比特的份额无关。
+    /// 秘密不为 0 的 Dealer 被排除，因此 s 和 e 的系数的绝对值不超过 eta 乘以其余 Dealer 的个数。
+    /// 节点再由其余 Dealer 的比特得到 s = Σ s_i 的份额，并公开 A·s + e 的份额，重构出公钥 A·s + e，s 不被重构
+    /// 2t 次的码要纠正 f 个错误，需要 n >= 2t + 2f + 1
+    /// Dealer 还分发 `decryptions` 组门限解密的淹没噪声，节点同样得到它们之和的份额，淹没噪声的范围不检查
+    pub fn lattice(
+        id: usize,
+        state: usize,
+        params: &ProtocolParams<Mersenne61Ext>,
+        avss: Rc<RefCell<AvssNode>>,
+        lattice: &Lattice,
+        decryptions: usize,
+    ) -> AdkgNode {
+        let committee = params.committee();
+        if committee.total_weight() < 2 * committee.t() + 2 * committee.f() + 1 {
+            panic!("must have n >= 2t+2f+1 to check the lattice dealings");
         }
+        let mut node = Self::new(id, state, params, avss);
+        let code = ReedSolomon::new(&params.coset_y(), params.t());
+        let dimension = lattice.params().dimension();
+        node.decoders = (0..dimension)
+            .map(|_| OnlineDecoder::new(code.clone(), Self::max_errors(params.committee())))
+            .collect();
+        node.decoded = vec![None; dimension];
+        node.mode = Mode::Lattice(LatticeMode {
+            lattice: lattice.clone(),
+            decryptions,
+            flooding: Vec::new(),
+            range_decoders: Vec::new(),
+            range_checks: Vec::new(),
+            range_senders: Vec::new(),
+            range_pending: Vec::new(),
+            accepted: None,
+        });
+        node
+    }
+
+    /// 重新分享：Dealer 为旧委员会 `old` 中的节点，各自分发自己的旧份额，`params` 为新委员会的参数
+    /// 新委员会协商出至少有 t + f + 1 个旧份额的 Dealer 集合，与主动刷新一样公开每个 Dealer 的检查值，
+    /// 译码后与 `published` 中 Dealer 公开过的旧份额不一致的 Dealer 被排除，其余 Dealer 仍有至少 t + 1 个旧份额。
+    /// 以这些旧份额的拉格朗日系数组合收到的份额，得到同一个组密钥在新委员会中的份额，新的份额不公开
+    pub fn reshare(
+        id: usize,
+        state: usize,
+        params: &ProtocolParams<Mersenne61Ext>,
+        avss: Rc<RefCell<AvssNode>>,
+        old: &ProtocolParams<Mersenne61Ext>,
+        published: Vec<Option<Vec<Mersenne61Ext>>>,
+    ) -> AdkgNode {
+        let mut node = Self::new(id, state, params, avss);
+        node.mode = Mode::Reshare { old: old.clone(), published };
+        node
+    }
+
+    /// 主动刷新：`avss` 分发秘密为 0 的多项式，`key_shares` 为自己的求值点上当前的密钥份额
+    /// 协商出的 Dealer 集合的份额加到当前份额上，得到新的份额，组密钥不变
+    /// 新的份额不公开：节点只公开每个 Dealer 的检查值 ρ_d·z_d + m_d 的份额，其中 m_d 为 Dealer 同时分发的
+    /// 秘密为 0 的掩码多项式，检查值与 z_d 无关；ρ_d 由 Dealer 的承诺得到，译码后秘密不为 0 的 Dealer 被排除
+    pub fn refresh(
+        id: usize,
+        state: usize,
+        params: &ProtocolParams<Mersenne61Ext>,
+        avss: Rc<RefCell<AvssNode>>,
+        key_shares: Vec<Mersenne61Ext>,
+    ) -> AdkgNode {
+        let mut node = Self::new(id, state, params, avss);
+        assert_eq!(key_shares.len(), node.allocation[id].len());
+        node.mode = Mode::Refresh { previous: key_shares };
+        node
+    }
+
+    /// 加密分发：`dk` 为自己的 KEM 私钥，`keys` 为每个节点的 KEM 公钥，Dealer 的份额只能由它的所有者解密
+    /// 需要在分发之前设置，之后不接受以明文广播份额的 Dealer。收到的份额无法通过验证时公开解密信封的随机数并投诉
+    pub fn set_encryption(&mut self, dk: DecapsulationKey, keys: &[EncapsulationKey]) {
+        self.avss.borrow_mut().seal_for(keys);
+        self.decapsulation = Some(dk);
+        self.keys = keys.to_vec();
+    }
+
+    /// 随机信标的第 `epoch` 个 epoch：`key` 为自己这个 epoch 的一次性签名密钥，`keys` 为每个节点的验证密钥
+    /// 节点签名自己公开的份额之和，只接受签名正确的份额之和，信标的记录因此可以公开验证
+    /// 只用于生成单个密钥的节点
+    pub fn set_signing(&mut self, epoch: u64, key: SigningKey, keys: &[VerifyingKey]) {
+        assert!(matches!(self.mode, Mode::Generate { batch: 1 }), "the beacon generates a single key");
+        self.mode = Mode::Beacon(BeaconMode {
+            epoch,
+            signing: key,
+            verifying: keys.to_vec(),
+            signed: Vec::new(),
+        });
+    }
+
+    /// 自己的求值点上的密钥份额，协商出 Dealer 集合并收到它们的份额后才有值
+    pub fn key_shares(&self) -> Option<&Vec<Mersenne61Ext>> {
+        self.key_shares.as_ref()
+    }
+
+    /// 随机信标的记录，设置了签名密钥、生成单个密钥且译码出秘密之后才有值
+    pub fn beacon(&self) -> Option<BeaconTranscript> {
+        let beacon = match &self.mode {
+            Mode::Beacon(beacon) => beacon,
+            _ => return None,
+        };
+        let res = self.res.as_ref()?;
+        let decoded = self.decoded[0].as_ref()?;
+        let commitments = res.users.iter().map(|d| self.commitment(*d)).collect::<Option<_>>()?;
+        Some(BeaconTranscript::new(
+            beacon.epoch,
+            res.users.clone(),
+            commitments,
+            beacon.signed.clone(),
+            decoded.polynomial.evaluation_at(Mersenne61Ext::from_int(0)),
+        ))
+    }
+
+    /// set_fin 中每个 Dealer 的两次分发在自己的每个求值点上的份额，生成乘法三元组时使用
+    pub fn dealings(&self) -> Vec<Dealing> {
+        let triples = match &self.mode {
+            Mode::Triples(triples) => triples,
+            _ => return vec![],
+        };
+        self.set_fin
+            .iter()
+            .filter(|d| triples.shares.contains_key(d))
+            .map(|d| Dealing {
+                dealer: *d,
+                challenge: triples.challenges[d],
+                single: self.shares[d].clone(),
+                double: triples.shares[d].clone(),
+            })
+            .collect()
+    }
+
+    /// 自己的每个求值点上淹没噪声的份额，与 `key_shares` 同时得到，只有格上的密钥才有
+    pub fn flooding(&self) -> &[Vec<Mersenne61Ext>] {
+        match &self.mode {
+            Mode::Lattice(lattice) => &lattice.flooding,
+            _ => &[],
+        }
+    }
+
+    /// 重构时能够纠正的错误份额的个数，总是取 f
+    /// 恶意节点不发送份额时诚实节点的 n - f 个份额要达到 t + 1 + f 个，因此要求 n >= t + 2f + 1
+    pub fn max_errors(committee: &Committee) -> usize {
+        if committee.total_weight() < committee.t() + 2 * committee.f() + 1 {
+            panic!("must have n >= t + 2f + 1 to correct f errors");
+        }
+        committee.f()
     }
 
     pub fn send_message(&self, recv: Vec<usize>, msg_type: MessageType, msg_content: Vec<usize>) -> Option<Message>{
@@ -52,29 +320,263 @@ impl AdkgNode {
             return None
         }
         println!("client_id:{} status:ADKG_SHARE_FIN", self.id);
-        self.avss.send_and_verify(MessageType::AdkgAvssFin)
+        self.avss.borrow_mut().send_and_verify(MessageType::AdkgAvssFin)
     }
 
-    pub fn handle_share_fin(&mut self, id: usize) -> Option<Message> {
-        if is_invector(id, &self.set_dealer) {
+    /// 收到 Dealer 分发完成的消息，验证并保存分配给自己的求值点上的份额
+    /// 验证失败时取消 Dealer 的资格，并广播投诉：收到的信封和解密它时得到的 KEM 随机数
+    /// 生成乘法三元组时门限 2t 的分发同样验证和投诉，投诉消息中以 `msg_content[1] = 1` 标记
+    /// 之前因为缺少这个 Dealer 的承诺而保存的检查值在这里处理，结果保存在 `res` 中，之前收到的恢复请求也在这里回复
+    pub fn handle_share_fin(&mut self, msg: Message) -> Option<Message> {
+        let message = self.receive_dealing(msg);
+        self.flush_sums();
+        message.or_else(|| self.answer_recovery())
+    }
+
+    fn receive_dealing(&mut self, msg: Message) -> Option<Message> {
+        let id = msg.sender_id;
+        if is_invector(id, &self.set_dealer) || is_invector(id, &self.disqualified) {
             return None
         }
+        let secrets = self.secrets(id);
+        let log_n = self.params.log_n();
+        // 生成乘法三元组时，门限 2t 的分发的证明在门限 t 的证明之后
+        let (proofs, double) = match self.mode {
+            Mode::Triples(_) if msg.proofs.len() == 2 << log_n => msg.proofs.split_at(1 << log_n),
+            Mode::Triples(_) => return None,
+            _ => msg.proofs.split_at(msg.proofs.len()),
+        };
+        let max = match secrets.iter().max() {
+            Some(max) => *max,
+            None => return None,
+        };
+        // 加密分发时只能检查自己的求值点上的份额
+        if self.decapsulation.is_none() && proofs.iter().any(|p| p.shares.len() <= max) {
+            return None
+        }
+        // Dealer 对每个参与方的承诺和信封不同，保存下来用于检查投诉
+        let commitments = proofs.iter().map(|p| (p.party, p.transcript.digest())).collect();
+        self.commitments.insert(id, commitments);
+        let envelopes = proofs.iter().filter_map(|p| Some((p.party, p.sealed.clone()?))).collect();
+        self.envelopes.insert(id, envelopes);
+        if let Mode::Triples(triples) = &mut self.mode {
+            let commitments = double.iter().map(|p| (p.party, p.transcript.digest())).collect();
+            triples.commitments.insert(id, commitments);
+            let envelopes = double.iter().filter_map(|p| Some((p.party, p.sealed.clone()?))).collect();
+            triples.envelopes.insert(id, envelopes);
+        }
+        let (mut shares, mut opened) = (vec![], vec![]);
+        for p in &self.allocation[self.id] {
+            let received = match proofs.iter().find(|x| x.party == p << log_n) {
+                Some(proof) => self.receive(&self.params, id, proof, max),
+                None => Received::Unusable,
+            };
+            let proof = match received {
+                Received::Valid(proof) => proof,
+                Received::Invalid(proof, m) => return self.complain(id, proof, m, false),
+                Received::Unusable => {
+                    self.unusable.push(id);
+                    return None
+                }
+            };
+            shares.push(secrets.iter().map(|k| proof.share(&self.params, *k, id)).collect());
+            opened.push(proof);
+        }
+        self.opened.insert(id, opened);
+        // 先于 Dealer 的消息到达的投诉
+        if let Some(complaints) = self.complaints.remove(&id) {
+            if complaints.iter().any(|(c, double)| self.check_complaint(id, c, *double)) {
+                self.disqualify(id);
+                return None
+            }
+        }
+        if let Mode::Triples(TripleMode { double: params, count, .. }) = &self.mode {
+            let (params, count) = (params.clone(), *count);
+            let mut double_shares = vec![];
+            for p in &self.allocation[self.id] {
+                let received = match double.iter().find(|x| x.party == p << log_n) {
+                    Some(proof) => self.receive(&params, id, proof, count),
+                    None => Received::Unusable,
+                };
+                match received {
+                    Received::Valid(proof) => {
+                        double_shares.push((0..=count).map(|k| proof.share(&params, k, id)).collect())
+                    }
+                    Received::Invalid(proof, m) => return self.complain(id, proof, m, true),
+                    Received::Unusable => {
+                        self.unusable.push(id);
+                        return None
+                    }
+                }
+            }
+            if let Mode::Triples(triples) = &mut self.mode {
+                triples.shares.insert(id, double_shares);
+                triples.challenges.insert(id, challenge(&msg.proofs));
+            }
+        }
+        self.shares.insert(id, shares);
+        let enough = self.enough_dealers(&self.set_dealer);
         self.set_dealer.push(id);
 
-        if self.set_dealer.len() == self.f + 1 {
+        if !enough && self.enough_dealers(&self.set_dealer) {
             self.set_prop = self.set_dealer.clone();
             return self.send_message(vec![], MessageType::AdkgProp, self.set_prop.clone());
         }
+        if self.fin && !self.sent {
+            return self.send_sum();
+        }
         None
     }
 
+    /// 取消 Dealer 的资格并广播投诉，`double` 表示投诉的是门限 2t 的分发
+    fn complain(&mut self, dealer: usize, proof: ShareProof<Mersenne61Ext>, m: [u8; 32], double: bool) -> Option<Message> {
+        self.disqualify(dealer);
+        println!("client_id:{} status:ADKG_COMPLAINT dealer:{}", self.id, dealer);
+        let content = if double { vec![dealer, 1] } else { vec![dealer] };
+        let mut message = self.send_message(vec![], MessageType::AdkgComplaint, content).unwrap();
+        message.proofs = Arc::new(vec![proof]);
+        message.additional = hex::encode(m);
+        Some(message)
+    }
+
+    /// 按参数 `params` 检查 Dealer `dealer` 发给自己的求值点的证明，`max` 为 Dealer 应当分发的秘密的最大下标
+    fn receive(&self, params: &ProtocolParams<Mersenne61Ext>, dealer: usize, proof: &ShareProof<Mersenne61Ext>, max: usize) -> Received {
+        let dk = match &self.decapsulation {
+            Some(dk) => dk,
+            // 明文分发只用于本地的单元测试，份额无法确认来自 Dealer，因此不投诉
+            None if proof.shares.len() > max && proof.verify(params) => return Received::Valid(proof.clone()),
+            None => return Received::Unusable,
+        };
+        // 密文不是由自己的公钥封装时无法公开随机数，其他节点无法确认，因此不投诉
+        let m = match proof.sealed.as_ref().and_then(|e| e.reveal(&SHARE_KEM, dk)) {
+            Some(m) => m,
+            None => return Received::Unusable,
+        };
+        match open_revealed(params, dealer, proof, &dk.encapsulation_key(), &m) {
+            Some(opened) if opened.shares.len() > max && opened.verify(params) => Received::Valid(opened),
+            _ => Received::Invalid(proof.clone(), m),
+        }
+    }
+
+    /// Dealer 分发的秘密中计入密钥的那些的下标，重新分享时只接受旧委员会中的节点
+    fn secrets(&self, dealer: usize) -> Vec<usize> {
+        match &self.mode {
+            Mode::Generate { batch } => (0..*batch).map(key_index).collect(),
+            Mode::Beacon(_) => vec![KEY_SECRET],
+            Mode::Refresh { .. } => vec![KEY_SECRET, key_index(1)],
+            Mode::Reshare { old, .. } if dealer < old.committee().n() => {
+                (0..=old.committee().weight(dealer)).map(key_index).collect()
+            }
+            Mode::Reshare { .. } => vec![],
+            Mode::Triples(triples) => (0..3 * triples.count + 1).map(key_index).collect(),
+            Mode::Lattice(LatticeMode { lattice, decryptions, .. }) => {
+                let count = 4 * lattice.params().eta() * lattice.params().dimension()
+                    + decryptions * lattice.params().degree()
+                    + 2;
+                (0..count).map(key_index).collect()
+            }
+        }
+    }
+
+    /// Dealer 集合足够大：权重超过 f，重新分享时旧份额的个数超过旧委员会的 t + f，排除作弊的 Dealer 后仍能重构
+    fn enough_dealers(&self, dealers: &[usize]) -> bool {
+        match &self.mode {
+            Mode::Reshare { old, .. } => old.committee().weight_of(dealers) > old.t() + old.committee().f(),
+            _ => self.committee.exceeds_faulty(dealers),
+        }
+    }
+
+    /// 是否为重新分享
+    pub fn resharing(&self) -> bool {
+        matches!(self.mode, Mode::Reshare { .. })
+    }
+
+    /// 重新分享时 `dealers` 分发的每个旧份额在密钥中的拉格朗日系数
+    fn coefficients(old: &ProtocolParams<Mersenne61Ext>, dealers: &[usize]) -> HashMap<usize, Vec<Mersenne61Ext>> {
+        let allocation = old.committee().allocate();
+        let xs: Vec<_> = dealers
+            .iter()
+            .flat_map(|d| allocation[*d].iter().map(|p| old.share_point(*p)))
+            .collect();
+        let mut lambda = Polynomial::lagrange_coefficients(&xs, Mersenne61Ext::from_int(0)).into_iter();
+        dealers
+            .iter()
+            .map(|d| (*d, lambda.by_ref().take(allocation[*d].len()).collect()))
+            .collect()
+    }
+
+    /// 收到对 Dealer 的投诉，投诉成立时永久取消 Dealer 的资格
+    /// 还没有收到 Dealer 的承诺时保存投诉，收到承诺后再检查
+    pub fn handle_complaint(&mut self, msg: Message) -> Option<Message> {
+        let dealer = *msg.msg_content.first()?;
+        let double = msg.msg_content.get(1) == Some(&1);
+        let proof = msg.proofs.first()?.clone();
+        let revealed = hex::decode(&msg.additional).ok()?.try_into().ok()?;
+        if is_invector(dealer, &self.disqualified) {
+            return None
+        }
+        let complaint = Complaint::new(proof, revealed);
+        if !self.commitments.contains_key(&dealer) {
+            self.complaints.entry(dealer).or_default().push((complaint, double));
+        } else if self.check_complaint(dealer, &complaint, double) {
+            println!("client_id:{} status:ADKG_DISQUALIFY dealer:{}", self.id, dealer);
+            self.disqualify(dealer);
+        }
+        None
+    }
+
+    /// 用 Dealer 对投诉者的承诺和信封检查投诉，信封由求值点的所有者的公钥打开
+    /// 份额少于 Dealer 应当分发的秘密个数时同样是 Dealer 的错误；`double` 时检查门限 2t 的分发
+    fn check_complaint(&self, dealer: usize, complaint: &Complaint<Mersenne61Ext>, double: bool) -> bool {
+        let party = complaint.proof.party;
+        let point = party >> self.params.log_n();
+        let owner = match self.allocation.iter().position(|points| points.contains(&point)) {
+            Some(owner) if owner < self.keys.len() => owner,
+            _ => return false,
+        };
+        let (params, commitments, envelopes, max) = match (&self.mode, double) {
+            (_, false) => {
+                let max = self.secrets(dealer).into_iter().max().unwrap_or(0);
+                (&self.params, &self.commitments, &self.envelopes, max)
+            }
+            (Mode::Triples(triples), true) => (&triples.double, &triples.commitments, &triples.envelopes, triples.count),
+            (_, true) => return false,
+        };
+        let (commitment, sealed) = match (commitments[&dealer].get(&party), envelopes[&dealer].get(&party)) {
+            (Some(commitment), Some(sealed)) => (commitment, sealed),
+            _ => return false,
+        };
+        complaint.verify(params, commitment, sealed, |proof, m| {
+            let mut opened = open_revealed(params, dealer, proof, &self.keys[owner], m)?;
+            if opened.shares.len() <= max {
+                opened.shares.clear();
+            }
+            Some(opened)
+        })
+    }
+
+    /// 从 set_dealer 中移除 Dealer，之后不再接受它的份额，也不为包含它的提议签名
+    fn disqualify(&mut self, dealer: usize) {
+        self.disqualified.push(dealer);
+        self.set_dealer.retain(|d| *d != dealer);
+        self.shares.remove(&dealer);
+        if let Mode::Triples(triples) = &mut self.mode {
+            triples.shares.remove(&dealer);
+        }
+    }
+
+    /// 被取消资格的 Dealer
+    pub fn disqualified(&self) -> &Vec<usize> {
+        &self.disqualified
+    }
+
     pub fn handle_prop(&mut self, msg: Message) -> Option<Message> {
         if !self.hash_prop.contains_key(&msg.sender_id) {
             self.hash_prop.insert(msg.sender_id, msg.msg_content.clone());
         }
 
-        // msg.msg_content 是自己的 set_dealer 集合的子集
-        if !is_subset(&msg.msg_content, &self.set_dealer) {
+        // msg.msg_content 是自己的 set_dealer 集合的子集，因此不包含被取消资格的 Dealer，并且足够大
+        if !is_subset(&msg.msg_content, &self.set_dealer) || !self.enough_dealers(&msg.msg_content) {
             return None
         }
 
@@ -92,6 +594,7 @@ impl AdkgNode {
                                                     .collect::<String>().chars().rev()
                                                     .collect::<String>().parse::<usize>();
 
+        let enough = self.committee.exceeds_faulty(&self.signers());
         if let Ok(number) = number {
             if number == msg.sender_id {
                 self.set_sig.push((msg.sender_id, msg));
@@ -105,7 +608,7 @@ impl AdkgNode {
             return None
         }
 
-        if self.set_sig.len() == self.f + 1 {
+        if !enough && self.committee.exceeds_faulty(&self.signers()) {
             println!("client_id:{} status:ADKG_SIG_ENOUGH set:{:?}", self.id, self.set_prop);
             return self.send_message(vec![self.id], MessageType::VabaStart, self.set_prop.clone());
         }
@@ -117,74 +620,1258 @@ impl AdkgNode {
         self.set_fin = self.hash_prop.get(&msg.msg_content[0]).unwrap().clone();
         self.fin = true;
         println!("client_id:{} status:ADKG_FIN set:{:?}", self.id, self.set_fin);
-        return Some(Message::send_message_with_addi(
-            self.id, 
-            vec![], 
-            MessageType::SumAndRec,
-            vec![], 
-            self.avss.sum_and_rec(self.set_fin.clone()).get_real().to_string().clone(),
-        ));
+        match &mut self.mode {
+            // 主动刷新和重新分享时每个 Dealer 的检查值各用一个译码器
+            Mode::Refresh { .. } | Mode::Reshare { .. } => {
+                let code = ReedSolomon::new(&self.params.coset_y(), self.params.t());
+                let max_errors = Self::max_errors(&self.committee);
+                self.decoders = self.set_fin.iter().map(|_| OnlineDecoder::new(code.clone(), max_errors)).collect();
+                self.decoded = vec![None; self.set_fin.len()];
+            }
+            // 格上的密钥先检查每个 Dealer 分发的是否为比特，2t 次的码
+            Mode::Lattice(lattice) => {
+                let code = ReedSolomon::new(&self.params.coset_y(), 2 * self.params.t());
+                let max_errors = TripleNode::max_errors(&self.committee);
+                lattice.range_decoders = self.set_fin.iter().map(|_| OnlineDecoder::new(code.clone(), max_errors)).collect();
+                lattice.range_checks = vec![None; self.set_fin.len()];
+                for msg in std::mem::take(&mut lattice.range_pending) {
+                    self.receive_range_check(msg);
+                }
+                let message = self.send_sum();
+                return message.or_else(|| self.range_checked())
+            }
+            _ => {}
+        }
+        // 协商出集合之前收到的检查值或者信标的份额之和，结果保存在 `res` 中
+        let message = self.send_sum();
+        self.flush_sums();
+        message
     }
 
+    /// 收到 set_fin 中所有 Dealer 的份额后，发送每个求值点上这些份额的和
+    /// 格上的密钥发送每个求值点上 A·s + e 的份额
+    /// set_fin 中有 Dealer 发给自己的份额无法使用时不会再收到它的份额，不发送份额之和而不是一直等待，
+    /// 自己只由其他节点的份额之和得到结果，没有密钥份额
+    fn send_sum(&mut self) -> Option<Message> {
+        if self.set_fin.iter().any(|d| self.disqualified.contains(d) || self.unusable.contains(d)) {
+            if !self.sent {
+                self.sent = true;
+                self.withheld = true;
+                println!("client_id:{} status:ADKG_NO_SHARE set:{:?}", self.id, self.set_fin);
+                return self.request_recovery()
+            }
+            return None
+        }
+        if !self.set_fin.iter().all(|d| self.shares.contains_key(d)) {
+            return None
+        }
+        self.sent = true;
+        match self.mode {
+            Mode::Triples(_) => self.send_message(vec![self.id], MessageType::TripleStart, vec![]),
+            Mode::Refresh { .. } | Mode::Reshare { .. } => self.send_check(),
+            Mode::Lattice(_) => self.send_range_check(),
+            Mode::Generate { .. } | Mode::Beacon(_) => {
+                let dealers = self.set_fin.clone();
+                Some(self.sums(&dealers))
+            }
+        }
+    }
+
+    /// 每个求值点上 `dealers` 的份额之和，格上的密钥为 A·s + e 的份额
+    fn sums(&mut self, dealers: &[usize]) -> Message {
+        let points = 0..self.allocation[self.id].len();
+        let sums: Vec<Mersenne61Ext> = match &mut self.mode {
+            Mode::Lattice(LatticeMode { lattice, decryptions, flooding, .. }) => {
+                let params = lattice.params();
+                let bits = 2 * params.eta() * params.dimension();
+                let count = 2 * bits + *decryptions * params.degree();
+                let (mut key_shares, mut sums) = (vec![], vec![]);
+                for j in points {
+                    let keys: Vec<_> = (0..count)
+                        .map(|k| {
+                            let shares: Vec<_> = dealers.iter().map(|d| self.shares[d][j][k].clone()).collect();
+                            Share::sum(&shares).value()
+                        })
+                        .collect();
+                    let (s, e) = (params.from_bits(&keys[..bits]), params.from_bits(&keys[bits..2 * bits]));
+                    sums.extend(lattice.public_key(&s, &e));
+                    key_shares.extend(s);
+                    flooding.push(keys[2 * bits..].to_vec());
+                }
+                self.key_shares = Some(key_shares);
+                sums
+            }
+            mode => {
+                // 批量生成时第 k 个密钥只用第 k 个秘密的份额
+                let batch = match mode {
+                    Mode::Generate { batch } => *batch,
+                    _ => 1,
+                };
+                let sums: Vec<_> = points
+                    .flat_map(|j| (0..batch).map(move |k| (j, k)))
+                    .map(|(j, k)| {
+                        let shares: Vec<_> = dealers.iter().map(|d| self.shares[d][j][k].clone()).collect();
+                        Share::sum(&shares).value()
+                    })
+                    .collect();
+                self.key_shares = Some(sums.clone());
+                sums
+            }
+        };
+        let mut msg = Message::send_message_with_addi(self.id, vec![], MessageType::SumAndRec, vec![], encode_values(&sums));
+        msg.signature = self.sign_sums(dealers, &sums);
+        msg
+    }
+
+    /// 随机信标中签名份额之和，签名同时覆盖 Dealer 集合和 Dealer 的承诺，其他模式不签名
+    fn sign_sums(&mut self, dealers: &[usize], sums: &[Mersenne61Ext]) -> Option<Signature> {
+        if !matches!(self.mode, Mode::Beacon(_)) {
+            return None
+        }
+        let commitments: Vec<_> = dealers.iter().map(|d| self.commitment(*d)).collect::<Option<_>>()?;
+        match &mut self.mode {
+            Mode::Beacon(beacon) => {
+                let message = BeaconTranscript::message(beacon.epoch, dealers, &commitments, self.id, sums);
+                beacon.signing.sign(&message)
+            }
+            _ => None,
+        }
+    }
+
+    /// 随机信标中只接受签名正确的份额之和并记录下来，其他模式不检查签名
+    /// `holding_sums` 保证此时已经收到 set_fin 中所有 Dealer 的承诺
+    fn accept_signed(&mut self, msg: &Message, values: &[Mersenne61Ext]) -> bool {
+        if !matches!(self.mode, Mode::Beacon(_)) {
+            return true
+        }
+        let commitments: Vec<_> = match self.set_fin.iter().map(|d| self.commitment(*d)).collect::<Option<_>>() {
+            Some(commitments) => commitments,
+            None => return false,
+        };
+        let beacon = match &mut self.mode {
+            Mode::Beacon(beacon) => beacon,
+            _ => return true,
+        };
+        let message = BeaconTranscript::message(beacon.epoch, &self.set_fin, &commitments, msg.sender_id, values);
+        match (&msg.signature, beacon.verifying.get(msg.sender_id)) {
+            (Some(signature), Some(key)) if key.verify(&message, signature) => {
+                beacon.signed.push(SignedSums {
+                    sender: msg.sender_id,
+                    sums: values.to_vec(),
+                    signature: signature.clone(),
+                });
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// 格上的密钥：公开自己的每个求值点上每个 Dealer 的比特检查值的份额，比特检查已经完成时直接发送份额之和
+    fn send_range_check(&mut self) -> Option<Message> {
+        let (params, decryptions) = match &self.mode {
+            Mode::Lattice(LatticeMode { accepted: Some(accepted), .. }) => {
+                let accepted = accepted.clone();
+                return Some(self.sums(&accepted))
+            }
+            Mode::Lattice(lattice) => (lattice.lattice.params(), lattice.decryptions),
+            _ => return None,
+        };
+        let bits = 4 * params.eta() * params.dimension();
+        let masks = bits + decryptions * params.degree();
+        let challenges: Vec<_> = self.set_fin.iter().map(|d| self.check_challenge(*d)).collect::<Option<_>>()?;
+        let mut values = vec![];
+        for (j, p) in self.allocation[self.id].iter().enumerate() {
+            let x = self.params.share_point(*p);
+            for (d, rho) in self.set_fin.iter().zip(&challenges) {
+                let shares = &self.shares[d][j];
+                let squares: Vec<_> = shares[..bits].iter().map(|a| a.value() * a.value() - a.value()).collect();
+                let mask = x * shares[masks].value() + x.pow(self.params.t()) * shares[masks + 1].value();
+                values.push(Self::combine(*rho, &squares) + mask);
+            }
+        }
+        println!("client_id:{} status:RANGE_CHECK", self.id);
+        Some(Message::send_message_with_addi(self.id, vec![], MessageType::RangeCheck, vec![], encode_values(&values)))
+    }
+
+    /// 收到比特检查值的份额，全部译码后排除秘密不为 0 的 Dealer，自己有全部份额时发送其余 Dealer 的份额之和
+    pub fn handle_range_check(&mut self, msg: Message) -> Option<Message> {
+        if !self.fin {
+            if let Mode::Lattice(lattice) = &mut self.mode {
+                lattice.range_pending.push(msg);
+            }
+            return None
+        }
+        self.receive_range_check(msg);
+        self.range_checked()
+    }
+
+    /// 把一条消息中每个求值点上每个 Dealer 的检查值交给对应的译码器，每个节点只接受一次
+    fn receive_range_check(&mut self, msg: Message) {
+        let sender = msg.sender_id;
+        let lattice = match &mut self.mode {
+            Mode::Lattice(lattice) => lattice,
+            _ => return,
+        };
+        if lattice.accepted.is_some() || is_invector(sender, &lattice.range_senders) || sender >= self.allocation.len() {
+            return
+        }
+        let width = lattice.range_checks.len();
+        let values = match decode_values(&msg.additional) {
+            Some(values) if values.len() == self.allocation[sender].len() * width => values,
+            _ => return,
+        };
+        lattice.range_senders.push(sender);
+        for (p, values) in self.allocation[sender].iter().zip(values.chunks(width)) {
+            for ((decoder, check), v) in lattice.range_decoders.iter_mut().zip(&mut lattice.range_checks).zip(values) {
+                if check.is_none() {
+                    *check = decoder.receive(*p, *v).map(|d| d.polynomial.evaluation_at(Mersenne61Ext::from_int(0)));
+                }
+            }
+        }
+    }
+
+    /// 全部检查值译码后确定接受的 Dealer；自己没有全部份额时不发送份额之和，处理之前收到的份额之和
+    fn range_checked(&mut self) -> Option<Message> {
+        let lattice = match &mut self.mode {
+            Mode::Lattice(lattice) if lattice.accepted.is_none() && self.fin => lattice,
+            _ => return None,
+        };
+        let checks: Vec<_> = lattice.range_checks.iter().cloned().collect::<Option<_>>()?;
+        let (accepted, excluded): (Vec<_>, Vec<_>) = self.set_fin.iter().zip(&checks).partition(|(_, c)| c.is_zero());
+        let accepted: Vec<_> = accepted.into_iter().map(|(d, _)| *d).collect();
+        let excluded: Vec<_> = excluded.into_iter().map(|(d, _)| *d).collect();
+        println!("client_id:{} status:RANGE_CHECKED excluded:{:?}", self.id, excluded);
+        lattice.accepted = Some(accepted.clone());
+        if self.set_fin.iter().all(|d| self.shares.contains_key(d)) {
+            return Some(self.sums(&accepted))
+        }
+        self.flush_sums();
+        None
+    }
+
+    /// 公开自己的每个求值点上每个 Dealer 的检查值 Σ ρ_d^(i+1)·s_i + m_d 的份额，而不是新的份额
+    /// s_i 为 Dealer 分发的密钥或旧份额，m_d 为最后一个秘密，即秘密为 0 的掩码，检查值与 s_i 的份额无关
+    fn send_check(&mut self) -> Option<Message> {
+        let mut values = vec![];
+        for j in 0..self.allocation[self.id].len() {
+            for d in &self.set_fin {
+                let (mask, shares) = self.shares[d][j].split_last().unwrap();
+                let values_of: Vec<_> = shares.iter().map(|s| s.value()).collect();
+                values.push(Self::combine(self.check_challenge(*d)?, &values_of) + mask.value());
+            }
+        }
+        println!("client_id:{} status:SHARE_CHECK", self.id);
+        Some(Message::send_message_with_addi(self.id, vec![], MessageType::SumAndRec, vec![], encode_values(&values)))
+    }
+
+    /// Σ ρ^(i+1)·values_i
+    fn combine(rho: Mersenne61Ext, values: &[Mersenne61Ext]) -> Mersenne61Ext {
+        let mut power = rho;
+        let mut sum = Mersenne61Ext::from_int(0);
+        for v in values {
+            sum += power * *v;
+            power *= rho;
+        }
+        sum
+    }
+
+    /// Dealer 的承诺：它对每个参与方的 `Transcript::digest` 按参与方排序后的哈希，没有收到 Dealer 的分发时为 None
+    fn commitment(&self, dealer: usize) -> Option<[u8; 32]> {
+        let mut commitments: Vec<_> = self.commitments.get(&dealer)?.iter().collect();
+        commitments.sort();
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(b"PQC-ADKG dealer commitment");
+        for (party, digest) in commitments {
+            hasher.update(&(*party as u64).to_le_bytes());
+            hasher.update(digest);
+        }
+        Some(hasher.finalize().into())
+    }
+
+    /// Dealer `dealer` 的检查值中的系数，由它对每个参与方的承诺得到，Dealer 分发之后才能确定
+    /// 还没有收到 Dealer 的分发时为 None
+    fn check_challenge(&self, dealer: usize) -> Option<Mersenne61Ext> {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(b"PQC-ADKG share check challenge");
+        hasher.update(&(dealer as u64).to_le_bytes());
+        hasher.update(&self.commitment(dealer)?);
+        let bytes = hasher.finalize();
+        let word = |i: usize| u64::from_le_bytes(bytes.as_bytes()[i..i + 8].try_into().unwrap());
+        Some(Mersenne61Ext::new(word(0), word(8)))
+    }
+
+    /// Dealer 的检查值的秘密应有的值：主动刷新时为 0，重新分享时由 Dealer 公开过的旧份额得到
+    /// Dealer 没有公开过旧份额或者还没有收到 Dealer 的分发时为 None
+    fn expected(&self, dealer: usize) -> Option<Mersenne61Ext> {
+        match &self.mode {
+            Mode::Refresh { .. } => Some(Mersenne61Ext::from_int(0)),
+            Mode::Reshare { old, published } => {
+                let published = published.get(dealer)?.as_ref()?;
+                if published.len() != old.committee().weight(dealer) {
+                    return None
+                }
+                Some(Self::combine(self.check_challenge(dealer)?, published))
+            }
+            _ => None,
+        }
+    }
+
+    /// 检查值全部译码后的结果：检查值的秘密与应有的值一致的 Dealer 被接受
+    /// 主动刷新时把它们的份额加到当前份额上，重新分享时以它们的旧份额的拉格朗日系数组合收到的份额
+    /// 自己缺少 set_fin 中某个 Dealer 的份额，或者重新分享时接受的旧份额不超过旧委员会的门限时没有新的份额
+    fn checked(&mut self, decoded: &[Decoded<Mersenne61Ext>], faulty: Vec<usize>) -> AdkgResult {
+        let points = self.params.secret_points();
+        let accepted: Vec<_> = self
+            .set_fin
+            .iter()
+            .zip(decoded)
+            .filter(|(d, decoded)| {
+                let expected = self.expected(**d);
+                points.iter().all(|e| Some(decoded.polynomial.evaluation_at(*e)) == expected)
+            })
+            .map(|(d, _)| *d)
+            .collect();
+        if self.set_fin.iter().all(|d| self.shares.contains_key(d)) {
+            let points = 0..self.allocation[self.id].len();
+            match &self.mode {
+                Mode::Refresh { previous } => {
+                    let key_shares = points
+                        .map(|j| {
+                            let shares: Vec<_> = accepted.iter().map(|d| self.shares[d][j][0].clone()).collect();
+                            previous[j] + Share::sum(&shares).value()
+                        })
+                        .collect();
+                    self.key_shares = Some(key_shares);
+                }
+                Mode::Reshare { old, .. } if old.committee().exceeds_threshold(&accepted) => {
+                    let coefficients = Self::coefficients(old, &accepted);
+                    let key_shares = points
+                        .map(|j| {
+                            let (mut shares, mut lambda) = (vec![], vec![]);
+                            for d in &accepted {
+                                shares.extend(self.shares[d][j].iter().take(coefficients[d].len()).cloned());
+                                lambda.extend(&coefficients[d]);
+                            }
+                            Share::linear_combination(&shares, &lambda).value()
+                        })
+                        .collect();
+                    self.key_shares = Some(key_shares);
+                }
+                _ => {}
+            }
+        }
+        AdkgResult {
+            id: self.id,
+            users: accepted,
+            sk: String::new(),
+            pk: String::new(),
+            faulty,
+            public_key: vec![],
+            keys: vec![],
+        }
+    }
+
+    /// 在线纠错：每收到一个节点的份额之和就尝试译码，能够确定正确的多项式时输出密钥，
+    /// 并给出份额与多项式不一致的节点
+    /// 主动刷新和重新分享时收到的是检查值，协商出 Dealer 集合之前先保存；格上的密钥在比特检查完成之前先保存；
+    /// 随机信标中份额之和的签名覆盖 Dealer 集合和它们的承诺，两者都确定之前先保存
     pub fn sum_and_rec(&mut self, msg: Message) -> Option<AdkgResult> {
+        if self.holding_sums() {
+            self.pending.push(msg);
+            return None
+        }
+        let res = self.flush_sums();
+        res.or_else(|| self.add_sum(msg))
+    }
 
-        self.hash_fin.insert(msg.sender_id, msg.additional.parse::<u64>().unwrap());
+    /// 份额之和或检查值还不能处理：检查值的系数和信标的签名由 set_fin 中每个 Dealer 的承诺得到，
+    /// 协商出 Dealer 集合并收到其中所有 Dealer 的分发之前不能检查；格上的密钥在比特检查完成之前不能译码
+    fn holding_sums(&self) -> bool {
+        let committed = self.fin && self.set_fin.iter().all(|d| self.commitments.contains_key(d));
+        match &self.mode {
+            Mode::Refresh { .. } | Mode::Reshare { .. } | Mode::Beacon(_) => !committed,
+            Mode::Lattice(lattice) => lattice.accepted.is_none(),
+            Mode::Generate { .. } | Mode::Triples(_) => false,
+        }
+    }
+
+    /// 处理之前保存的份额之和或检查值，还不能处理时继续保存
+    fn flush_sums(&mut self) -> Option<AdkgResult> {
+        if self.holding_sums() {
+            return None
+        }
+        let mut res = None;
+        for msg in std::mem::take(&mut self.pending) {
+            res = res.or(self.add_sum(msg));
+        }
+        res
+    }
+
+    fn add_sum(&mut self, msg: Message) -> Option<AdkgResult> {
+        if msg.sender_id >= self.allocation.len() {
+            return None
+        }
+        if self.res.is_some() || self.hash_fin.contains_key(&msg.sender_id) {
+            return None
+        }
+        let width = self.decoders.len();
+        let values = match decode_values(&msg.additional) {
+            Some(values) if values.len() == self.allocation[msg.sender_id].len() * width => values,
+            _ => return None,
+        };
+        if !self.accept_signed(&msg, &values) {
+            return None
+        }
+        self.hash_fin.insert(msg.sender_id, values.first().map_or(0, |v| v.get_real()));
         // println!("sum_and_rec, {}, {:?}, {:?}", self.id, self.hash_fin.keys(),self.set_fin);
-        let n_f: u64 = (self.n - self.f) as u64; 
-        if self.hash_fin.len() == self.n - self.f {
-            let mut sum: u64 = 0;
-            for (_, &v) in self.hash_fin.iter() {
-                sum += v / n_f;
-            }
-            return Some(AdkgResult {
-                id: self.id,
-                users: self.set_fin.clone(),
-                sk: self.avss.reconstruct().get_real().to_string().clone(),
-                pk: sum.to_string(),
-            })
+        // 节点公开的是 set_fin 中所有 Dealer 的份额之和，只有取值，求值点由发送者的分配确定
+        for (p, values) in self.allocation[msg.sender_id].iter().zip(values.chunks(width)) {
+            for ((decoder, decoded), v) in self.decoders.iter_mut().zip(&mut self.decoded).zip(values) {
+                if decoded.is_none() {
+                    *decoded = decoder.receive(*p, *v);
+                }
+            }
         }
-        None
+        let decoded: Vec<_> = self.decoded.iter().cloned().collect::<Option<_>>()?;
+
+        let mut faulty: Vec<usize> = (0..self.allocation.len())
+            .filter(|i| self.allocation[*i].iter().any(|p| decoded.iter().any(|d| d.errors.contains(p))))
+            .collect();
+        faulty.sort();
+        let res = match &self.mode {
+            Mode::Refresh { .. } | Mode::Reshare { .. } => self.checked(&decoded, faulty),
+            // 格上的密钥只重构公钥，私钥 s 只以份额的形式存在：Dealer 分发的份额和证明都加密给求值点的所有者
+            Mode::Lattice(lattice) => {
+                let secrets: Vec<_> = decoded
+                    .iter()
+                    .map(|d| d.polynomial.evaluation_at(Mersenne61Ext::from_int(0)))
+                    .collect();
+                AdkgResult {
+                    id: self.id,
+                    users: lattice.accepted.clone().unwrap_or_else(|| self.set_fin.clone()),
+                    sk: String::new(),
+                    pk: blake3::hash(&as_bytes_vec(&secrets)).to_hex().to_string(),
+                    faulty,
+                    public_key: secrets,
+                    keys: vec![],
+                }
+            }
+            _ => {
+                // 打包时秘密位于多项式的 packing 个点上，每个点上为一个独立的密钥，批量生成时依次排列每个多项式的密钥
+                let points = self.params.secret_points();
+                let keys: Vec<_> = decoded
+                    .iter()
+                    .flat_map(|d| points.iter().map(|e| d.polynomial.evaluation_at(*e)))
+                    .collect();
+                AdkgResult {
+                    id: self.id,
+                    users: self.set_fin.clone(),
+                    sk: keys[0].get_real().to_string(),
+                    pk: blake3::hash(&as_bytes_vec(&keys)).to_hex().to_string(),
+                    faulty,
+                    public_key: vec![],
+                    keys,
+                }
+            }
+        };
+        self.res = Some(res.clone());
+        Some(res)
     }
 
+    /// set_fin 中的 Dealer 没有把份额发给自己时，请求同一列的其他求值点的所有者公开它们的份额，`msg_content` 为这些 Dealer
+    /// 有 Dealer 的资格被取消或者生成乘法三元组时不恢复
+    fn request_recovery(&mut self) -> Option<Message> {
+        if matches!(self.mode, Mode::Triples(_)) || self.set_fin.iter().any(|d| self.disqualified.contains(d)) {
+            return None
+        }
+        let dealers: Vec<_> = self.set_fin.iter().filter(|d| self.unusable.contains(d)).copied().collect();
+        println!("client_id:{} status:RECOVER_REQUEST dealers:{:?}", self.id, dealers);
+        self.send_message(vec![], MessageType::RecoverRequest, dealers)
+    }
 
-}
+    /// 收到恢复请求，还没有收到 Dealer 的分发时先保存
+    pub fn handle_recover_request(&mut self, msg: Message) -> Option<Message> {
+        if msg.sender_id >= self.allocation.len() || msg.sender_id == self.id {
+            return None
+        }
+        for dealer in msg.msg_content {
+            if !self.recover_requests.contains(&(msg.sender_id, dealer)) {
+                self.recover_requests.push((msg.sender_id, dealer));
+            }
+        }
+        self.answer_recovery()
+    }
+
+    /// 回复能够回复的恢复请求：公开自己的求值点上 Dealer 的证明，加密给请求者
+    /// 同一列的 t + 1 个份额足以重构 Dealer 的秘密，因此只在 Dealer 广播的消息中确实没有发给请求者的信封时回复，
+    /// 这样的 Dealer 一定是恶意的，它的秘密不需要保护；信封存在而请求者声称无法解密时无法确认，不回复
+    /// `msg_content` 依次为每个请求者、Dealer 和证明的个数，`proofs` 中依次为这些证明
+    fn answer_recovery(&mut self) -> Option<Message> {
+        let log_n = self.params.log_n();
+        let (mut content, mut proofs) = (vec![], vec![]);
+        let mut waiting = vec![];
+        for (requester, dealer) in std::mem::take(&mut self.recover_requests) {
+            let (opened, envelopes) = match (self.opened.get(&dealer), self.envelopes.get(&dealer)) {
+                (Some(opened), Some(envelopes)) => (opened, envelopes),
+                _ => {
+                    waiting.push((requester, dealer));
+                    continue
+                }
+            };
+            let omitted = self.allocation[requester].iter().any(|p| !envelopes.contains_key(&(p << log_n)));
+            if !omitted {
+                continue
+            }
+            content.extend([requester, dealer, opened.len()]);
+            for proof in opened {
+                let mut proof = proof.clone();
+                if let Some(ek) = self.keys.get(requester) {
+                    seal_proof(dealer, &mut proof, ek);
+                }
+                proofs.push(proof);
+            }
+        }
+        self.recover_requests = waiting;
+        if content.is_empty() {
+            return None
+        }
+        let mut message = self.send_message(vec![], MessageType::RecoverResponse, content)?;
+        message.proofs = Arc::new(proofs);
+        Some(message)
+    }
 
-/// 计算log2(n)
-fn log_2_n (n: usize) -> usize {
-    let mut i = 0;
-    let mut tmp = n;
-    while tmp > 1 {
-        tmp = tmp >> 1;
-        i += 1;
+    /// 收到其他节点公开的份额，只处理发给自己的、与 Dealer 广播的承诺一致并通过验证的证明
+    /// 同一列有 t + 1 个求值点的份额时插值得到自己的求值点上的份额，之后发送之前没有发送的份额之和
+    pub fn handle_recover_response(&mut self, msg: Message) -> Option<Message> {
+        let sender = msg.sender_id;
+        if sender >= self.allocation.len() {
+            return None
+        }
+        let log_n = self.params.log_n();
+        let (mut content, mut proofs) = (&msg.msg_content[..], &msg.proofs[..]);
+        while content.len() >= 3 && proofs.len() >= content[2] {
+            let (requester, dealer, count) = (content[0], content[1], content[2]);
+            let received = &proofs[..count];
+            content = &content[3..];
+            proofs = &proofs[count..];
+            if requester != self.id || self.shares.contains_key(&dealer) || !self.unusable.contains(&dealer) {
+                continue
+            }
+            let secrets = self.secrets(dealer);
+            let max = match secrets.iter().max() {
+                Some(max) => *max,
+                None => continue,
+            };
+            for proof in received {
+                let point = proof.party >> log_n;
+                let committed = self.commitments.get(&dealer).and_then(|c| c.get(&proof.party));
+                if proof.party != point << log_n
+                    || !self.allocation[sender].contains(&point)
+                    || committed != Some(&proof.transcript.digest())
+                {
+                    continue
+                }
+                let opened = match &self.decapsulation {
+                    Some(dk) => open_proof(&self.params, dealer, proof, dk),
+                    None => Some(proof.clone()),
+                };
+                let opened = match opened {
+                    Some(opened) if opened.shares.len() > max && opened.verify(&self.params) => opened,
+                    _ => continue,
+                };
+                let recovery = self.recovery.entry(dealer).or_default();
+                if recovery.iter().all(|(p, _)| *p != point) {
+                    recovery.push((point, secrets.iter().map(|k| opened.share_of(&self.params, *k)).collect()));
+                }
+            }
+            self.recover(dealer);
+        }
+        let recovered = !self.set_fin.iter().any(|d| self.unusable.contains(d));
+        if self.withheld && recovered {
+            self.withheld = false;
+            self.sent = false;
+            return self.send_sum()
+        }
+        None
     }
-    if 1<<i == n {
-        i
-    } else {
-        i+1
+
+    /// 由同一列的至少 t + 1 个份额插值得到自己的每个求值点上 Dealer 的份额，份额不足或者不一致时什么都不做
+    fn recover(&mut self, dealer: usize) {
+        let t = self.params.t();
+        let recovery = match self.recovery.get(&dealer) {
+            Some(recovery) if recovery.len() > t => recovery,
+            _ => return,
+        };
+        let secrets = self.secrets(dealer);
+        let mut shares = vec![];
+        for p in &self.allocation[self.id] {
+            let values: Result<Vec<_>, _> = (0..secrets.len())
+                .map(|k| {
+                    let points: Vec<_> = recovery.iter().map(|(q, v)| vec![self.params.share_point(*q), v[k]]).collect();
+                    Polynomial::reconstruct_at(&points, t, self.params.share_point(*p))
+                })
+                .collect();
+            match values {
+                Ok(values) => shares.push(
+                    values.into_iter().map(|v| Share::dealt(Domain::new(&self.params), *p, v, dealer)).collect(),
+                ),
+                Err(_) => return,
+            }
+        }
+        println!("client_id:{} status:RECOVERED dealer:{}", self.id, dealer);
+        self.shares.insert(dealer, shares);
+        self.unusable.retain(|d| *d != dealer);
+        self.recovery.remove(&dealer);
     }
+
+    /// 已经为自己的提议签名的参与者
+    fn signers(&self) -> Vec<usize> {
+        self.set_sig.iter().map(|(id, _)| *id).collect()
+    }
+
+}
+
+/// 节点的运行模式，每种模式只保存自己用到的状态
+enum Mode {
+    /// 生成 `batch` 个独立的密钥，每个 Dealer 批量分发同样多的秘密
+    Generate { batch: usize },
+    /// 主动刷新，`previous` 为刷新前自己的求值点上的密钥份额
+    Refresh { previous: Vec<Mersenne61Ext> },
+    /// 重新分享，`old` 为旧委员会的参数，Dealer 为旧委员会的节点；`published` 为旧委员会生成密钥时
+    /// 每个节点在 SUM_AND_REC 中公开的份额，用于检查 Dealer 分发的是自己的旧份额
+    Reshare {
+        old: ProtocolParams<Mersenne61Ext>,
+        published: Vec<Option<Vec<Mersenne61Ext>>>,
+    },
+    Lattice(LatticeMode),
+    Beacon(BeaconMode),
+    Triples(TripleMode),
+}
+
+/// 生成格上的密钥
+struct LatticeMode {
+    /// 公开矩阵
+    lattice: Lattice,
+    decryptions: usize,
+    /// 自己的每个求值点上淹没噪声之和的份额，每次解密 degree 个
+    flooding: Vec<Vec<Mersenne61Ext>>,
+    /// 每个 Dealer 的比特检查值的 2t 次译码器和译码出的秘密，以及发送过检查值的节点
+    range_decoders: Vec<OnlineDecoder<Mersenne61Ext>>,
+    range_checks: Vec<Option<Mersenne61Ext>>,
+    range_senders: Vec<usize>,
+    /// 协商出 Dealer 集合之前收到的比特检查值
+    range_pending: Vec<Message>,
+    /// 比特检查通过的 Dealer，密钥只由它们的份额组成
+    accepted: Option<Vec<usize>>,
+}
+
+/// 随机信标的一个 epoch
+struct BeaconMode {
+    epoch: u64,
+    /// 自己这个 epoch 的一次性签名密钥和每个节点的验证密钥
+    signing: SigningKey,
+    verifying: Vec<VerifyingKey>,
+    /// 收到的经过签名的份额之和，按收到的顺序排列
+    signed: Vec<SignedSums>,
+}
+
+/// 生成乘法三元组
+struct TripleMode {
+    /// 门限为 2t 的参数，Dealer 还以它分发 r 和 p
+    double: ProtocolParams<Mersenne61Ext>,
+    count: usize,
+    /// 门限 2t 的分发的承诺和信封
+    commitments: HashMap<usize, HashMap<usize, [u8; MERKLE_ROOT_SIZE]>>,
+    envelopes: HashMap<usize, HashMap<usize, Envelope>>,
+    /// 每个 Dealer 以门限 2t 分发的份额，以及检查两次分发是否一致的挑战
+    shares: HashMap<usize, Vec<Vec<Share<Mersenne61Ext>>>>,
+    challenges: HashMap<usize, Mersenne61Ext>,
+}
+
+/// Dealer 发给自己的求值点的证明的检查结果
+enum Received {
+    /// 解密后的证明通过验证
+    Valid(ShareProof<Mersenne61Ext>),
+    /// 解密后的证明无法通过验证，投诉时公开收到的证明和解密信封时得到的随机数
+    Invalid(ShareProof<Mersenne61Ext>, [u8; 32]),
+    /// 没有份额或者无法解密，其他节点无法确认，不投诉
+    Unusable,
 }
 
 #[cfg(test)]
 mod tests {
-    fn log_2_n (n: usize) -> usize {
-        let mut i = 0;
-        let mut tmp = n;
-        while tmp > 1 {
-            tmp = tmp >> 1;
-            i += 1;
-        }
-        if 1<<i == n {
-            i
-        } else {
-            i+1
+    use super::AdkgNode;
+    use crate::client::avss::{open_proof, seal_proof, AvssNode, COIN_SECRET, KEY_SECRET, SHARE_KEM};
+    use crate::msg::message::{decode_values, encode_values, Message, MessageType};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::sync::Arc;
+    use util::algebra::field::{as_bytes_vec, Field};
+    use util::algebra::field::mersenne61_ext::Mersenne61Ext;
+    use util::algebra::lattice::{centered, Lattice, LatticeParams};
+    use util::algebra::polynomial::{MultilinearPolynomial, Polynomial};
+    use util::committee::Committee;
+    use util::params::ProtocolParams;
+    use util::signature;
+    use util::SECURITY_BITS;
+
+    #[test]
+    fn online_error_correction() {
+        let params = ProtocolParams::new(&Committee::uniform(7, 2, 2), SECURITY_BITS);
+        let avss = Rc::new(RefCell::new(AvssNode::new(0, &params)));
+        let mut node = AdkgNode::new(0, 1, &params, avss);
+
+        // 节点 1 和 4 发送错误的份额之和，其余节点的份额位于同一个 t 次多项式上
+        let poly = Polynomial::random_polynomial(3);
+        // 不在委员会中的发送者被忽略
+        let v = poly.evaluation_at(params.share_point(0));
+        let msg = Message::send_message_with_addi(7, vec![], MessageType::SumAndRec, vec![], encode_values(&[v]));
+        assert!(node.sum_and_rec(msg).is_none());
+        let mut res = None;
+        for id in [1, 4, 0, 2, 3, 5, 6] {
+            let mut v = poly.evaluation_at(params.share_point(id));
+            if id == 1 || id == 4 {
+                v += Mersenne61Ext::from_int(1);
+            }
+            let msg = Message::send_message_with_addi(id, vec![], MessageType::SumAndRec, vec![], encode_values(&[v]));
+            let r = node.sum_and_rec(msg);
+            // 至少收到 t + 1 + f 个一致的份额之后才能输出
+            assert_eq!(r.is_some(), id == 6);
+            res = res.or(r);
         }
+        let res = res.unwrap();
+        assert_eq!(res.sk, poly.coefficients()[0].get_real().to_string());
+        assert_eq!(res.pk, blake3::hash(&as_bytes_vec(&[poly.coefficients()[0]])).to_hex().to_string());
+        assert_eq!(res.faulty, vec![1, 4]);
     }
 
+    #[test]
+    #[should_panic(expected = "must have n >= t + 2f + 1")]
+    fn max_errors() {
+        // 总是纠正 f 个错误，委员会太小时拒绝而不是降低纠错能力
+        assert_eq!(AdkgNode::max_errors(&Committee::uniform(7, 2, 2)), 2);
+        AdkgNode::max_errors(&Committee::uniform(7, 2, 3));
+    }
 
     #[test]
-    fn t() {
-        for i in 3..70 {
-            println!("{}: {}", i, log_2_n(i));
+    fn complaint_disqualifies_dealer() {
+        let params = ProtocolParams::new(&Committee::uniform(7, 2, 2), SECURITY_BITS);
+        let (keys, dks): (Vec<_>, Vec<_>) = (0..7).map(|_| SHARE_KEM.keygen()).unzip();
+        let avss = |id| Rc::new(RefCell::new(AvssNode::new(id, &params)));
+        let node = |id: usize| {
+            let mut node = AdkgNode::new(id, 1, &params, avss(id));
+            node.set_encryption(dks[id].clone(), &keys);
+            node
+        };
+        // 求值点 i 属于节点 i，第 8 个求值点不属于任何节点
+        let seal = |dealer, mut msg: Message| {
+            for (i, proof) in Arc::make_mut(&mut msg.proofs).iter_mut().enumerate() {
+                match keys.get(i) {
+                    Some(ek) => seal_proof(dealer, proof, ek),
+                    None => proof.shares.clear(),
+                }
+            }
+            msg
+        };
+        let deal = |id| seal(id, avss(id).borrow_mut().send_and_verify(MessageType::AdkgAvssFin).unwrap());
+        let (mut node0, mut other) = (node(0), node(1));
+
+        // Dealer 3 加密给节点 0 的份额是错误的，节点 0 公开解密信封的随机数并广播投诉
+        let mut msg = avss(3).borrow_mut().send_and_verify(MessageType::AdkgAvssFin).unwrap();
+        let proofs = Arc::make_mut(&mut msg.proofs);
+        let mut coefficients = proofs[0].shares[KEY_SECRET].coefficients().clone();
+        coefficients[0] += Mersenne61Ext::from_int(1);
+        proofs[0].shares[KEY_SECRET] = MultilinearPolynomial::new(coefficients);
+        let msg = seal(3, msg);
+        let complaint = node0.handle_share_fin(msg.clone()).unwrap();
+        assert_eq!(complaint.msg_type, MessageType::AdkgComplaint);
+        assert_eq!(node0.disqualified(), &vec![3]);
+
+        // 协商出的集合仍然包含 Dealer 3 时节点 0 不发送份额之和，而不是一直等待
+        for id in [2, 4] {
+            node0.handle_share_fin(deal(id));
+        }
+        node0.handle_prop(Message::send_message(5, vec![], MessageType::AdkgProp, vec![2, 3, 4]).unwrap());
+        assert!(node0.handle_vaba_fin(Message::send_message(5, vec![], MessageType::VabaFin, vec![5]).unwrap()).is_none());
+        assert!(node0.sent && node0.key_shares().is_none());
+
+        // 节点 1 先收到投诉，收到 Dealer 的承诺后确认投诉成立
+        assert!(other.handle_complaint(complaint.clone()).is_none());
+        assert!(other.disqualified().is_empty());
+        assert!(other.handle_share_fin(msg.clone()).is_none());
+        assert_eq!(other.disqualified(), &vec![3]);
+        assert!(!other.set_dealer.contains(&3));
+
+        // 之后不再接受 Dealer 3 的份额，也不为包含它的提议或者太小的提议签名
+        for id in [2, 4, 5] {
+            other.handle_share_fin(deal(id));
+        }
+        assert!(other.handle_share_fin(msg).is_none());
+        assert_eq!(other.set_dealer, vec![2, 4, 5]);
+        for set in [vec![2, 3, 4], vec![2, 4]] {
+            let prop = Message::send_message(5, vec![], MessageType::AdkgProp, set).unwrap();
+            assert!(other.handle_prop(prop).is_none());
+        }
+        let prop = Message::send_message(5, vec![], MessageType::AdkgProp, vec![2, 4, 5]).unwrap();
+        assert!(other.handle_prop(prop).is_some());
+
+        // 对诚实 Dealer 4 的投诉不成立：投诉者不能换掉份额，也不能换成自己加密的错误份额
+        let mut honest = node(2);
+        let msg = deal(4);
+        honest.handle_share_fin(msg.clone());
+        let mut replayed = complaint.clone();
+        replayed.msg_content = vec![4];
+        replayed.proofs = Arc::new(vec![msg.proofs[0].clone()]);
+        honest.handle_complaint(replayed);
+        let mut tampered = complaint.clone();
+        tampered.msg_content = vec![4];
+        let mut proof = msg.proofs[0].clone();
+        proof.shares = vec![MultilinearPolynomial::random_polynomial(params.terminate_round())];
+        seal_proof(4, &mut proof, &keys[0]);
+        tampered.additional = hex::encode(proof.sealed.as_ref().unwrap().reveal(&SHARE_KEM, &dks[0]).unwrap());
+        tampered.proofs = Arc::new(vec![proof]);
+        honest.handle_complaint(tampered);
+        assert!(honest.disqualified().is_empty());
+    }
+
+    #[test]
+    fn double_dealing_complaint() {
+        let params = ProtocolParams::new(&Committee::uniform(5, 1, 1), SECURITY_BITS);
+        let double = params.clone().with_threshold(2 * params.t());
+        let (keys, dks): (Vec<_>, Vec<_>) = (0..5).map(|_| SHARE_KEM.keygen()).unzip();
+        let avss = |id| Rc::new(RefCell::new(AvssNode::triples(id, &params, &double, 1)));
+        let node = |id: usize| {
+            let mut node = AdkgNode::triples(id, 1, &params, avss(id), &double, 1);
+            node.set_encryption(dks[id].clone(), &keys);
+            node
+        };
+        // 前 8 个证明属于门限 t 的分发，后 8 个属于门限 2t 的分发，求值点 i 属于节点 i
+        let seal = |dealer, mut msg: Message| {
+            for (i, proof) in Arc::make_mut(&mut msg.proofs).iter_mut().enumerate() {
+                match keys.get(i % 8) {
+                    Some(ek) => seal_proof(dealer, proof, ek),
+                    None => proof.shares.clear(),
+                }
+            }
+            msg
+        };
+
+        // Dealer 3 以门限 2t 加密给节点 0 的份额是错误的，节点 0 投诉的是门限 2t 的分发
+        let mut msg = avss(3).borrow_mut().send_and_verify(MessageType::AdkgAvssFin).unwrap();
+        let proofs = Arc::make_mut(&mut msg.proofs);
+        let mut coefficients = proofs[8].shares[0].coefficients().clone();
+        coefficients[0] += Mersenne61Ext::from_int(1);
+        proofs[8].shares[0] = MultilinearPolynomial::new(coefficients);
+        let msg = seal(3, msg);
+        let mut node0 = node(0);
+        let complaint = node0.handle_share_fin(msg.clone()).unwrap();
+        assert_eq!(complaint.msg_type, MessageType::AdkgComplaint);
+        assert_eq!(complaint.msg_content, vec![3, 1]);
+        assert_eq!(node0.disqualified(), &vec![3]);
+
+        // 其它节点检查投诉后同样取消 Dealer 3 的资格
+        let mut other = node(1);
+        assert!(other.handle_share_fin(msg.clone()).is_none());
+        assert!(other.handle_complaint(complaint.clone()).is_none());
+        assert_eq!(other.disqualified(), &vec![3]);
+
+        // 对诚实 Dealer 4 的两次分发重放投诉都不成立
+        let mut honest = node(2);
+        let msg = seal(4, avss(4).borrow_mut().send_and_verify(MessageType::AdkgAvssFin).unwrap());
+        honest.handle_share_fin(msg.clone());
+        for (content, proof) in [(vec![4, 1], &msg.proofs[8]), (vec![4], &msg.proofs[0])] {
+            let mut replayed = complaint.clone();
+            replayed.msg_content = content;
+            replayed.proofs = Arc::new(vec![proof.clone()]);
+            honest.handle_complaint(replayed);
+        }
+        assert!(honest.disqualified().is_empty());
+    }
+
+    #[test]
+    fn reshare() {
+        let old = ProtocolParams::new(&Committee::uniform(7, 2, 2), SECURITY_BITS);
+        let new = ProtocolParams::new(&Committee::uniform(5, 1, 2), SECURITY_BITS);
+        let key: Polynomial<Mersenne61Ext> = Polynomial::random_polynomial(old.t() + 1);
+        let published: Vec<_> = (0..7).map(|i| Some(vec![key.evaluation_at(old.share_point(i))])).collect();
+
+        // 旧委员会中的节点 2 到 6 分发自己的份额，节点 4 分发的不是自己公开过的份额
+        let messages: Vec<_> = (2..7)
+            .map(|i| {
+                let mut share = key.evaluation_at(old.share_point(i));
+                if i == 4 {
+                    share += Mersenne61Ext::from_int(1);
+                }
+                AvssNode::reshare(i, &new, &[share]).send_and_verify(MessageType::AdkgAvssFin).unwrap()
+            })
+            .collect();
+        let mut nodes: Vec<_> = (0..5)
+            .map(|j| {
+                let avss = Rc::new(RefCell::new(AvssNode::new(j, &new)));
+                let mut node = AdkgNode::reshare(j, 1, &new, avss, &old, published.clone());
+                for msg in &messages {
+                    node.handle_share_fin(msg.clone());
+                }
+                // 旧份额的个数要超过旧委员会的 t + f
+                let prop = |sender, set| Message::send_message(sender, vec![], MessageType::AdkgProp, set).unwrap();
+                assert!(node.handle_prop(prop(1, vec![3, 4, 5, 6])).is_none());
+                assert!(node.handle_prop(prop(0, vec![2, 3, 4, 5, 6])).is_some());
+                node
+            })
+            .collect();
+        let checks: Vec<_> = nodes
+            .iter_mut()
+            .map(|node| node.handle_vaba_fin(Message::send_message(0, vec![], MessageType::VabaFin, vec![0]).unwrap()).unwrap())
+            .collect();
+        for node in &mut nodes {
+            let res = checks.iter().fold(None, |res, msg| res.or(node.sum_and_rec(msg.clone()))).unwrap();
+            assert_eq!(res.users, vec![2, 3, 5, 6]);
+        }
+
+        // 新委员会的份额是同一个组密钥的 t' 次多项式的取值，且与旧份额无关，公开的检查值不是新的份额
+        let shares: Vec<_> = nodes
+            .iter()
+            .enumerate()
+            .map(|(j, node)| vec![new.share_point(j), node.key_shares().unwrap()[0]])
+            .collect();
+        assert_eq!(Polynomial::reconstruct(&shares, new.t()), Ok(key.coefficients()[0]));
+        assert_ne!(shares[2][1], key.evaluation_at(old.share_point(2)));
+        for (share, msg) in shares.iter().zip(&checks) {
+            assert!(!decode_values(&msg.additional).unwrap().contains(&share[1]));
+        }
+    }
+
+    #[test]
+    fn refresh_excludes_nonzero_dealer() {
+        let params = ProtocolParams::new(&Committee::uniform(4, 1, 1), SECURITY_BITS);
+        let key: Polynomial<Mersenne61Ext> = Polynomial::random_polynomial(params.t() + 1);
+        let (zero, one) = (Mersenne61Ext::from_int(0), Mersenne61Ext::from_int(1));
+
+        // Dealer 2 分发的多项式的秘密不是 0，掩码的秘密为 0
+        let mut messages: Vec<_> = (0..2)
+            .map(|i| AvssNode::refresh(i, &params).send_and_verify(MessageType::AdkgAvssFin).unwrap())
+            .collect();
+        let mut cheating = AvssNode::with_secrets(2, &params, &[one, Mersenne61Ext::random_element(), zero]);
+        messages.push(cheating.send_and_verify(MessageType::AdkgAvssFin).unwrap());
+        let mut nodes: Vec<_> = (0..4)
+            .map(|j| {
+                let avss = Rc::new(RefCell::new(AvssNode::new(j, &params)));
+                let previous = vec![key.evaluation_at(params.share_point(j))];
+                let mut node = AdkgNode::refresh(j, 1, &params, avss, previous);
+                for msg in &messages {
+                    node.handle_share_fin(msg.clone());
+                }
+                node.handle_prop(Message::send_message(0, vec![], MessageType::AdkgProp, vec![0, 1, 2]).unwrap());
+                node
+            })
+            .collect();
+        let fin = || Message::send_message(0, vec![], MessageType::VabaFin, vec![0]).unwrap();
+
+        // 节点 0 在协商出 Dealer 集合之前收到其他节点的检查值，协商出集合之后再处理
+        let checks: Vec<_> = (1..4).map(|j| nodes[j].handle_vaba_fin(fin()).unwrap()).collect();
+        for msg in &checks {
+            assert!(nodes[0].sum_and_rec(msg.clone()).is_none());
+        }
+        let checks: Vec<_> = std::iter::once(nodes[0].handle_vaba_fin(fin()).unwrap()).chain(checks).collect();
+        let res = nodes[0].res.clone().unwrap();
+        assert_eq!(res.users, vec![0, 1]);
+        assert!(res.faulty.is_empty() && res.keys.is_empty() && res.sk.is_empty());
+        for node in &mut nodes[1..] {
+            let res = checks.iter().fold(None, |res, msg| res.or(node.sum_and_rec(msg.clone()))).unwrap();
+            assert_eq!(res.users, vec![0, 1]);
+        }
+
+        // 节点 3 在收到 Dealer 2 的分发之前收到全部检查值，先保存，收到分发之后再检查
+        let avss = Rc::new(RefCell::new(AvssNode::new(3, &params)));
+        let mut late = AdkgNode::refresh(3, 1, &params, avss, vec![key.evaluation_at(params.share_point(3))]);
+        for msg in &messages[..2] {
+            late.handle_share_fin(msg.clone());
+        }
+        late.handle_prop(Message::send_message(0, vec![], MessageType::AdkgProp, vec![0, 1, 2]).unwrap());
+        assert!(late.handle_vaba_fin(fin()).is_none());
+        for msg in &checks {
+            assert!(late.sum_and_rec(msg.clone()).is_none());
+        }
+        assert!(late.res.is_none());
+        late.handle_share_fin(messages[2].clone());
+        assert_eq!(late.res.clone().unwrap().users, vec![0, 1]);
+
+        // 公开的检查值不是新的份额，新的份额仍然是同一个组密钥的份额
+        let shares: Vec<_> = nodes
+            .iter()
+            .enumerate()
+            .map(|(j, node)| vec![params.share_point(j), node.key_shares().unwrap()[0]])
+            .collect();
+        assert_eq!(Polynomial::reconstruct(&shares, params.t()), Ok(key.coefficients()[0]));
+        for (j, msg) in checks.iter().enumerate() {
+            let values = decode_values(&msg.additional).unwrap();
+            assert_eq!(values.len(), 3);
+            assert!(!values.contains(&shares[j][1]));
+            assert_ne!(shares[j][1], key.evaluation_at(params.share_point(j)));
+        }
+    }
+
+    #[test]
+    fn refresh_stays_private() {
+        // 加密分发时刷新的广播中只有 Dealer 的承诺和检查值，旁观者得不到刷新前后的任何份额
+        let params = ProtocolParams::new(&Committee::uniform(4, 1, 1), SECURITY_BITS);
+        let key: Polynomial<Mersenne61Ext> = Polynomial::random_polynomial(params.t() + 1);
+        let (keys, dks): (Vec<_>, Vec<_>) = (0..4).map(|_| SHARE_KEM.keygen()).unzip();
+        let (_, outsider) = SHARE_KEM.keygen();
+        let dealings: Vec<_> = (0..3)
+            .map(|i| {
+                let mut avss = AvssNode::refresh(i, &params);
+                avss.seal_for(&keys);
+                avss.send_and_verify(MessageType::AdkgAvssFin).unwrap()
+            })
+            .collect();
+        let previous: Vec<_> = (0..4).map(|j| key.evaluation_at(params.share_point(j))).collect();
+        let mut nodes: Vec<_> = (0..4)
+            .map(|j| {
+                let avss = Rc::new(RefCell::new(AvssNode::new(j, &params)));
+                let mut node = AdkgNode::refresh(j, 1, &params, avss, vec![previous[j]]);
+                node.set_encryption(dks[j].clone(), &keys);
+                for msg in &dealings {
+                    node.handle_share_fin(msg.clone());
+                }
+                node.handle_prop(Message::send_message(0, vec![], MessageType::AdkgProp, vec![0, 1, 2]).unwrap());
+                node
+            })
+            .collect();
+        let checks: Vec<_> = nodes
+            .iter_mut()
+            .map(|node| node.handle_vaba_fin(Message::send_message(0, vec![], MessageType::VabaFin, vec![0]).unwrap()).unwrap())
+            .collect();
+        for node in &mut nodes {
+            let res = checks.iter().fold(None, |res, msg| res.or(node.sum_and_rec(msg.clone()))).unwrap();
+            assert_eq!(res.users, vec![0, 1, 2]);
+        }
+
+        for (dealer, msg) in dealings.iter().enumerate() {
+            for proof in msg.proofs.iter() {
+                assert!(proof.shares.is_empty() && proof.folding_proofs.is_empty() && proof.function_proofs.is_empty());
+                assert!(proof.transcript.final_value.is_none());
+                assert!(open_proof(&params, dealer, proof, &outsider).is_none());
+            }
+        }
+        let published: Vec<_> = checks.iter().flat_map(|msg| decode_values(&msg.additional).unwrap()).collect();
+        let refreshed: Vec<_> = nodes.iter().map(|node| node.key_shares().unwrap()[0]).collect();
+        for (old, new) in previous.iter().zip(&refreshed) {
+            assert_ne!(old, new);
+            assert!(!published.contains(old) && !published.contains(new));
+        }
+        let shares: Vec<_> = (0..4).map(|j| vec![params.share_point(j), refreshed[j]]).collect();
+        assert_eq!(Polynomial::reconstruct(&shares, params.t()), Ok(key.coefficients()[0]));
+    }
+
+    #[test]
+    fn beacon_holds_early_sums() {
+        let params = ProtocolParams::new(&Committee::uniform(4, 1, 1), SECURITY_BITS);
+        let (verifying, signing): (Vec<_>, Vec<_>) = (0..4).map(|_| signature::keygen()).unzip();
+        let dealings: Vec<_> = (0..3)
+            .map(|i| AvssNode::new(i, &params).send_and_verify(MessageType::AdkgAvssFin).unwrap())
+            .collect();
+        let node = |j: usize, dealings: &[Message]| {
+            let avss = Rc::new(RefCell::new(AvssNode::new(j, &params)));
+            let mut node = AdkgNode::new(j, 1, &params, avss);
+            node.set_signing(7, signing[j].clone(), &verifying);
+            for msg in dealings {
+                node.handle_share_fin(msg.clone());
+            }
+            node.handle_prop(Message::send_message(0, vec![], MessageType::AdkgProp, vec![0, 1, 2]).unwrap());
+            node
+        };
+        let fin = || Message::send_message(0, vec![], MessageType::VabaFin, vec![0]).unwrap();
+        let sums: Vec<_> = (1..4).map(|j| node(j, &dealings).handle_vaba_fin(fin()).unwrap()).collect();
+
+        // 节点 0 在协商出 Dealer 集合之前、也还没有收到 Dealer 2 的分发时收到份额之和，先保存而不是丢弃
+        let mut late = node(0, &dealings[..2]);
+        for msg in &sums {
+            assert!(late.sum_and_rec(msg.clone()).is_none());
+        }
+        assert!(late.handle_vaba_fin(fin()).is_none());
+        assert!(late.res.is_none());
+
+        // 收到 Dealer 2 的承诺后检查全部签名，三个份额之和都记入信标的记录
+        late.handle_share_fin(dealings[2].clone());
+        assert_eq!(late.res.clone().unwrap().users, vec![0, 1, 2]);
+        let beacon = late.beacon().unwrap();
+        assert_eq!(beacon.sums.len(), 3);
+        assert!(beacon.verify(&params, &verifying));
+    }
+
+    #[test]
+    fn recover_withheld_share() {
+        let params = ProtocolParams::new(&Committee::uniform(4, 1, 1), SECURITY_BITS);
+        let (keys, dks): (Vec<_>, Vec<_>) = (0..4).map(|_| SHARE_KEM.keygen()).unzip();
+        let mut dealings: Vec<_> = (0..3)
+            .map(|i| {
+                let mut avss = AvssNode::new(i, &params);
+                avss.seal_for(&keys);
+                avss.send_and_verify(MessageType::AdkgAvssFin).unwrap()
+            })
+            .collect();
+        // Dealer 2 没有把份额加密给节点 0
+        Arc::make_mut(&mut dealings[2].proofs)[0].sealed = None;
+        let mut nodes: Vec<_> = (0..4)
+            .map(|j| {
+                let avss = Rc::new(RefCell::new(AvssNode::new(j, &params)));
+                let mut node = AdkgNode::new(j, 1, &params, avss);
+                node.set_encryption(dks[j].clone(), &keys);
+                for msg in &dealings {
+                    node.handle_share_fin(msg.clone());
+                }
+                node.handle_prop(Message::send_message(0, vec![], MessageType::AdkgProp, vec![0, 1, 2]).unwrap());
+                node
+            })
+            .collect();
+        let fin = || Message::send_message(0, vec![], MessageType::VabaFin, vec![0]).unwrap();
+
+        // 节点 0 缺少 Dealer 2 的份额，请求其他节点公开同一列的份额
+        let request = nodes[0].handle_vaba_fin(fin()).unwrap();
+        assert_eq!(request.msg_type, MessageType::RecoverRequest);
+        assert_eq!(request.msg_content, vec![2]);
+        let mut sums: Vec<_> = (1..4).map(|j| nodes[j].handle_vaba_fin(fin()).unwrap()).collect();
+
+        // 其他节点公开的份额加密给节点 0，收到 t + 1 个求值点的份额之后插值得到自己的份额并发送份额之和
+        let responses: Vec<_> = (1..4).map(|j| nodes[j].handle_recover_request(request.clone()).unwrap()).collect();
+        for response in &responses {
+            assert_eq!(response.msg_type, MessageType::RecoverResponse);
+            assert!(response.proofs.iter().all(|p| p.shares.is_empty() && p.sealed.is_some()));
+        }
+        assert!(nodes[0].handle_recover_response(responses[0].clone()).is_none());
+        let sum = nodes[0].handle_recover_response(responses[1].clone()).unwrap();
+        assert_eq!(sum.msg_type, MessageType::SumAndRec);
+        assert!(nodes[0].handle_recover_response(responses[2].clone()).is_none());
+        sums.insert(0, sum);
+        for node in &mut nodes {
+            let res = sums.iter().fold(None, |res, msg| res.or(node.sum_and_rec(msg.clone()))).unwrap();
+            assert_eq!(res.users, vec![0, 1, 2]);
+        }
+        let shares: Vec<_> = nodes
+            .iter()
+            .enumerate()
+            .map(|(j, node)| vec![params.share_point(j), node.key_shares().unwrap()[0]])
+            .collect();
+        assert_eq!(
+            Polynomial::reconstruct(&shares[..2], params.t()),
+            Polynomial::reconstruct(&shares[2..], params.t())
+        );
+
+        // Dealer 1 把份额加密给了节点 0，对它的恢复请求不回复，否则 t + 1 个份额会泄露它的秘密
+        let mut request = request;
+        request.msg_content = vec![1];
+        assert!(nodes[1].handle_recover_request(request).is_none());
+    }
+
+    #[test]
+    fn lattice_key() {
+        let params = ProtocolParams::new(&Committee::uniform(5, 1, 1), SECURITY_BITS);
+        let lattice = Lattice::new(LatticeParams::new(8, 2, 2), b"seed");
+        let mut messages: Vec<_> = (0..3)
+            .map(|i| AvssNode::lattice(i, &params, lattice.params(), 0).send_and_verify(MessageType::AdkgAvssFin).unwrap())
+            .collect();
+        // Dealer 3 分发的不是比特，比特检查值的秘密不为 0
+        let count = 4 * lattice.params().eta() * lattice.params().dimension();
+        let mut secrets = vec![Mersenne61Ext::from_int(2); count];
+        secrets.insert(COIN_SECRET, Mersenne61Ext::random_element());
+        secrets.extend([Mersenne61Ext::random_element(), Mersenne61Ext::random_element()]);
+        let mut cheating = AvssNode::with_secrets(3, &params, &secrets);
+        messages.push(cheating.send_and_verify(MessageType::AdkgAvssFin).unwrap());
+        let mut nodes: Vec<_> = (0..5)
+            .map(|j| {
+                let avss = Rc::new(RefCell::new(AvssNode::new(j, &params)));
+                let mut node = AdkgNode::lattice(j, 1, &params, avss, &lattice, 0);
+                for msg in &messages {
+                    node.handle_share_fin(msg.clone());
+                }
+                node.handle_prop(Message::send_message(0, vec![], MessageType::AdkgProp, vec![0, 1, 2, 3]).unwrap());
+                node
+            })
+            .collect();
+        let checks: Vec<_> = nodes
+            .iter_mut()
+            .map(|node| node.handle_vaba_fin(Message::send_message(0, vec![], MessageType::VabaFin, vec![0]).unwrap()).unwrap())
+            .collect();
+        assert!(checks.iter().all(|msg| msg.msg_type == MessageType::RangeCheck));
+        let sums: Vec<_> = nodes
+            .iter_mut()
+            .map(|node| checks.iter().fold(None, |sum, msg| sum.or(node.handle_range_check(msg.clone()))).unwrap())
+            .collect();
+        let node = &mut nodes[0];
+        let res = sums.into_iter().fold(None, |res, msg| res.or(node.sum_and_rec(msg))).unwrap();
+        assert!(res.sk.is_empty());
+        assert_eq!(res.users, vec![0, 1, 2]);
+
+        // 由份额重构 s，公钥减去 A·s 得到 e，s 和 e 的系数都不超过 eta 乘以 Dealer 的个数
+        let dimension = lattice.params().dimension();
+        let s: Vec<_> = (0..dimension)
+            .map(|c| {
+                let shares: Vec<_> = nodes
+                    .iter()
+                    .enumerate()
+                    .map(|(j, node)| vec![params.share_point(j), node.key_shares().unwrap()[c]])
+                    .collect();
+                Polynomial::reconstruct(&shares, params.t()).unwrap()
+            })
+            .collect();
+        let zero = vec![Mersenne61Ext::from_int(0); dimension];
+        let e: Vec<_> = res.public_key.iter().zip(lattice.public_key(&s, &zero)).map(|(b, a)| *b - a).collect();
+        assert!(s.iter().chain(&e).all(|x| centered(*x).unwrap().abs() <= 6));
+        assert!(s.iter().any(|x| !x.is_zero()));
+    }
+
+    #[test]
+    fn lattice_key_stays_private() {
+        // 加密分发时广播的只有 Dealer 的承诺、比特检查值和 A·s + e 的份额，旁观者由它们得不到 s 的份额
+        let params = ProtocolParams::new(&Committee::uniform(5, 1, 1), SECURITY_BITS);
+        let lattice = Lattice::new(LatticeParams::new(8, 2, 2), b"seed");
+        let (keys, dks): (Vec<_>, Vec<_>) = (0..5).map(|_| SHARE_KEM.keygen()).unzip();
+        let (_, outsider) = SHARE_KEM.keygen();
+        let dealings: Vec<_> = (0..3)
+            .map(|i| {
+                let mut avss = AvssNode::lattice(i, &params, lattice.params(), 0);
+                avss.seal_for(&keys);
+                avss.send_and_verify(MessageType::AdkgAvssFin).unwrap()
+            })
+            .collect();
+        let mut nodes: Vec<_> = (0..5)
+            .map(|j| {
+                let avss = Rc::new(RefCell::new(AvssNode::new(j, &params)));
+                let mut node = AdkgNode::lattice(j, 1, &params, avss, &lattice, 0);
+                node.set_encryption(dks[j].clone(), &keys);
+                for msg in &dealings {
+                    node.handle_share_fin(msg.clone());
+                }
+                node.handle_prop(Message::send_message(0, vec![], MessageType::AdkgProp, vec![0, 1, 2]).unwrap());
+                node
+            })
+            .collect();
+        let checks: Vec<_> = nodes
+            .iter_mut()
+            .map(|node| node.handle_vaba_fin(Message::send_message(0, vec![], MessageType::VabaFin, vec![0]).unwrap()).unwrap())
+            .collect();
+        let sums: Vec<_> = nodes
+            .iter_mut()
+            .map(|node| checks.iter().fold(None, |sum, msg| sum.or(node.handle_range_check(msg.clone()))).unwrap())
+            .collect();
+        let res = sums.iter().fold(None, |res, msg| res.or(nodes[0].sum_and_rec(msg.clone()))).unwrap();
+        assert_eq!(res.users, vec![0, 1, 2]);
+
+        // 广播的证明中没有份额、最终多项式和查询结果，旁观者的私钥打不开任何信封
+        for (dealer, msg) in dealings.iter().enumerate() {
+            for proof in msg.proofs.iter() {
+                assert!(proof.shares.is_empty() && proof.folding_proofs.is_empty() && proof.function_proofs.is_empty());
+                assert!(proof.transcript.final_value.is_none());
+                assert!(open_proof(&params, dealer, proof, &outsider).is_none());
+            }
+        }
+        // 公开的检查值和份额之和中没有任何节点的 s 的份额
+        let published: Vec<_> = checks.iter().chain(&sums).flat_map(|msg| decode_values(&msg.additional).unwrap()).collect();
+        for node in &nodes {
+            let key_shares = node.key_shares().unwrap();
+            assert!(key_shares.iter().any(|x| !x.is_zero()));
+            assert!(key_shares.iter().all(|x| x.is_zero() || !published.contains(x)));
         }
     }
 }
@@ -1,119 +1,303 @@
 use avss::avss::dealer::Dealer;
-use avss::avss::party::AvssParty;
+use avss::avss::party::{AvssParty, ShareOpening, ShareProof};
 
 use util::algebra::field::Field;
-use util::algebra::polynomial::MultilinearPolynomial;
-use util::random_oracle::RandomOracle;
-use util::algebra::coset::Coset;
+use util::algebra::polynomial::{MultilinearPolynomial, Polynomial, ReconstructError};
 use util::algebra::field::mersenne61_ext::Mersenne61Ext;
-use util::split_n;
-use util::{CODE_RATE, SECURITY_BITS};
+use util::algebra::lattice::LatticeParams;
+use util::envelope::Envelope;
+use util::kem::{DecapsulationKey, EncapsulationKey, KemParams};
+use util::params::ProtocolParams;
+use util::query_result::QueryResult;
+use std::collections::HashMap;
+use std::sync::Arc;
 
-use crate::msg::message::Message;
+use crate::msg::message::{decode_values, encode_values, Message};
 use crate::msg::message::MessageType;
 
+/// ADKG 中每个 Dealer 批量分发的秘密：密钥和 VABA 的公共随机数
+pub const KEY_SECRET: usize = 0;
+pub const COIN_SECRET: usize = 1;
+
+/// 加密分发份额时使用的 KEM
+pub const SHARE_KEM: KemParams = KemParams::ML_KEM_768;
+
+/// 密钥有多个分量时（重新分享时的旧份额、格上密钥的系数）第 j 个分量在批量分发中的下标
+pub fn key_index(j: usize) -> usize {
+    if j == 0 {
+        KEY_SECRET
+    } else {
+        COIN_SECRET + j
+    }
+}
+
+/// 加密证明时的关联数据：Dealer、参与方和 Dealer 对参与方的公开承诺，密文不能被挪用到别的 Dealer 或参与方
+fn sealing_aad(dealer: usize, proof: &ShareProof<Mersenne61Ext>) -> Vec<u8> {
+    let mut aad = (dealer as u64).to_le_bytes().to_vec();
+    aad.extend((proof.party as u64).to_le_bytes());
+    aad.extend(proof.transcript.digest());
+    aad
+}
+
+/// 从证明中取出只属于参与方的部分：份额多项式、最终多项式和全部查询结果，编码为字符串
+/// 最后一轮的查询结果和最终多项式在公开的开点上折叠即可得到份额多项式，它们不能留在广播的证明中，
+/// 剩下的只有默克尔树根等公开的承诺
+fn take_private(proof: &mut ShareProof<Mersenne61Ext>) -> String {
+    let coefficients: Vec<_> = proof.shares.drain(..).flat_map(|s| s.coefficients().clone()).collect();
+    let final_value = proof.transcript.final_value.take().map(|p| p.coefficients().clone()).unwrap_or_default();
+    let encode = |queries: Vec<QueryResult<Mersenne61Ext>>| {
+        queries.iter().map(encode_query).collect::<Vec<_>>().join(";")
+    };
+    let folding = encode(proof.folding_proofs.drain(..).collect());
+    let function = encode(proof.function_proofs.drain(..).collect());
+    [encode_values(&coefficients), encode_values(&final_value), folding, function].join("|")
+}
+
+/// 查询结果编码为 `proof_bytes/proof_values/salts/batch_values`，取值以 `encode_map` 编码，
+/// 盐写作 `下标=十六进制`，批量承诺的各个多项式的取值以 `&` 分隔
+fn encode_query(query: &QueryResult<Mersenne61Ext>) -> String {
+    let mut salts: Vec<_> = query.salts.iter().collect();
+    salts.sort();
+    let salts: Vec<_> = salts.iter().map(|(k, v)| format!("{}={}", k, hex::encode(v))).collect();
+    let batch: Vec<_> = query.batch_values.iter().map(encode_map).collect();
+    [hex::encode(&query.proof_bytes), encode_map(&query.proof_values), salts.join(","), batch.join("&")].join("/")
+}
+
+/// 下标到域元素的映射编码为 `下标,...#取值`，下标按从小到大排列
+fn encode_map(map: &HashMap<usize, Mersenne61Ext>) -> String {
+    let mut keys: Vec<_> = map.keys().copied().collect();
+    keys.sort();
+    let values: Vec<_> = keys.iter().map(|k| map[k]).collect();
+    let keys: Vec<_> = keys.iter().map(|k| k.to_string()).collect();
+    format!("{}#{}", keys.join(","), encode_values(&values))
+}
+
+fn decode_map(s: &str) -> Option<HashMap<usize, Mersenne61Ext>> {
+    let (keys, values) = s.split_once('#')?;
+    let keys = split(keys, ',').map(|k| k.parse().ok()).collect::<Option<Vec<usize>>>()?;
+    let values = decode_values(values)?;
+    if keys.len() != values.len() {
+        return None
+    }
+    Some(keys.into_iter().zip(values).collect())
+}
+
+fn decode_query(s: &str) -> Option<QueryResult<Mersenne61Ext>> {
+    let fields: Vec<_> = s.split('/').collect();
+    let [proof_bytes, proof_values, salts, batch] = fields[..] else { return None };
+    let salts = split(salts, ',')
+        .map(|salt| {
+            let (k, v) = salt.split_once('=')?;
+            Some((k.parse().ok()?, hex::decode(v).ok()?))
+        })
+        .collect::<Option<_>>()?;
+    Some(QueryResult {
+        proof_bytes: hex::decode(proof_bytes).ok()?,
+        proof_values: decode_map(proof_values)?,
+        salts,
+        batch_values: split(batch, '&').map(decode_map).collect::<Option<_>>()?,
+    })
+}
+
+/// 以 `separator` 分隔的列表，空字符串为空列表
+fn split(s: &str, separator: char) -> impl Iterator<Item = &str> {
+    s.split(separator).filter(move |_| !s.is_empty())
+}
+
+/// 把证明中只属于参与方的部分（份额多项式、最终多项式和查询结果）以接收者的 KEM 公钥 `ek` 加密，
+/// 关联数据绑定 Dealer `dealer`，广播的证明中只剩下公开的承诺
+pub fn seal_proof(dealer: usize, proof: &mut ShareProof<Mersenne61Ext>, ek: &EncapsulationKey) {
+    let payload = take_private(proof);
+    let aad = sealing_aad(dealer, proof);
+    let envelope = Envelope::seal(&SHARE_KEM, ek, &aad, payload.as_bytes());
+    proof.sealed = Some(envelope.expect("invalid encapsulation key"));
+}
+
+/// 用自己的 KEM 私钥解密 Dealer `dealer` 发来的证明
+/// 没有加密（证明以明文广播）、解密失败或者证明的格式不正确时返回 None
+pub fn open_proof(
+    params: &ProtocolParams<Mersenne61Ext>,
+    dealer: usize,
+    proof: &ShareProof<Mersenne61Ext>,
+    dk: &DecapsulationKey,
+) -> Option<ShareProof<Mersenne61Ext>> {
+    let envelope = proof.sealed.as_ref()?;
+    let plaintext = envelope.open(&SHARE_KEM, dk, &sealing_aad(dealer, proof))?;
+    with_private(params, proof, &plaintext)
+}
+
+/// 由投诉者公开的 KEM 随机数 `m` 打开 Dealer `dealer` 加密给公钥 `ek` 的证明，用于检查投诉
+/// `m` 与信封中的密文不一致时返回 None；一致而信封无法解密或者证明的格式不正确是 Dealer 的错误，
+/// 此时返回没有份额的证明，它无法通过验证
+pub fn open_revealed(
+    params: &ProtocolParams<Mersenne61Ext>,
+    dealer: usize,
+    proof: &ShareProof<Mersenne61Ext>,
+    ek: &EncapsulationKey,
+    m: &[u8; 32],
+) -> Option<ShareProof<Mersenne61Ext>> {
+    let envelope = proof.sealed.as_ref()?;
+    if !envelope.matches(&SHARE_KEM, ek, m) {
+        return None
+    }
+    let opened = envelope
+        .open_revealed(&SHARE_KEM, ek, m, &sealing_aad(dealer, proof))
+        .and_then(|plaintext| with_private(params, proof, &plaintext));
+    Some(opened.unwrap_or_else(|| ShareProof { shares: vec![], sealed: None, ..proof.clone() }))
+}
+
+/// 以解密出的明文恢复证明中只属于参与方的部分，格式不正确时返回 None
+fn with_private(
+    params: &ProtocolParams<Mersenne61Ext>,
+    proof: &ShareProof<Mersenne61Ext>,
+    plaintext: &[u8],
+) -> Option<ShareProof<Mersenne61Ext>> {
+    let fields: Vec<_> = std::str::from_utf8(plaintext).ok()?.split('|').collect();
+    let [shares, final_value, folding, function] = fields[..] else { return None };
+    let coefficients = decode_values(shares)?;
+    let len = 1 << params.terminate_round();
+    if coefficients.is_empty() || coefficients.len() % len != 0 {
+        return None
+    }
+    let total_round = params.total_round();
+    let folding = split(folding, ';').map(decode_query).collect::<Option<Vec<_>>>()?;
+    let function = split(function, ';').map(decode_query).collect::<Option<Vec<_>>>()?;
+    if folding.len() + 1 != total_round || function.len() != total_round {
+        return None
+    }
+    let mut opened = proof.clone();
+    opened.shares = coefficients.chunks(len).map(|c| MultilinearPolynomial::new(c.to_vec())).collect();
+    opened.transcript.final_value = Some(Polynomial::new(decode_values(final_value)?));
+    opened.folding_proofs = folding;
+    opened.function_proofs = function;
+    opened.sealed = None;
+    Some(opened)
+}
+
 pub struct AvssNode{
     id: usize,
-    log_n: usize,
-    terminate_round: usize,
-    polynomial: MultilinearPolynomial<Mersenne61Ext>,
+    params: ProtocolParams<Mersenne61Ext>,
+    polynomials: Vec<MultilinearPolynomial<Mersenne61Ext>>,
     dealer: Dealer<Mersenne61Ext>,
     parties: Vec<AvssParty<Mersenne61Ext>>,
+    dealt: bool,
+    proofs: Arc<Vec<ShareProof<Mersenne61Ext>>>,
+    /// 生成乘法三元组时以门限 2t 进行的第二次分发，它的证明附在本次分发的证明之后
+    double: Option<Box<AvssNode>>,
+    /// 加密分发时每个求值点的所有者的 KEM 公钥，不属于任何节点的求值点为 None
+    /// 没有设置时份额以明文放在广播的证明中，只用于本地的模拟和单元测试
+    recipients: Option<Vec<Option<EncapsulationKey>>>,
 }
 
 impl AvssNode {
 
-    pub fn new(id: usize, log_n: usize, terminate_round: usize) -> AvssNode {
-        let log_t = log_n - 2;
-        let log_d = log_t * 2;
-        let oracle = RandomOracle::new(log_d - terminate_round, SECURITY_BITS / CODE_RATE);
-        
-        // interpolate_cosets 首先生成一个生成元，阶为 2 ^ (log_t * 2 + CODE_RATE)
-        let mut interpolate_cosets = vec![Coset::new(
-            1 << (log_t * 2 + CODE_RATE),
-            Mersenne61Ext::random_element(),
-        )];
-        // 然后生成 log_d - 1 个余元，每个余元是前一个余元的平方
-        for i in 1..log_d {
-            interpolate_cosets.push(interpolate_cosets[i - 1].pow(2));
-        }
-
-        // 生成一个随机多项式，次数为 2 ^ log_d，即 n^2/16
-        let polynomial = MultilinearPolynomial::random_polynomial(log_d);
-
-        // 生成两个生成元 coset_x 和 coset_y，阶为 2 ^ log_n，即 n
-        // 使用这些参数创建两个余元集合 coset_x 和 coset_y
-        let x_shift = Mersenne61Ext::random_element();
-        let coset_x = Coset::new(1 << log_n, x_shift);
-        let y_shift = Mersenne61Ext::random_element();
-        let coset_y = Coset::new(1 << log_n, y_shift);
-
-        // folding_parameter 存储多轮的折叠参数
-        // 首先得到 t - 1 的二进制表达的数组，比如 t = 4，那么 v = [1, 2]
-        // 然后计算出 coset_x 的 v 次方，得到一个二维数组，每一行都是 coset_x 的 v[i] 次方的所有元素
-        let mut folding_parameter = vec![];
-        let v = split_n((1 << log_t) - 1);
-        for i in &v {
-            folding_parameter.push(coset_x.pow(*i).all_elements());
-        }
-        
-        // 计算最后一个元素的长度
-        // 遍历 v 中的每一个元素，计算 coset_y 的 v[i] 次方，得到一个二维数组，每一行都是 coset_y 的 v[i] 次方的所有元素
-        // 第二层map函数使得每个元素都重复 last_len 次
-        let last_len = folding_parameter.last().unwrap().len();
-        for i in &v {
-            folding_parameter.push(
-                coset_y
-                    .pow(*i)
-                    .all_elements()
-                    .iter()
-                    .map(|x| (0..last_len).into_iter().map(|_| *x).collect::<Vec<_>>())
-                    .flatten()
-                    .collect(),
-            );
-        }
+    /// 按照协议参数分发一个随机多项式，多项式在 x 和 y 上的次数均为门限 t
+    /// 任意 t + 1 个份额可以重构秘密，而 t 个份额不泄露秘密的任何信息
+    pub fn new(id: usize, params: &ProtocolParams<Mersenne61Ext>) -> AvssNode {
+        Self::batch(id, params, 1)
+    }
 
-        // 输出折叠参数
-        // for i in 0..folding_parameter.len() {
-        //     println!("{}: {:?}", i, folding_parameter[i]);
-        // }
+    /// 批量分发 `k` 个随机多项式，所有多项式共用一份低次证明
+    pub fn batch(id: usize, params: &ProtocolParams<Mersenne61Ext>, k: usize) -> AvssNode {
+        // 生成 k 个随机多项式，每个共有 2 ^ log_d 个系数
+        let polynomials: Vec<_> = (0..k)
+            .map(|_| MultilinearPolynomial::random_polynomial(params.log_d()))
+            .collect();
+        Self::from_polynomials(id, params, polynomials)
+    }
+
+    /// 分发指定的秘密，第 i 个多项式满足 `f_i(x_0, 0) = secrets[i]`
+    pub fn with_secrets(id: usize, params: &ProtocolParams<Mersenne61Ext>, secrets: &[Mersenne61Ext]) -> AvssNode {
+        let polynomials = secrets.iter().map(|s| params.secret_polynomial(*s)).collect();
+        Self::from_polynomials(id, params, polynomials)
+    }
+
+    /// 主动刷新时分发的多项式：密钥对应的多项式满足 `f(x_0, 0) = 0`，打包时在所有秘密的位置上为 0，
+    /// VABA 的公共随机数仍然是随机的，最后是同样秘密为 0 的掩码多项式，用于公开检查前者的秘密为 0
+    pub fn refresh(id: usize, params: &ProtocolParams<Mersenne61Ext>) -> AvssNode {
+        let zero = vec![Mersenne61Ext::from_int(0); params.packing()];
+        let polynomials = vec![
+            params.packed_polynomial(&zero),
+            MultilinearPolynomial::random_polynomial(params.log_d()),
+            params.packed_polynomial(&zero),
+        ];
+        Self::from_polynomials(id, params, polynomials)
+    }
+
+    /// 重新分享：`key_shares` 为节点在旧委员会中的密钥份额，按照新委员会的参数 `params` 分发，
+    /// 最后是秘密为 0 的掩码多项式，用于公开检查分发的确实是旧份额
+    pub fn reshare(id: usize, params: &ProtocolParams<Mersenne61Ext>, key_shares: &[Mersenne61Ext]) -> AvssNode {
+        let mut keys = key_shares.to_vec();
+        keys.push(Mersenne61Ext::from_int(0));
+        Self::with_keys(id, params, &keys)
+    }
+
+    /// 格上的密钥：依次分发中心二项分布的私钥 s 和噪声 e 的全部比特，
+    /// 以及 `decryptions` 次门限解密所用的淹没噪声，每次 degree 个，
+    /// 最后是两个随机数，节点由它们的份额得到检查比特时所用的秘密为 0 的 2t 次掩码
+    pub fn lattice(id: usize, params: &ProtocolParams<Mersenne61Ext>, lattice: &LatticeParams, decryptions: usize) -> AvssNode {
+        let mut keys = lattice.sample_bits();
+        keys.extend(lattice.sample_bits());
+        keys.extend(lattice.sample_flooding(decryptions * lattice.degree()));
+        keys.extend([Mersenne61Ext::random_element(), Mersenne61Ext::random_element()]);
+        Self::with_keys(id, params, &keys)
+    }
+
+    /// 乘法三元组：以门限 t 分发 `count` 个随机的 a、b、r 和一个随机的掩码 p，
+    /// 再以门限为 2t 的参数 `double` 分发相同的 r 和 p，两次分发的证明在同一条消息中发送
+    pub fn triples(id: usize, params: &ProtocolParams<Mersenne61Ext>, double: &ProtocolParams<Mersenne61Ext>, count: usize) -> AvssNode {
+        let keys: Vec<_> = (0..3 * count + 1).map(|_| Mersenne61Ext::random_element()).collect();
+        let mut node = Self::with_keys(id, params, &keys);
+        node.double = Some(Box::new(Self::with_secrets(id, double, &keys[2 * count..])));
+        node
+    }
+
+    /// 第 j 个分量是批量分发的第 `key_index(j)` 个秘密，COIN_SECRET 位置为 VABA 的公共随机数
+    fn with_keys(id: usize, params: &ProtocolParams<Mersenne61Ext>, keys: &[Mersenne61Ext]) -> AvssNode {
+        let mut polynomials: Vec<_> = keys.iter().map(|s| params.secret_polynomial(*s)).collect();
+        polynomials.insert(COIN_SECRET, MultilinearPolynomial::random_polynomial(params.log_d()));
+        Self::from_polynomials(id, params, polynomials)
+    }
+
+    fn from_polynomials(
+        id: usize,
+        params: &ProtocolParams<Mersenne61Ext>,
+        polynomials: Vec<MultilinearPolynomial<Mersenne61Ext>>,
+    ) -> AvssNode {
+        let oracle = params.oracle();
 
         // parties 存储参与方，有 n^2 个参与方
-        let mut parties = vec![];
-        for i in 0..(1 << (log_n * 2)) {
-            let mut open_point = vec![];
-            // 每个参与方有 log_d 个开放点，每个开放点都是折叠参数的一个元素
-            for j in 0..log_d {
-                open_point.push(folding_parameter[j][i % folding_parameter[j].len()]);
-            }
-            parties.push(AvssParty::new(
-                log_d - terminate_round,
-                &interpolate_cosets,
-                open_point,
-                &oracle,
-            ));
-        }
-
-        let dealer = Dealer::new(
-            log_d - terminate_round,
-            &polynomial,
-            &interpolate_cosets,
-            &oracle,
-            &folding_parameter,
-        );
+        let parties = AvssParty::from_params(params, &oracle);
+
+        let dealer = Dealer::batch(params, &polynomials, &oracle);
 
         AvssNode {
-            id: id,
-            log_n: log_n,
-            terminate_round: terminate_round,
-            polynomial: polynomial,
-            dealer: dealer,
-            parties: parties,
+            id,
+            params: params.clone(),
+            polynomials,
+            dealer,
+            parties,
+            dealt: false,
+            proofs: Arc::default(),
+            double: None,
+            recipients: None,
         }
 
     }
     
+    /// 分发并验证份额，批量分发的多个秘密只需要证明一次，之后的调用只返回消息
+    /// 第一次调用返回的消息的 `proofs` 中依次为每个求值点上的份额和证明，节点验证分配给自己的份额，验证失败时投诉
     pub fn send_and_verify(&mut self, msg_type: MessageType) -> Option<Message> {
+        let mut message = Message::send_message_with_addi(self.id, vec![], msg_type, vec![], String::new());
+        if !self.dealt {
+            self.deal();
+            message.proofs = self.proofs.clone();
+        }
+        Some(message)
+    }
+
+    fn deal(&mut self) {
+        self.dealt = true;
         self.dealer.send_evaluations(&mut self.parties);
         self.dealer.commit_functions(&self.parties);
         self.dealer.prove();
@@ -122,11 +306,10 @@ impl AvssNode {
         let mut folding0 = vec![];
         let mut function0 = vec![];
 
-        let log_d = (self.log_n - 2) * 2;
-        let terminate_round = self.terminate_round;
+        let total_round = self.params.total_round();
 
-        for i in 0..(log_d - terminate_round) {
-            if i < log_d - terminate_round - 1 {
+        for i in 0..total_round {
+            if i < total_round - 1 {
                 folding0.push(folding[i][0].clone());
             }
             function0.push(function[i][0].clone());
@@ -134,49 +317,173 @@ impl AvssNode {
         
         assert!(self.parties[0].verify(&folding0, &function0));
 
-        Message::send_message2all(self.id, msg_type, vec![])
+        let n = 1 << self.params.log_n();
+        let mut proofs: Vec<_> = (0..n)
+            .map(|i| {
+                let party = i * n;
+                let folding: Vec<_> = folding.iter().map(|f| f[party % f.len()].clone()).collect();
+                let function: Vec<_> = function.iter().map(|f| f[party % f.len()].clone()).collect();
+                self.parties[party].proof(party, &folding, &function)
+            })
+            .collect();
+        if let Some(recipients) = &self.recipients {
+            for (proof, ek) in proofs.iter_mut().zip(recipients) {
+                match ek {
+                    Some(ek) => seal_proof(self.id, proof, ek),
+                    None => {
+                        take_private(proof);
+                    }
+                }
+            }
+        }
+        if let Some(double) = self.double.as_mut() {
+            double.deal();
+            proofs.extend(double.proofs.iter().cloned());
+        }
+        self.proofs = Arc::new(proofs);
     }
 
+    /// 加密分发：每个求值点上的份额多项式、最终多项式和查询结果以它的所有者的 KEM 公钥 `keys[i]` 加密，
+    /// 只有承诺公开，不属于任何节点的求值点只发送承诺。需要在分发之前设置，生成乘法三元组时门限 2t 的分发同样加密
+    pub fn seal_for(&mut self, keys: &[EncapsulationKey]) {
+        if let Some(double) = self.double.as_mut() {
+            double.seal_for(keys);
+        }
+        let allocation = self.params.committee().allocate();
+        let mut recipients = vec![None; 1 << self.params.log_n()];
+        for (node, points) in allocation.iter().enumerate() {
+            for p in points {
+                recipients[*p] = Some(keys[node].clone());
+            }
+        }
+        self.recipients = Some(recipients);
+    }
+
+    /// 第 i 个求值点上的份额和证明，需要先分发
+    pub fn share_proof(&self, i: usize) -> ShareProof<Mersenne61Ext> {
+        self.proofs[i].clone()
+    }
+
+    /// 第 i 个份额为 `[y_i, f(x_0, y_i)]`，其中 `y_i` 为 coset_y 的第 i 个元素
+    /// 秘密为 `f(x_0, 0)`，任意 t + 1 个份额可以通过拉格朗日插值重构秘密
     pub fn shares(&self) -> Vec<Vec<Mersenne61Ext>> {
+        self.shares_of(KEY_SECRET)
+    }
+
+    /// 批量分发时第 `index` 个秘密的份额，格式与 `shares` 相同
+    pub fn shares_of(&self, index: usize) -> Vec<Vec<Mersenne61Ext>> {
         let mut shares = vec![];
-        let n = 1 << self.log_n;
+        let n = 1 << self.params.log_n();
         for i in 0..n {
-            shares.push(self.parties[i*n].interpolate_share());
-            // println!("shares {}: {:?}", i, shares[i])
+            shares.push(self.parties[i*n].interpolate_share_of(index));
         }
         shares
     }
 
-    pub fn sum_and_rec(&self, dealers: Vec<usize>) -> Mersenne61Ext{
-        let mut sum = Mersenne61Ext::from_int(0);
-        for i in 0..dealers.len() {
-            sum += self.parties[dealers[i]].share();
-        }
-        sum
-    } 
-
-    pub fn reconstruct(&self) -> Mersenne61Ext{
-        // let n = 1 << self.log_n;
+    /// 按照权重分配秘密份额，权重为 w 的节点得到 w 个求值点上的份额
+    /// 返回值的第 i 项为节点 i 的全部份额，每个份额的格式与 `shares` 相同
+    pub fn weighted_shares(&self) -> Vec<Vec<Vec<Mersenne61Ext>>> {
+        let committee = self.params.committee();
+        let n = 1 << self.params.log_n();
+        let coset_y = self.params.coset_y();
+        assert!(committee.total_weight() <= n);
+        committee
+            .allocate()
+            .iter()
+            .map(|points| {
+                points
+                    .iter()
+                    .map(|i| vec![coset_y.element_at(*i), self.parties[i * n].share()])
+                    .collect()
+            })
+            .collect()
+    }
 
-        // println!("{}", self.parties.len());
-        // for i in 0..self.parties.len() {
-        //     println!("reconstruct {}: {:?}", i, self.parties[i].all_share().evaluate_as_polynomial(Mersenne61Ext::from_int(0)));
-        // }
-        // println!(" ==================================== ");
-        
-        // for i in 0..n {
-        //     println!("reconstruct {}: {:?}", i, self.parties[i*n].all_share().evaluate_as_polynomial(Mersenne61Ext::from_int(0)));
-        // }
+    pub fn reconstruct(&self) -> Result<Mersenne61Ext, ReconstructError> {
+        self.reconstruct_of(KEY_SECRET)
+    }
 
+    /// 重构批量分发的第 `index` 个秘密，没有分发时为 0
+    pub fn reconstruct_of(&self, index: usize) -> Result<Mersenne61Ext, ReconstructError> {
         if self.parties[0].has_share() {
-            self.parties[0].all_share().evaluate_as_polynomial(Mersenne61Ext::from_int(0))
+            // 由全部份额插值计算 f(x_0, 0)，并检查所有份额位于同一个 t 次多项式上
+            self.reconstruct_from(&self.shares_of(index))
         }else {
-            Mersenne61Ext::from_int(0)
+            Ok(Mersenne61Ext::from_int(0))
+        }
+    }
+
+    /// 由任意至少 t + 1 个来自不同参与方的份额重构秘密，份额的格式与 `interpolate_share` 相同
+    /// 存在不一致的份额时返回它们在 `shares` 中的下标
+    pub fn reconstruct_from(&self, shares: &[Vec<Mersenne61Ext>]) -> Result<Mersenne61Ext, ReconstructError> {
+        Polynomial::reconstruct(shares, self.params.t())
+    }
+
+    /// 打包秘密分享时由至少 t + 1 个份额重构全部秘密，不打包时只有一个秘密
+    pub fn reconstruct_packed(&self, shares: &[Vec<Mersenne61Ext>]) -> Result<Vec<Mersenne61Ext>, ReconstructError> {
+        Polynomial::reconstruct_packed(shares, self.params.t(), &self.params.secret_points())
+    }
+
+    /// 第 i 个求值点上的份额连同 Dealer 承诺的默克尔打开
+    pub fn open_share(&self, i: usize) -> ShareOpening<Mersenne61Ext> {
+        let party = i << self.params.log_n();
+        self.parties[party].open(party, &self.dealer.share_proof(party))
+    }
+
+    /// 检查公开的份额与 Dealer 的承诺一致
+    pub fn verify_opening(&self, opening: &ShareOpening<Mersenne61Ext>) -> bool {
+        opening.verify(&self.params, &self.dealer.function_root(opening.party))
+    }
+
+    /// 只使用通过验证的份额重构第 `index` 个秘密，不需要纠错
+    /// 返回值为秘密（通过验证的份额不足 t + 1 个时为 None）和未通过验证的份额在 `openings` 中的下标
+    pub fn reconstruct_verified(
+        &self,
+        index: usize,
+        openings: &[ShareOpening<Mersenne61Ext>],
+    ) -> (Option<Mersenne61Ext>, Vec<usize>) {
+        let mut shares: Vec<Vec<Mersenne61Ext>> = vec![];
+        let mut rejected = vec![];
+        for (i, opening) in openings.iter().enumerate() {
+            let share = opening.share_of(&self.params, index);
+            if self.verify_opening(opening) && shares.iter().all(|s| s[0] != share[0]) {
+                shares.push(share);
+            } else {
+                rejected.push(i);
+            }
+        }
+        let t = self.params.t();
+        if shares.len() <= t {
+            return (None, rejected);
         }
+        (self.reconstruct_from(&shares[..t + 1]).ok(), rejected)
+    }
+
+    /// 第 i 个求值点的参与方没有收到 Dealer 的份额时，由同一行和同一列的参与方公开份额来恢复
+    /// 未通过验证的份额被忽略，份额不一致时返回与插值结果不一致的参与方
+    /// 这里在一个节点内模拟，节点之间通过消息恢复见 `AdkgNode::handle_recover_request`
+    pub fn recover_share(&mut self, i: usize) -> Result<(), ReconstructError> {
+        let log_n = self.params.log_n();
+        let mask = (1 << log_n) - 1;
+        let party = i << log_n;
+        let helpers: Vec<_> = (0..self.parties.len())
+            .filter(|h| *h != party && (h & mask == party & mask || h >> log_n == party >> log_n))
+            .filter(|h| self.parties[*h].has_share())
+            .map(|h| self.parties[h].open(h, &self.dealer.share_proof(h)))
+            .filter(|o| self.verify_opening(o))
+            .collect();
+        self.parties[party]
+            .recover(&self.params, party, &helpers)
+            .map_err(|e| match e {
+                ReconstructError::Inconsistent(e) => {
+                    ReconstructError::Inconsistent(e.iter().map(|j| helpers[*j].party).collect())
+                }
+                e => e,
+            })
     }
 
     pub fn get_poly(&self) -> MultilinearPolynomial<Mersenne61Ext> {
-        self.polynomial.clone()
+        self.polynomials[KEY_SECRET].clone()
     }
     
 }
@@ -184,21 +491,419 @@ impl AvssNode {
 
 #[cfg(test)]
 mod tests {
-    use super::AvssNode;
+    use super::{open_proof, open_revealed, seal_proof, AvssNode, COIN_SECRET, KEY_SECRET, SHARE_KEM};
     use crate::msg::message::MessageType;
+    use avss::avss::party::{Complaint, ShareProof};
+    use avss::one2many::verifier::One2ManyVerifier;
+    use util::algebra::field::Field;
+    use util::algebra::field::mersenne61_ext::Mersenne61Ext;
+    use util::algebra::polynomial::{MultilinearPolynomial, Polynomial, ReconstructError};
+    use util::committee::Committee;
+    use util::params::ProtocolParams;
+    use util::security::SecurityConfig;
+    use util::SECURITY_BITS;
+
+    #[test]
+    fn sealed() {
+        // 加权的委员会共有 9 个求值点，其余求值点不属于任何节点，不发送份额
+        let params = ProtocolParams::new(&Committee::new(vec![1, 3, 1, 2, 2], 1, 2), SECURITY_BITS);
+        let (keys, dks): (Vec<_>, Vec<_>) = (0..5).map(|_| SHARE_KEM.keygen()).unzip();
+        let mut s = AvssNode::batch(4, &params, 2);
+        s.seal_for(&keys);
+        let msg = s.send_and_verify(MessageType::AdkgAvssFin).unwrap();
+        assert!(msg.proofs.iter().all(|p| p.shares.is_empty()));
+        assert_eq!(msg.proofs.iter().filter(|p| p.sealed.is_some()).count(), 9);
+
+        // 节点 1 的第二个求值点只有节点 1 能解密，解密后的份额通过验证
+        let proof = &msg.proofs[2];
+        let opened = open_proof(&params, 4, proof, &dks[1]).unwrap();
+        assert!(opened.verify(&params));
+        assert_eq!(opened.share_of(&params, KEY_SECRET), s.shares()[2][1]);
+        assert!(open_proof(&params, 4, proof, &dks[0]).is_none());
+        assert!(open_proof(&params, 3, proof, &dks[1]).is_none());
+
+        // 明文广播的份额不被接受
+        let plain = AvssNode::batch(4, &params, 2).send_and_verify(MessageType::AdkgAvssFin).unwrap();
+        assert!(open_proof(&params, 4, &plain.proofs[2], &dks[1]).is_none());
+
+        // 生成乘法三元组时门限 2t 的分发同样加密
+        let double = params.clone().with_threshold(2 * params.t());
+        let mut s = AvssNode::triples(4, &params, &double, 1);
+        s.seal_for(&keys);
+        let msg = s.send_and_verify(MessageType::AdkgAvssFin).unwrap();
+        assert!(msg.proofs.iter().all(|p| p.shares.is_empty()));
+        assert_eq!(msg.proofs.iter().filter(|p| p.sealed.is_some()).count(), 18);
+        let opened = open_proof(&double, 4, &msg.proofs[16 + 2], &dks[1]).unwrap();
+        assert!(opened.verify(&double));
+    }
+
+    #[test]
+    fn outsider() {
+        // 广播的证明中只有承诺：没有份额、最终多项式和查询结果，只有份额的所有者能打开
+        let params = ProtocolParams::new(&Committee::uniform(7, 2, 2), SECURITY_BITS);
+        let (keys, dks): (Vec<_>, Vec<_>) = (0..7).map(|_| SHARE_KEM.keygen()).unzip();
+        let mut s = AvssNode::batch(0, &params, 2);
+        s.seal_for(&keys);
+        let msg = s.send_and_verify(MessageType::AdkgAvssFin).unwrap();
+        let shares = s.shares();
+        let allocation = params.committee().allocate();
+        for proof in msg.proofs.iter() {
+            assert!(proof.shares.is_empty() && proof.folding_proofs.is_empty() && proof.function_proofs.is_empty());
+            assert!(proof.transcript.final_value.is_none());
+            assert!(!proof.verify(&params));
+            let i = proof.party >> params.log_n();
+            let owner = allocation.iter().position(|points| points.contains(&i));
+            for (node, dk) in dks.iter().enumerate() {
+                let opened = open_proof(&params, 0, proof, dk);
+                assert_eq!(opened.is_some(), owner == Some(node));
+                if let Some(opened) = opened {
+                    assert!(opened.verify(&params));
+                    assert_eq!(opened.share_of(&params, KEY_SECRET), shares[i][1]);
+                    assert!(opened.transcript.final_value.is_some());
+                }
+            }
+        }
+    }
 
     #[test]
     fn avss_log_print() {
-        let mut s = AvssNode::new(0, 3, 1);
+        let params = ProtocolParams::new(&Committee::uniform(7, 2, 2), SECURITY_BITS);
+        let mut s = AvssNode::new(0, &params);
         let m = s.send_and_verify(MessageType::AdkgAvssFin);
         println!("{}", m.unwrap());
 
         let shares = s.shares();
         println!("{:?}", shares);
         println!("{}", shares.len());
-        let res = s.reconstruct();
+        let res = s.reconstruct().unwrap();
         println!("{}", res);
 
     }
-}
 
+    #[test]
+    fn weighted_shares() {
+        let committee = Committee::new(vec![1, 2, 1, 3], 2, 2);
+        let mut s = AvssNode::new(0, &ProtocolParams::new(&committee, SECURITY_BITS));
+        s.send_and_verify(MessageType::AdkgAvssFin);
+
+        let shares = s.shares();
+        let weighted = s.weighted_shares();
+        assert_eq!(weighted.len(), committee.n());
+        for (id, points) in committee.allocate().iter().enumerate() {
+            assert_eq!(weighted[id].len(), committee.weight(id));
+            for (j, p) in points.iter().enumerate() {
+                assert_eq!(weighted[id][j], shares[*p]);
+            }
+        }
+    }
+
+    #[test]
+    fn reconstruct_with_threshold() {
+        let t = 3;
+        let params = ProtocolParams::new(&Committee::uniform(8, 2, t), SECURITY_BITS);
+        let mut s = AvssNode::new(0, &params);
+        s.send_and_verify(MessageType::AdkgAvssFin);
+        let secret = s.reconstruct().unwrap();
+
+        // 任意 t + 1 个份额重构出相同的秘密
+        let shares = s.shares();
+        let zero = Mersenne61Ext::from_int(0);
+        for start in 0..(shares.len() - t) {
+            let subset = &shares[start..start + t + 1];
+            let xs: Vec<_> = subset.iter().map(|s| s[0]).collect();
+            let ys: Vec<_> = subset.iter().map(|s| s[1]).collect();
+            assert_eq!(Polynomial::lagrange_evaluate(&xs, &ys, zero), secret);
+        }
+
+        // 所有份额都位于同一个 t 次多项式上
+        let xs: Vec<_> = shares[..t + 1].iter().map(|s| s[0]).collect();
+        let ys: Vec<_> = shares[..t + 1].iter().map(|s| s[1]).collect();
+        for share in &shares {
+            assert_eq!(Polynomial::lagrange_evaluate(&xs, &ys, share[0]), share[1]);
+        }
+    }
+
+    #[test]
+    fn terminate_round() {
+        // 不同的 terminate_round 下验证都能通过，且份额都位于同一个 t 次多项式上
+        let params = ProtocolParams::new(&Committee::uniform(13, 4, 4), SECURITY_BITS);
+        for r in 1..params.log_d() {
+            let mut s = AvssNode::new(0, &params.clone().with_terminate_round(r));
+            s.send_and_verify(MessageType::AdkgAvssFin);
+            let shares = s.shares();
+            let xs: Vec<_> = shares[..5].iter().map(|s| s[0]).collect();
+            let ys: Vec<_> = shares[..5].iter().map(|s| s[1]).collect();
+            for share in &shares {
+                assert_eq!(Polynomial::lagrange_evaluate(&xs, &ys, share[0]), share[1]);
+            }
+        }
+    }
+
+    #[test]
+    fn zero_knowledge() {
+        // 零知识模式下份额与普通模式下一样位于同一个 t 次多项式上
+        let params = ProtocolParams::new(&Committee::uniform(7, 2, 2), SECURITY_BITS).with_zero_knowledge();
+        let mut s = AvssNode::new(0, &params);
+        s.send_and_verify(MessageType::AdkgAvssFin);
+        let shares = s.shares();
+        let xs: Vec<_> = shares[..3].iter().map(|s| s[0]).collect();
+        let ys: Vec<_> = shares[..3].iter().map(|s| s[1]).collect();
+        for share in &shares {
+            assert_eq!(Polynomial::lagrange_evaluate(&xs, &ys, share[0]), share[1]);
+        }
+    }
+
+    #[test]
+    fn grinding() {
+        let committee = Committee::uniform(7, 2, 2);
+        let security = SecurityConfig::new(SECURITY_BITS).with_grinding_bits(16);
+        let params = ProtocolParams::with_security(&committee, security);
+        let plain: ProtocolParams<Mersenne61Ext> = ProtocolParams::new(&committee, SECURITY_BITS);
+        assert!(params.query_num() < plain.query_num());
+        let mut s = AvssNode::new(0, &params);
+        s.send_and_verify(MessageType::AdkgAvssFin);
+
+        // 篡改 nonce 后验证失败
+        let (folding, function) = s.dealer.query();
+        let folding0: Vec<_> = folding.iter().map(|x| x[0].clone()).collect();
+        let function0: Vec<_> = function.iter().map(|x| x[0].clone()).collect();
+        assert!(s.parties[0].verify(&folding0, &function0));
+        // 工作量证明绑定整个记录：篡改最终多项式后查询下标改变，默克尔证明也无法通过
+        let proof = s.parties[0].proof(0, &folding0, &function0);
+        let mut transcript = proof.transcript.clone();
+        let mut coefficients = transcript.final_value.unwrap().coefficients().to_vec();
+        coefficients[0] += Mersenne61Ext::from_int(1);
+        transcript.final_value = Some(Polynomial::new(coefficients));
+        let verifier = |transcript| {
+            One2ManyVerifier::from_transcript(
+                params.total_round(),
+                params.open_point(0).len(),
+                &params.interpolate_cosets(),
+                transcript,
+            )
+        };
+        assert!(verifier(&proof.transcript).verify_merkle_proofs(&folding0, &function0));
+        assert!(!verifier(&transcript).verify_merkle_proofs(&folding0, &function0));
+
+        let nonce = s.dealer.nonce();
+        s.parties[0].verifier.borrow_mut().set_nonce(nonce + 1);
+        assert!(!s.parties[0].verify(&folding0, &function0));
+    }
+
+    #[test]
+    fn batch() {
+        // 批量分发的每个秘密的份额都位于各自的 t 次多项式上
+        let params = ProtocolParams::new(&Committee::uniform(7, 2, 2), SECURITY_BITS);
+        let mut s = AvssNode::batch(0, &params, 3);
+        s.send_and_verify(MessageType::AdkgAvssFin);
+        for index in 0..3 {
+            let shares = s.shares_of(index);
+            let xs: Vec<_> = shares[..3].iter().map(|s| s[0]).collect();
+            let ys: Vec<_> = shares[..3].iter().map(|s| s[1]).collect();
+            for share in &shares {
+                assert_eq!(Polynomial::lagrange_evaluate(&xs, &ys, share[0]), share[1]);
+            }
+        }
+        assert_eq!(s.reconstruct().unwrap(), s.reconstruct_of(KEY_SECRET).unwrap());
+        assert_ne!(s.reconstruct_of(KEY_SECRET).unwrap(), s.reconstruct_of(COIN_SECRET).unwrap());
+
+        // 批量证明的大小与单个秘密的证明相近，只有第 0 轮打开的取值随秘密的个数增加
+        let size = |s: &AvssNode| -> usize {
+            let (folding, function) = s.dealer.query();
+            folding.iter().chain(function.iter()).map(|x| x[0].proof_size()).sum()
+        };
+        let mut single = AvssNode::new(0, &params);
+        single.send_and_verify(MessageType::AdkgAvssFin);
+        assert!(size(&s) < 2 * size(&single));
+
+        // 再次调用不会重新证明
+        assert!(s.send_and_verify(MessageType::VabaAvssFin).is_some());
+    }
+
+    #[test]
+    fn deal_secret() {
+        // 分发指定的秘密，重构得到的恰好是这些秘密
+        let params = ProtocolParams::new(&Committee::uniform(8, 2, 3), SECURITY_BITS);
+        let secrets: Vec<_> = (0..2).map(|_| Mersenne61Ext::random_element()).collect();
+        let mut s = AvssNode::with_secrets(0, &params, &secrets);
+        s.send_and_verify(MessageType::AdkgAvssFin);
+        assert_eq!(s.reconstruct_of(KEY_SECRET).unwrap(), secrets[KEY_SECRET]);
+        assert_eq!(s.reconstruct_of(COIN_SECRET).unwrap(), secrets[COIN_SECRET]);
+
+        // 零知识模式下同样成立
+        let secret = Mersenne61Ext::from_int(42);
+        let mut s = AvssNode::with_secrets(0, &params.with_zero_knowledge(), &[secret]);
+        s.send_and_verify(MessageType::AdkgAvssFin);
+        assert_eq!(s.reconstruct().unwrap(), secret);
+    }
+
+    #[test]
+    fn reconstruct_from_subset() {
+        let t = 3;
+        let params = ProtocolParams::new(&Committee::uniform(8, 2, t), SECURITY_BITS);
+        let secret = Mersenne61Ext::from_int(7);
+        let mut s = AvssNode::with_secrets(0, &params, &[secret]);
+        s.send_and_verify(MessageType::AdkgAvssFin);
+
+        // 任意顺序的 t + 1 个份额即可重构，多出的份额用于检查一致性
+        let shares = s.shares();
+        let subset: Vec<_> = [6, 1, 4, 2, 7, 0, 3].iter().map(|i| shares[*i].clone()).collect();
+        assert_eq!(s.reconstruct_from(&subset[..t + 1]), Ok(secret));
+        assert_eq!(s.reconstruct_from(&subset), Ok(secret));
+
+        // 篡改的份额被报告出来
+        let mut tampered = subset.clone();
+        tampered[2][1] += Mersenne61Ext::from_int(1);
+        assert_eq!(s.reconstruct_from(&tampered), Err(ReconstructError::Inconsistent(vec![2])));
+    }
+
+    #[test]
+    fn verifiable_opening() {
+        let params = ProtocolParams::new(&Committee::uniform(7, 2, 2), SECURITY_BITS);
+        let secrets = [Mersenne61Ext::from_int(11), Mersenne61Ext::from_int(12)];
+        let mut s = AvssNode::with_secrets(0, &params, &secrets);
+        let rlc = s.send_and_verify(MessageType::AdkgAvssFin).unwrap().proofs[0].transcript.oracle.rlc;
+        let mut openings: Vec<_> = (0..7).map(|i| s.open_share(i)).collect();
+        assert!(openings.iter().all(|o| s.verify_opening(o)));
+
+        // 每个秘密的份额分别检查：KEY + δ 与 COIN - δ / rlc 的线性组合不变，但不能通过验证
+        let mut forged = openings[1].clone();
+        let delta = Mersenne61Ext::from_int(5);
+        let shift = |poly: &MultilinearPolynomial<Mersenne61Ext>, d: Mersenne61Ext| {
+            let mut coefficients = poly.coefficients().clone();
+            coefficients[0] += d;
+            MultilinearPolynomial::new(coefficients)
+        };
+        forged.shares[KEY_SECRET] = shift(&forged.shares[KEY_SECRET], delta);
+        forged.shares[COIN_SECRET] = shift(&forged.shares[COIN_SECRET], -delta * rlc.inverse());
+        assert!(!s.verify_opening(&forged));
+
+        // 篡改份额多项式，或者冒充其他参与方公开份额，都不能通过验证
+        let mut coefficients = openings[0].shares[KEY_SECRET].coefficients().clone();
+        coefficients[0] += Mersenne61Ext::from_int(1);
+        openings[0].shares[KEY_SECRET] = MultilinearPolynomial::new(coefficients);
+        openings[3].party = openings[5].party;
+        assert!(!s.verify_opening(&openings[0]));
+        assert!(!s.verify_opening(&openings[3]));
+
+        let (secret, rejected) = s.reconstruct_verified(KEY_SECRET, &openings);
+        assert_eq!(secret, Some(secrets[KEY_SECRET]));
+        assert_eq!(rejected, vec![0, 3]);
+        let (secret, _) = s.reconstruct_verified(COIN_SECRET, &openings);
+        assert_eq!(secret, Some(secrets[COIN_SECRET]));
+
+        // 通过验证的份额不足 t + 1 个时无法重构
+        assert_eq!(s.reconstruct_verified(KEY_SECRET, &openings[..4]).0, None);
+
+        // 最小和最大的 terminate_round 以及零知识模式下诚实的份额都能通过验证
+        for r in [1, params.log_d() - 1] {
+            for p in [params.clone(), params.clone().with_zero_knowledge()] {
+                let mut s = AvssNode::batch(0, &p.with_terminate_round(r), 2);
+                s.send_and_verify(MessageType::AdkgAvssFin);
+                let openings: Vec<_> = (0..7).map(|i| s.open_share(i)).collect();
+                assert_eq!(s.reconstruct_verified(KEY_SECRET, &openings), (Some(s.reconstruct().unwrap()), vec![]));
+            }
+        }
+    }
+
+    #[test]
+    fn recover_share() {
+        let params = ProtocolParams::new(&Committee::uniform(7, 2, 2), SECURITY_BITS);
+        let mut s = AvssNode::batch(0, &params, 2);
+        s.send_and_verify(MessageType::AdkgAvssFin);
+        let shares = s.shares();
+        let coins = s.shares_of(COIN_SECRET);
+
+        // 第 2 个求值点的参与方没有收到份额，同一行的一个参与方的份额被篡改
+        let n = 1 << params.log_n();
+        s.parties[2 * n].set_shares(&[]);
+        assert!(!s.parties[2 * n].has_share());
+        let mut polys: Vec<_> = (0..2).map(|i| s.parties[2 * n + 1].all_share_of(i)).collect();
+        let mut coefficients = polys[0].coefficients().clone();
+        coefficients[0] += Mersenne61Ext::from_int(1);
+        polys[0] = MultilinearPolynomial::new(coefficients);
+        s.parties[2 * n + 1].set_shares(&polys);
+
+        // 篡改的份额不能通过验证，由其余的份额恢复出正确的份额
+        assert!(s.recover_share(2).is_ok());
+        assert_eq!(s.parties[2 * n].interpolate_share(), shares[2]);
+        assert_eq!(s.parties[2 * n].interpolate_share_of(COIN_SECRET), coins[2]);
+        assert_eq!(s.reconstruct().unwrap(), s.reconstruct_from(&shares).unwrap());
+
+        // 没有帮助者或者同一行和同一列的份额都不足 t + 1 个时返回错误
+        let mut party = s.parties[2 * n].clone();
+        assert_eq!(party.recover(&params, 2 * n, &[]), Err(ReconstructError::NotEnoughShares));
+        let helpers: Vec<_> = (0..2).map(|i| s.open_share(i)).collect();
+        assert_eq!(party.recover(&params, 2 * n, &helpers), Err(ReconstructError::NotEnoughShares));
+    }
+
+    #[test]
+    fn complaint() {
+        let params = ProtocolParams::new(&Committee::uniform(7, 2, 2), SECURITY_BITS);
+        let mut s = AvssNode::batch(0, &params, 2);
+        let msg = s.send_and_verify(MessageType::AdkgAvssFin).unwrap();
+        assert_eq!(msg.proofs.len(), 1 << params.log_n());
+        assert!(s.send_and_verify(MessageType::VabaAvssFin).unwrap().proofs.is_empty());
+        let proof = s.share_proof(2);
+        assert!(proof.verify(&params));
+        assert_eq!(proof.share_of(&params, COIN_SECRET), s.shares_of(COIN_SECRET)[2][1]);
+
+        // Dealer 把份额加密给参与方 2，投诉者公开解密信封时得到的随机数
+        let (ek, dk) = SHARE_KEM.keygen();
+        let seal = |proof: &ShareProof<Mersenne61Ext>| {
+            let mut sealed = proof.clone();
+            seal_proof(0, &mut sealed, &ek);
+            let m = sealed.sealed.as_ref().unwrap().reveal(&SHARE_KEM, &dk).unwrap();
+            (sealed, m)
+        };
+        let open = |p: &ShareProof<Mersenne61Ext>, m: &[u8; 32]| open_revealed(&params, 0, p, &ek, m);
+        let verify = |complaint: &Complaint<Mersenne61Ext>, broadcast: &ShareProof<Mersenne61Ext>| {
+            let commitment = broadcast.transcript.digest();
+            complaint.verify(&params, &commitment, broadcast.sealed.as_ref().unwrap(), open)
+        };
+
+        // 诚实的证明不能用来投诉
+        let (honest, m) = seal(&proof);
+        assert!(!verify(&Complaint::new(honest.clone(), m), &honest));
+
+        // Dealer 发送了错误的份额，投诉成立
+        let mut bad = proof.clone();
+        let mut coefficients = bad.shares[KEY_SECRET].coefficients().clone();
+        coefficients[0] += Mersenne61Ext::from_int(1);
+        bad.shares[KEY_SECRET] = MultilinearPolynomial::new(coefficients);
+        assert!(!bad.verify(&params));
+        let (bad_sealed, bad_m) = seal(&bad);
+        assert!(verify(&Complaint::new(bad_sealed.clone(), bad_m), &bad_sealed));
+
+        // 投诉者篡改诚实 Dealer 的份额：换掉明文、公开别的随机数或者自己重新加密，投诉都不成立
+        let mut tampered = honest.clone();
+        tampered.shares = bad.shares.clone();
+        assert!(!verify(&Complaint::new(tampered, m), &honest));
+        assert!(!verify(&Complaint::new(honest.clone(), bad_m), &honest));
+        assert!(!verify(&Complaint::new(bad_sealed.clone(), bad_m), &honest));
+        assert!(!verify(&Complaint::new(bad.clone(), m), &honest));
+
+        // 承诺不是 Dealer 广播的承诺时投诉不成立
+        let complaint = Complaint::new(bad_sealed.clone(), bad_m);
+        assert!(!complaint.verify(&params, &[0; 32], bad_sealed.sealed.as_ref().unwrap(), open));
+
+        // 查询结果在信封中，与承诺不一致是 Dealer 的错误，投诉成立
+        let mut forged = proof;
+        let j = *forged.function_proofs[1].proof_values.keys().next().unwrap();
+        *forged.function_proofs[1].proof_values.get_mut(&j).unwrap() += Mersenne61Ext::from_int(1);
+        assert!(!forged.verify(&params));
+        let (forged, m) = seal(&forged);
+        assert!(verify(&Complaint::new(forged.clone(), m), &forged));
+    }
+
+    #[test]
+    fn refresh() {
+        // 刷新时分发的密钥份额之和为 0，公共随机数仍然是随机的
+        let params = ProtocolParams::new(&Committee::uniform(7, 2, 2), SECURITY_BITS);
+        let mut s = AvssNode::refresh(0, &params);
+        s.send_and_verify(MessageType::AdkgAvssFin);
+        assert_eq!(s.reconstruct_of(KEY_SECRET).unwrap(), Mersenne61Ext::from_int(0));
+        assert!(s.shares().iter().any(|share| !share[1].is_zero()));
+        assert!(!s.reconstruct_of(COIN_SECRET).unwrap().is_zero());
+    }
+}
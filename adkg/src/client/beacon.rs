@@ -0,0 +1,180 @@
+use util::algebra::field::{as_bytes_vec, Field};
+use util::algebra::field::mersenne61_ext::Mersenne61Ext;
+use util::algebra::reed_solomon::{OnlineDecoder, ReedSolomon};
+use util::params::ProtocolParams;
+use util::signature::{Signature, VerifyingKey};
+use util::vec_check::is_invector;
+
+use super::adkg::AdkgNode;
+
+/// 节点公开的自己的每个求值点上的份额之和，以及节点对它们的签名
+#[derive(Debug, Clone, PartialEq)]
+pub struct SignedSums {
+    pub sender: usize,
+    pub sums: Vec<Mersenne61Ext>,
+    pub signature: Signature,
+}
+
+/// 随机信标一个 epoch 的记录：协商出的 Dealer 集合、每个 Dealer 的承诺、节点签名的份额之和、以及信标的值
+/// 信标的值为 epoch 和所有 Dealer 的秘密之和的哈希，秘密在协商 Dealer 集合之前已经承诺，任何人无法使其偏离
+/// 每个节点用自己这个 epoch 的一次性签名密钥签名 epoch、Dealer 集合、Dealer 的承诺和自己的份额之和，
+/// 任何人都可以用节点的验证密钥检查份额之和，再重新译码出秘密并检查信标的值。
+/// 恶意节点只能签名自己的求值点上错误的份额之和，它们的权重不超过 f，译码时被纠正
+#[derive(Debug, Clone, PartialEq)]
+pub struct BeaconTranscript {
+    pub epoch: u64,
+    pub dealers: Vec<usize>,
+    /// 每个 Dealer 的承诺：它对每个参与方的 `Transcript::digest` 按参与方排序后的哈希
+    pub commitments: Vec<[u8; 32]>,
+    /// 译码时收到的经过签名的份额之和，按收到的顺序排列
+    pub sums: Vec<SignedSums>,
+    pub value: [u8; 32],
+}
+
+impl BeaconTranscript {
+    pub fn new(
+        epoch: u64,
+        dealers: Vec<usize>,
+        commitments: Vec<[u8; 32]>,
+        sums: Vec<SignedSums>,
+        secret: Mersenne61Ext,
+    ) -> Self {
+        let value = Self::hash(epoch, &dealers, secret);
+        BeaconTranscript { epoch, dealers, commitments, sums, value }
+    }
+
+    fn hash(epoch: u64, dealers: &[usize], secret: Mersenne61Ext) -> [u8; 32] {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(b"PQC-ADKG beacon");
+        hasher.update(&epoch.to_le_bytes());
+        for d in dealers {
+            hasher.update(&(*d as u64).to_le_bytes());
+        }
+        hasher.update(&secret.get_real().to_le_bytes());
+        hasher.update(&secret.get_image().to_le_bytes());
+        hasher.finalize().into()
+    }
+
+    /// 节点 `sender` 签名的消息：epoch、Dealer 集合、Dealer 的承诺和自己的每个求值点上的份额之和
+    pub fn message(epoch: u64, dealers: &[usize], commitments: &[[u8; 32]], sender: usize, sums: &[Mersenne61Ext]) -> [u8; 32] {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(b"PQC-ADKG beacon sums");
+        hasher.update(&epoch.to_le_bytes());
+        for (d, commitment) in dealers.iter().zip(commitments) {
+            hasher.update(&(*d as u64).to_le_bytes());
+            hasher.update(commitment);
+        }
+        hasher.update(&(sender as u64).to_le_bytes());
+        hasher.update(&as_bytes_vec(sums));
+        hasher.finalize().into()
+    }
+
+    /// 检查 Dealer 集合的权重超过 f，每个节点的份额之和都经过它的验证密钥 `keys[sender]` 签名，
+    /// 份额之和按照节点的在线纠错规则译码成功，且信标的值与译码出的秘密一致
+    pub fn verify(&self, params: &ProtocolParams<Mersenne61Ext>, keys: &[VerifyingKey]) -> bool {
+        let committee = params.committee();
+        if self.commitments.len() != self.dealers.len() || keys.len() != committee.n() {
+            return false;
+        }
+        let mut dealers = vec![];
+        for d in &self.dealers {
+            if *d >= committee.n() || is_invector(*d, &dealers) {
+                return false;
+            }
+            dealers.push(*d);
+        }
+        if !committee.exceeds_faulty(&dealers) {
+            return false;
+        }
+        let allocation = committee.allocate();
+        let code = ReedSolomon::new(&params.coset_y(), params.t());
+        let mut decoder = OnlineDecoder::new(code, AdkgNode::max_errors(committee));
+        let mut decoded = None;
+        let mut senders = vec![];
+        for signed in &self.sums {
+            let sender = signed.sender;
+            if sender >= committee.n() || is_invector(sender, &senders) || signed.sums.len() != allocation[sender].len() {
+                return false;
+            }
+            let message = Self::message(self.epoch, &self.dealers, &self.commitments, sender, &signed.sums);
+            if !keys[sender].verify(&message, &signed.signature) {
+                return false;
+            }
+            senders.push(sender);
+            for (p, v) in allocation[sender].iter().zip(&signed.sums) {
+                decoded = decoded.or_else(|| decoder.receive(*p, *v));
+            }
+        }
+        match decoded {
+            Some(decoded) => {
+                let secret = decoded.polynomial.evaluation_at(Mersenne61Ext::from_int(0));
+                self.value == Self::hash(self.epoch, &self.dealers, secret)
+            }
+            None => false,
+        }
+    }
+
+    /// 信标的值的十六进制表示
+    pub fn to_hex(&self) -> String {
+        self.value.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BeaconTranscript, SignedSums};
+    use util::algebra::field::Field;
+    use util::algebra::field::mersenne61_ext::Mersenne61Ext;
+    use util::algebra::polynomial::Polynomial;
+    use util::committee::Committee;
+    use util::params::ProtocolParams;
+    use util::signature::keygen;
+    use util::SECURITY_BITS;
+
+    #[test]
+    fn verify() {
+        let params = ProtocolParams::new(&Committee::uniform(7, 2, 2), SECURITY_BITS);
+        let (keys, mut signing): (Vec<_>, Vec<_>) = (0..7).map(|_| keygen()).unzip();
+        let (dealers, commitments) = (vec![0, 4, 5], vec![[1u8; 32], [4u8; 32], [5u8; 32]]);
+        let poly = Polynomial::random_polynomial(3);
+        let secret = poly.coefficients()[0];
+        // 节点 2 签名了错误的份额之和，译码时被纠正
+        let sums: Vec<_> = (0..7)
+            .map(|p| {
+                let mut sum = vec![poly.evaluation_at(params.share_point(p))];
+                if p == 2 {
+                    sum[0] += Mersenne61Ext::from_int(1);
+                }
+                let message = BeaconTranscript::message(3, &dealers, &commitments, p, &sum);
+                SignedSums { sender: p, sums: sum, signature: signing[p].sign(&message).unwrap() }
+            })
+            .collect();
+        let transcript = BeaconTranscript::new(3, dealers.clone(), commitments.clone(), sums.clone(), secret);
+        assert!(transcript.verify(&params, &keys));
+        assert_ne!(transcript.value, BeaconTranscript::new(4, dealers.clone(), commitments.clone(), sums.clone(), secret).value);
+
+        // 秘密错误、份额不足以译码、Dealer 集合太小时都不能通过验证
+        let wrong = BeaconTranscript::new(3, dealers.clone(), commitments.clone(), sums.clone(), secret + Mersenne61Ext::from_int(1));
+        assert!(!wrong.verify(&params, &keys));
+        let few = BeaconTranscript::new(3, dealers.clone(), commitments.clone(), sums[..4].to_vec(), secret);
+        assert!(!few.verify(&params, &keys));
+        let small = BeaconTranscript::new(3, vec![0, 0, 4], commitments.clone(), sums.clone(), secret);
+        assert!(!small.verify(&params, &keys));
+
+        // 任意的份额之和、换掉 Dealer 的承诺或者换成别的 epoch 都不能通过签名的检查
+        let other = Polynomial::random_polynomial(3);
+        let mut forged = transcript.clone();
+        for (p, signed) in forged.sums.iter_mut().enumerate() {
+            signed.sums = vec![other.evaluation_at(params.share_point(p))];
+        }
+        forged.value = BeaconTranscript::new(3, dealers.clone(), commitments.clone(), vec![], other.coefficients()[0]).value;
+        assert!(!forged.verify(&params, &keys));
+        let mut replaced = transcript.clone();
+        replaced.commitments[1] = [9u8; 32];
+        assert!(!replaced.verify(&params, &keys));
+        let replayed = BeaconTranscript::new(4, dealers, commitments, sums, secret);
+        assert!(!replayed.verify(&params, &keys));
+        let (others, _): (Vec<_>, Vec<_>) = (0..7).map(|_| keygen()).unzip();
+        assert!(!transcript.verify(&params, &others));
+    }
+}
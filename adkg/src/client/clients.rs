@@ -3,37 +3,289 @@ use super::gather::GatherNode;
 use super::adkg::AdkgNode;
 use crate::msg::message::{Message, MessageType};
 use crate::msg::result::AdkgResult;
+use util::params::ProtocolParams;
+use util::algebra::field::mersenne61_ext::Mersenne61Ext;
+use util::algebra::lattice::Lattice;
+use util::kem::{DecapsulationKey, EncapsulationKey};
+use util::signature::{SigningKey, VerifyingKey};
+use super::avss::AvssNode;
+use super::beacon::BeaconTranscript;
+use super::decrypt::{to_hex, DecryptNode};
+use super::kdf::{DerivedKey, KdfNode, Label};
+use super::triple::{TripleError, TripleNode, TripleShares};
+use std::cell::RefCell;
+use std::rc::Rc;
 use std::time::Instant;
 
 pub struct Client{
     pub id: usize,
     pub state: usize,
-    pub n: usize,
-    pub f: usize,
+    pub params: ProtocolParams<Mersenne61Ext>,
     pub additional_data: String,
     gather: GatherNode,
     vaba: VabaNode,
     adkg: AdkgNode,
     start_time: Instant,
+    finished: bool,
+    /// 重新分享时只属于旧委员会的节点只分发份额，不参与协商
+    member: bool,
+    /// 重新分享时没有旧份额的节点不作为 Dealer
+    dealer: bool,
+    /// 格上的密钥的门限解密，每个节点得到公钥后都发起解密请求，使用自己的淹没噪声
+    decrypt: Option<DecryptNode>,
+    /// 得到公钥后还需要发起请求，请求发出后为 false
+    requester: bool,
+    /// 生成乘法三元组时协商出 Dealer 集合之后的乘法
+    triple: Option<TripleNode>,
+    /// 由格上的密钥门限派生子密钥，每个节点都请求全部标签
+    kdf: Option<KdfNode>,
 }
 
 impl Client {
-    pub fn new(id: usize, state: usize, n: usize, f: usize) -> Client {
+    pub fn new(id: usize, state: usize, params: &ProtocolParams<Mersenne61Ext>) -> Client {
+        // 密钥和 VABA 的公共随机数由同一个 Dealer 批量分发，共用一份证明
+        let avss = Rc::new(RefCell::new(AvssNode::batch(id, params, 2)));
         Client {
             id,
             state,
-            n,
-            f,
+            params: params.clone(),
             additional_data: String::new(),
-            gather: GatherNode::new(id, state, n, f),
-            vaba: VabaNode::new(id, state, n, f),
-            adkg: AdkgNode::new(id, state, n, f),
+            gather: GatherNode::new(id, state, params),
+            vaba: VabaNode::new(id, state, params, avss.clone()),
+            adkg: AdkgNode::new(id, state, params, avss),
             start_time: std::time::Instant::now(),
+            finished: false,
+            member: true,
+            dealer: true,
+            decrypt: None,
+            requester: false,
+            triple: None,
+            kdf: None,
         }
     }
 
+    /// 加密分发份额：`dk` 为自己的 KEM 私钥，`keys` 为每个节点的 KEM 公钥
+    pub fn with_encryption(mut self, dk: DecapsulationKey, keys: &[EncapsulationKey]) -> Client {
+        if let Some(kdf) = self.kdf.as_mut() {
+            kdf.set_encryption(dk.clone(), keys);
+        }
+        if let Some(decrypt) = self.decrypt.as_mut() {
+            decrypt.set_encryption(dk.clone(), keys);
+        }
+        self.adkg.set_encryption(dk, keys);
+        self
+    }
+
+    /// 一次生成 `count` 个独立的密钥，密钥和 VABA 的公共随机数仍由同一次批量分发给出
+    pub fn batch(id: usize, state: usize, params: &ProtocolParams<Mersenne61Ext>, count: usize) -> Client {
+        let avss = Rc::new(RefCell::new(AvssNode::batch(id, params, count + 1)));
+        Client {
+            id,
+            state,
+            params: params.clone(),
+            additional_data: String::new(),
+            gather: GatherNode::new(id, state, params),
+            vaba: VabaNode::new(id, state, params, avss.clone()),
+            adkg: AdkgNode::batch(id, state, params, avss, count),
+            start_time: std::time::Instant::now(),
+            finished: false,
+            member: true,
+            dealer: true,
+            decrypt: None,
+            requester: false,
+            triple: None,
+            kdf: None,
+        }
+    }
+
+    /// 随机信标的第 `epoch` 个 epoch：与生成密钥相同，输出所有 Dealer 的秘密之和的哈希
+    /// `key` 为自己这个 epoch 的一次性签名密钥，`keys` 为每个节点的验证密钥，节点签名自己公开的份额之和
+    pub fn beacon(
+        id: usize,
+        state: usize,
+        params: &ProtocolParams<Mersenne61Ext>,
+        epoch: u64,
+        key: SigningKey,
+        keys: &[VerifyingKey],
+    ) -> Client {
+        let mut client = Self::new(id, state, params);
+        client.adkg.set_signing(epoch, key, keys);
+        client
+    }
+
+    /// 主动刷新，`key_shares` 为节点在自己的求值点上当前的密钥份额
+    pub fn refresh(id: usize, state: usize, params: &ProtocolParams<Mersenne61Ext>, key_shares: Vec<Mersenne61Ext>) -> Client {
+        let avss = Rc::new(RefCell::new(AvssNode::refresh(id, params)));
+        Client {
+            id,
+            state,
+            params: params.clone(),
+            additional_data: String::new(),
+            gather: GatherNode::new(id, state, params),
+            vaba: VabaNode::new(id, state, params, avss.clone()),
+            adkg: AdkgNode::refresh(id, state, params, avss, key_shares),
+            start_time: std::time::Instant::now(),
+            finished: false,
+            member: true,
+            dealer: true,
+            decrypt: None,
+            requester: false,
+            triple: None,
+            kdf: None,
+        }
+    }
+
+    /// 把旧委员会 `old` 的密钥重新分享给参数为 `params` 的新委员会
+    /// `key_shares` 为节点在旧委员会中的份额，没有份额的节点只为 VABA 分发公共随机数
+    /// `published` 为旧委员会生成密钥时每个节点公开的份额，用于检查 Dealer
+    /// 编号不小于新委员会节点数的节点只属于旧委员会，分发份额后不再参与
+    pub fn reshare(
+        id: usize,
+        state: usize,
+        params: &ProtocolParams<Mersenne61Ext>,
+        old: &ProtocolParams<Mersenne61Ext>,
+        key_shares: Option<Vec<Mersenne61Ext>>,
+        published: Vec<Option<Vec<Mersenne61Ext>>>,
+    ) -> Client {
+        let avss = match &key_shares {
+            Some(key_shares) => AvssNode::reshare(id, params, key_shares),
+            None => AvssNode::batch(id, params, 2),
+        };
+        let dealer = key_shares.is_some();
+        let avss = Rc::new(RefCell::new(avss));
+        Client {
+            id,
+            state,
+            params: params.clone(),
+            additional_data: String::new(),
+            gather: GatherNode::new(id, state, params),
+            vaba: VabaNode::new(id, state, params, avss.clone()),
+            adkg: AdkgNode::reshare(id, state, params, avss, old, published),
+            start_time: std::time::Instant::now(),
+            finished: false,
+            member: id < params.committee().n(),
+            dealer,
+            decrypt: None,
+            requester: false,
+            triple: None,
+            kdf: None,
+        }
+    }
+
+    /// 生成格上的密钥，`lattice` 为所有节点相同的公开矩阵，之后每个节点用密钥门限解密 `decryptions` 个随机明文
+    pub fn lattice(
+        id: usize,
+        state: usize,
+        params: &ProtocolParams<Mersenne61Ext>,
+        lattice: &Lattice,
+        decryptions: usize,
+    ) -> Client {
+        let mut client = Self::with_lattice(id, state, params, lattice, DecryptNode::groups(params, decryptions));
+        client.decrypt = Some(DecryptNode::new(id, params, lattice, decryptions));
+        client.requester = true;
+        client
+    }
+
+    /// 生成格上的密钥，Dealer 分发 `groups` 组淹没噪声
+    fn with_lattice(
+        id: usize,
+        state: usize,
+        params: &ProtocolParams<Mersenne61Ext>,
+        lattice: &Lattice,
+        groups: usize,
+    ) -> Client {
+        let avss = Rc::new(RefCell::new(AvssNode::lattice(id, params, lattice.params(), groups)));
+        Client {
+            id,
+            state,
+            params: params.clone(),
+            additional_data: String::new(),
+            gather: GatherNode::new(id, state, params),
+            vaba: VabaNode::new(id, state, params, avss.clone()),
+            adkg: AdkgNode::lattice(id, state, params, avss, lattice, groups),
+            start_time: std::time::Instant::now(),
+            finished: false,
+            member: true,
+            dealer: true,
+            decrypt: None,
+            requester: false,
+            triple: None,
+            kdf: None,
+        }
+    }
+
+    /// 生成格上的密钥，之后每个节点都门限派生 `labels` 中每个标签的子密钥，第 k 个标签固定使用第 k 组淹没噪声
+    pub fn kdf(id: usize, state: usize, params: &ProtocolParams<Mersenne61Ext>, lattice: &Lattice, labels: &[Label]) -> Client {
+        let mut client = Self::with_lattice(id, state, params, lattice, labels.len());
+        client.requester = true;
+        client.kdf = Some(KdfNode::new(id, params, lattice, labels));
+        client
+    }
+
+    /// 生成 `count` 个乘法三元组，`double` 为 `params` 改为门限 2t 的参数，所有节点相同
+    /// 委员会不满足 `TripleNode::check` 时返回错误
+    pub fn triples(
+        id: usize,
+        state: usize,
+        params: &ProtocolParams<Mersenne61Ext>,
+        double: &ProtocolParams<Mersenne61Ext>,
+        count: usize,
+    ) -> Result<Client, TripleError> {
+        let triple = TripleNode::new(id, params, count)?;
+        let avss = Rc::new(RefCell::new(AvssNode::triples(id, params, double, count)));
+        Ok(Client {
+            id,
+            state,
+            params: params.clone(),
+            additional_data: String::new(),
+            gather: GatherNode::new(id, state, params),
+            vaba: VabaNode::new(id, state, params, avss.clone()),
+            adkg: AdkgNode::triples(id, state, params, avss, double, count),
+            start_time: std::time::Instant::now(),
+            finished: false,
+            member: true,
+            dealer: true,
+            decrypt: None,
+            requester: false,
+            triple: Some(triple),
+            kdf: None,
+        })
+    }
+
+    /// 节点在自己的求值点上的密钥份额，批量生成时每个求值点上依次为每个密钥的份额
+    pub fn key_shares(&self) -> Option<Vec<Mersenne61Ext>> {
+        self.adkg.key_shares().cloned()
+    }
+
+    /// 节点在自己的每个求值点上的三元组份额
+    pub fn triple_shares(&self) -> Option<Vec<TripleShares>> {
+        self.triple.as_ref()?.triples().cloned()
+    }
+
+    /// 按标签顺序排列的子密钥
+    pub fn derived_keys(&self) -> Vec<DerivedKey> {
+        self.kdf.as_ref().map_or(vec![], |k| k.derived_keys())
+    }
+
+    /// 随机信标的记录，得到密钥后才有值
+    pub fn beacon_transcript(&self) -> Option<BeaconTranscript> {
+        self.adkg.beacon()
+    }
+
+    /// 诚实的委员会成员，运行结束时应当已经得到密钥
+    pub fn honest(&self) -> bool {
+        self.state != 0 && self.member
+    }
+
+    /// 是否已经得到密钥，格上的密钥还需要完成全部解密和派生
+    pub fn finished(&self) -> bool {
+        self.finished
+            && self.decrypt.as_ref().is_none_or(|d| d.finished())
+            && self.kdf.as_ref().is_none_or(|k| k.finished())
+    }
+
     pub fn start(&mut self) -> Option<Message> {
-        if self.state == 0 {
+        if self.state == 0 || !self.dealer {
             return None
         }
 
@@ -41,14 +293,18 @@ impl Client {
     }
 
     pub fn handle_message(&mut self, msg: Message) -> Option<Message> {
-        if self.state == 0 {
+        if self.state == 0 || !self.member {
             return None
         }
 
         let message = match msg.msg_type {
-            MessageType::AdkgAvssFin   => self.adkg.handle_share_fin(msg.sender_id),
+            MessageType::AdkgAvssFin   => self.adkg.handle_share_fin(msg),
             MessageType::AdkgProp      => self.adkg.handle_prop(msg),
             MessageType::AdkgSig       => self.adkg.handle_sig(msg),
+            MessageType::AdkgComplaint => self.adkg.handle_complaint(msg),
+            MessageType::RangeCheck    => self.adkg.handle_range_check(msg),
+            MessageType::RecoverRequest  => self.adkg.handle_recover_request(msg),
+            MessageType::RecoverResponse => self.adkg.handle_recover_response(msg),
             MessageType::VabaAvssFin   => self.vaba.handle_share_fin(msg.sender_id),
             MessageType::VabaAttach    => self.vaba.handle_attach(msg),
             MessageType::VabaSig       => self.vaba.handle_sig(msg),
@@ -57,11 +313,35 @@ impl Client {
             MessageType::Gather1       => self.gather.handle_gather_1(msg),
             MessageType::Gather2       => self.gather.handle_gather_2(msg),
             MessageType::Gather3       => self.gather.handle_gather_3(msg),
+            MessageType::DecRequest    => self.decrypt.as_mut().and_then(|d| d.handle_request(msg)),
+            MessageType::DecShare      => {
+                self.handle_dec_share(msg);
+                None
+            }
+            MessageType::KdfRequest    => self.kdf.as_mut().and_then(|k| k.handle_request(msg)),
+            MessageType::KdfShare      => {
+                self.handle_kdf_share(msg);
+                None
+            }
+            MessageType::TripleStart   => {
+                let dealings = self.adkg.dealings();
+                self.triple.as_mut().and_then(|t| t.start(dealings))
+            }
+            MessageType::TripleCheck   => {
+                if let Some(triple) = self.triple.as_mut() {
+                    triple.handle_check(msg);
+                }
+                None
+            }
+            MessageType::TripleMul     => {
+                if let Some(triple) = self.triple.as_mut() {
+                    triple.handle_mul(msg);
+                }
+                None
+            }
             MessageType::SumAndRec     => {
-                let res = self.adkg.sum_and_rec(msg);
-                match res {
-                    Some(r) => self.end(r),
-                    None => (),
+                if let Some(res) = self.adkg.sum_and_rec(msg) {
+                    self.end(res);
                 }
                 None
             }
@@ -69,7 +349,7 @@ impl Client {
             _ => None,
         };
 
-        match message {
+        let message = match message {
             Some(m) => {
                 // println!("{}", m);
                 match m.msg_type {
@@ -77,17 +357,127 @@ impl Client {
                     MessageType::GatherStart    => self.gather.start(),
                     MessageType::GatherFin      => self.vaba.handle_gather_fin(m),
                     MessageType::VabaFin        => self.adkg.handle_vaba_fin(m),
+                    MessageType::TripleStart    => {
+                        let dealings = self.adkg.dealings();
+                        self.triple.as_mut().and_then(|t| t.start(dealings))
+                    }
                     _ => Some(m),
                 }
             },
             None => None,
+        };
+
+        // 主动刷新时协商出 Dealer 集合之后才处理之前收到的检查值，结果可能在这里得到
+        if !self.finished {
+            if let Some(res) = self.adkg.res.clone() {
+                self.end(res);
+            }
+        }
+
+        if let Some(triple) = self.triple.as_mut() {
+            let message = message.or_else(|| triple.poll());
+            if !self.finished && triple.finished() {
+                println!("client_id:{} status:GET_TRIPLES count:{} excluded:{:?}", self.id, triple.count(), triple.excluded().unwrap());
+                println!("{}", self.start_time.elapsed().as_millis());
+                self.finished = true;
+            }
+            return message
+        }
+
+        // 得到公钥后发起请求，这次有其它消息要发送时留到之后
+        let message = match message {
+            None if self.finished && self.requester => self.request(),
+            message => message,
+        };
+
+        // 得到密钥后回复之前收到的派生请求
+        if let Some(kdf) = self.kdf.as_mut() {
+            if !kdf.ready() {
+                if let Some(res) = self.adkg.res.as_ref() {
+                    if let Err(e) = kdf.set_key(res, self.adkg.key_shares().cloned(), self.adkg.flooding().to_vec()) {
+                        println!("client_id:{} status:KDF_ERROR {}", self.id, e);
+                        self.kdf = None;
+                        return message
+                    }
+                }
+            }
+            return message.or_else(|| kdf.answer())
+        }
+
+        // 得到密钥份额后回复之前收到的解密请求
+        let decrypt = match self.decrypt.as_mut() {
+            Some(decrypt) => decrypt,
+            None => return message,
+        };
+        if !decrypt.ready() {
+            if let Some(key_shares) = self.adkg.key_shares() {
+                decrypt.set_key(key_shares.clone(), self.adkg.flooding().to_vec());
+            }
+        }
+        message.or_else(|| decrypt.answer())
+    }
+
+    /// 发起解密请求的节点得到公钥后加密随机的明文，派生子密钥时请求全部标签
+    fn request(&mut self) -> Option<Message> {
+        self.requester = false;
+        if let Some(kdf) = self.kdf.as_mut() {
+            return kdf.request()
+        }
+        let decrypt = self.decrypt.as_mut()?;
+        let public_key = &self.adkg.res.as_ref()?.public_key;
+        let degree = decrypt.lattice().params().degree();
+        let messages: Vec<Vec<bool>> = (0..decrypt.decryptions())
+            .map(|_| (0..degree).map(|_| rand::random()).collect())
+            .collect();
+        decrypt.request(public_key, &messages)
+    }
+
+    fn handle_dec_share(&mut self, msg: Message) {
+        let decrypt = match self.decrypt.as_mut() {
+            Some(decrypt) => decrypt,
+            None => return,
+        };
+        for slot in decrypt.handle_share(msg) {
+            let plaintext = decrypt.plaintext(slot).unwrap();
+            match decrypt.sent(slot) {
+                Some(sent) => println!("client_id:{} status:DECRYPTED slot:{} plaintext:{} correct:{}", self.id, slot, to_hex(plaintext), sent == plaintext),
+                None => println!("client_id:{} status:DECRYPTED slot:{} plaintext:{}", self.id, slot, to_hex(plaintext)),
+            }
+        }
+    }
+
+    fn handle_kdf_share(&mut self, msg: Message) {
+        let kdf = match self.kdf.as_mut() {
+            Some(kdf) => kdf,
+            None => return,
+        };
+        for slot in kdf.handle_share(msg) {
+            let key = kdf.derived_at(slot).unwrap();
+            println!("client_id:{} status:DERIVED context:{:?} epoch:{} key:{}", self.id, key.label.context, key.label.epoch, key.to_hex());
         }
     }
 
     pub fn end(&mut self, res: AdkgResult){
         // println!("Client {} end", self.id);
         // println!("{}", res);
-        println!("client_id:{} status:GET_SK_PK sk:{} pk:{}", self.id, res.sk, res.pk);
+        if let Some(transcript) = self.beacon_transcript() {
+            println!("client_id:{} status:BEACON epoch:{} value:{}", self.id, transcript.epoch, transcript.to_hex());
+        } else if res.public_key.is_empty() && res.keys.is_empty() {
+            let status = if self.adkg.resharing() { "RESHARED" } else { "REFRESHED" };
+            println!("client_id:{} status:{} dealers:{:?}", self.id, status, res.users);
+        } else if res.public_key.is_empty() {
+            println!("client_id:{} status:GET_SK_PK sk:{} pk:{}", self.id, res.sk, res.pk);
+            if res.keys.len() > 1 {
+                let keys: Vec<_> = res.keys.iter().map(|k| k.get_real()).collect();
+                println!("client_id:{} status:GET_KEYS keys:{:?}", self.id, keys);
+            }
+        } else {
+            println!("client_id:{} status:GET_LATTICE_PK pk:{}", self.id, res.pk);
+        }
+        if !res.faulty.is_empty() {
+            println!("client_id:{} status:FAULTY_SHARES nodes:{:?}", self.id, res.faulty);
+        }
+        self.finished = true;
         //结束进程
         println!("{}", self.start_time.elapsed().as_millis());
     }
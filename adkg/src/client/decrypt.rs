@@ -0,0 +1,312 @@
+use std::collections::HashMap;
+use util::algebra::field::mersenne61_ext::Mersenne61Ext;
+use util::algebra::lattice::{Ciphertext, Lattice};
+use util::kem::{DecapsulationKey, EncapsulationKey};
+use util::params::ProtocolParams;
+
+use super::evaluation::{entries, open, seal, ThresholdEvaluator};
+use crate::msg::message::{decode_values, encode_values, Message, MessageType};
+
+/// 用 ADKG 生成的格上密钥进行门限解密
+/// 每个节点都可以请求解密，节点 r 的第 k 个密文使用 Dealer 预先分发的第 `r * decryptions + k` 组淹没噪声，
+/// 节点不能占用其它节点的淹没噪声。节点以 `ThresholdEvaluator` 门限计算 s^T·u 与淹没噪声之和，足以解密而不泄露 s
+/// 每组淹没噪声只用于一个密文。解密份额以请求者的 KEM 公钥加密，只有请求者能译码出明文，路由和其它节点看不到
+/// 没有设置 KEM 密钥时解密份额以明文广播，任何节点都能译码出明文，只用于本地的单元测试
+pub struct DecryptNode {
+    id: usize,
+    evaluator: ThresholdEvaluator,
+    decapsulation: Option<DecapsulationKey>,
+    keys: Vec<EncapsulationKey>,
+    /// 每个节点至多请求解密的密文个数
+    decryptions: usize,
+    /// 以淹没噪声的组号为键
+    ciphertexts: HashMap<usize, Ciphertext>,
+    /// 已经收到但还没有回复的请求，节点得到密钥份额后回复
+    pending: Vec<usize>,
+    plaintexts: HashMap<usize, Vec<bool>>,
+    /// 自己发起的请求的明文，用于检查解密结果
+    sent: HashMap<usize, Vec<bool>>,
+}
+
+impl DecryptNode {
+    /// `decryptions` 为每个节点至多请求解密的密文个数，Dealer 分发 `groups(params, decryptions)` 组淹没噪声
+    pub fn new(id: usize, params: &ProtocolParams<Mersenne61Ext>, lattice: &Lattice, decryptions: usize) -> DecryptNode {
+        DecryptNode {
+            id,
+            evaluator: ThresholdEvaluator::new(params, lattice),
+            decapsulation: None,
+            keys: Vec::new(),
+            decryptions,
+            ciphertexts: HashMap::new(),
+            pending: Vec::new(),
+            plaintexts: HashMap::new(),
+            sent: HashMap::new(),
+        }
+    }
+
+    /// 所有节点请求解密 `decryptions` 个密文需要的淹没噪声的组数
+    pub fn groups(params: &ProtocolParams<Mersenne61Ext>, decryptions: usize) -> usize {
+        params.committee().n() * decryptions
+    }
+
+    /// 节点 `requester` 的第 `k` 个密文使用的淹没噪声的组号
+    pub fn slot(&self, requester: usize, k: usize) -> usize {
+        requester * self.decryptions + k
+    }
+
+    /// 解密份额以请求者的 KEM 公钥加密：`dk` 为自己的 KEM 私钥，`keys` 为每个节点的 KEM 公钥
+    pub fn set_encryption(&mut self, dk: DecapsulationKey, keys: &[EncapsulationKey]) {
+        self.decapsulation = Some(dk);
+        self.keys = keys.to_vec();
+    }
+
+    /// ADKG 结束后设置自己的求值点上 s 的份额和淹没噪声的份额，格式与 `AdkgNode` 相同
+    pub fn set_key(&mut self, key_shares: Vec<Mersenne61Ext>, flooding: Vec<Vec<Mersenne61Ext>>) {
+        self.evaluator.set_key(key_shares, flooding);
+    }
+
+    pub fn lattice(&self) -> &Lattice {
+        self.evaluator.lattice()
+    }
+
+    pub fn decryptions(&self) -> usize {
+        self.decryptions
+    }
+
+    pub fn ready(&self) -> bool {
+        self.evaluator.ready()
+    }
+
+    /// 已经请求解密
+    pub fn requested(&self) -> bool {
+        !self.sent.is_empty()
+    }
+
+    /// 自己请求解密的密文都已经解密
+    pub fn finished(&self) -> bool {
+        self.requested() && self.sent.keys().all(|slot| self.plaintexts.contains_key(slot))
+    }
+
+    /// 使用第 `slot` 组淹没噪声的密文的解密结果
+    pub fn plaintext(&self, slot: usize) -> Option<&Vec<bool>> {
+        self.plaintexts.get(&slot)
+    }
+
+    /// 自己发起的请求中使用第 `slot` 组淹没噪声的明文
+    pub fn sent(&self, slot: usize) -> Option<&Vec<bool>> {
+        self.sent.get(&slot)
+    }
+
+    /// 用公钥加密 `messages` 并请求解密，`msg_content` 为密文在自己的请求中的序号 k
+    pub fn request(&mut self, public_key: &[Mersenne61Ext], messages: &[Vec<bool>]) -> Option<Message> {
+        assert!(messages.len() <= self.decryptions);
+        let mut values = vec![];
+        for (k, m) in messages.iter().enumerate() {
+            let ciphertext = self.evaluator.lattice().encrypt(public_key, m);
+            values.extend(&ciphertext.u);
+            values.extend(&ciphertext.v);
+            self.sent.insert(self.slot(self.id, k), m.clone());
+        }
+        let slots: Vec<_> = (0..messages.len()).map(|k| self.slot(self.id, k)).collect();
+        println!("client_id:{} status:DEC_REQUEST slots:{:?}", self.id, slots);
+        let indices = (0..messages.len()).collect();
+        Some(Message::send_message_with_addi(self.id, vec![], MessageType::DecRequest, indices, encode_values(&values)))
+    }
+
+    /// 收到解密请求，`msg_content` 为每个密文在发送者的请求中的序号，只能使用发送者自己的淹没噪声
+    /// 一组淹没噪声已经用于另一个密文时拒绝解密
+    pub fn handle_request(&mut self, msg: Message) -> Option<Message> {
+        let params = self.lattice().params();
+        let (dimension, degree) = (params.dimension(), params.degree());
+        let values = decode_values(&msg.additional)?;
+        if values.len() != msg.msg_content.len() * (dimension + degree) {
+            return None
+        }
+        self.evaluator.width(msg.sender_id)?;
+        for (k, values) in msg.msg_content.iter().zip(values.chunks(dimension + degree)) {
+            let ciphertext = Ciphertext {
+                u: values[..dimension].to_vec(),
+                v: values[dimension..].to_vec(),
+            };
+            let slot = self.slot(msg.sender_id, *k);
+            if *k >= self.decryptions || self.ciphertexts.contains_key(&slot) {
+                continue
+            }
+            self.ciphertexts.insert(slot, ciphertext);
+            self.pending.push(slot);
+        }
+        self.answer()
+    }
+
+    /// 得到密钥份额后，为所有未回复的请求发送解密份额，格式与 `KdfNode` 的派生份额相同：
+    /// `msg_content` 依次为每个请求者、组的个数和组号，`additional` 为以分号分隔的每个请求者的解密份额
+    pub fn answer(&mut self) -> Option<Message> {
+        if !self.ready() || self.pending.is_empty() {
+            return None
+        }
+        let mut requesters: Vec<usize> = self.pending.iter().map(|slot| slot / self.decryptions).collect();
+        requesters.sort();
+        requesters.dedup();
+        let (mut content, mut entries) = (vec![], vec![]);
+        for requester in requesters {
+            let slots: Vec<_> = self.pending.iter().filter(|slot| *slot / self.decryptions == requester).copied().collect();
+            let mut values = vec![];
+            for slot in &slots {
+                values.extend(self.evaluator.shares(*slot, &self.ciphertexts[slot].u)?);
+            }
+            let entry = match self.keys.get(requester) {
+                Some(ek) => seal(ek, &share_aad(self.id, requester, &slots), &values)?,
+                None => encode_values(&values),
+            };
+            content.push(requester);
+            content.push(slots.len());
+            content.extend(slots);
+            entries.push(entry);
+        }
+        self.pending.clear();
+        Some(Message::send_message_with_addi(self.id, vec![], MessageType::DecShare, content, entries.join(";")))
+    }
+
+    /// 收到解密份额，只处理发给自己的份额，返回这条消息使得解密完成的密文的组号
+    pub fn handle_share(&mut self, msg: Message) -> Vec<usize> {
+        let width = match self.evaluator.width(msg.sender_id) {
+            Some(width) => width,
+            None => return vec![],
+        };
+        let mut res = vec![];
+        for (requester, slots, entry) in entries(&msg) {
+            if requester != self.id {
+                continue
+            }
+            let values = match &self.decapsulation {
+                Some(dk) => open(dk, &share_aad(msg.sender_id, requester, &slots), entry),
+                None => decode_values(entry),
+            };
+            let values = match values {
+                Some(values) if values.len() == slots.len() * width => values,
+                _ => return res,
+            };
+            for (slot, values) in slots.iter().zip(values.chunks(width)) {
+                if !self.ciphertexts.contains_key(slot) || self.plaintexts.contains_key(slot) {
+                    continue
+                }
+                if let Some(w) = self.evaluator.receive(msg.sender_id, *slot, values) {
+                    let plaintext = self.evaluator.lattice().decode(&self.ciphertexts[slot], &w);
+                    self.plaintexts.insert(*slot, plaintext);
+                    res.push(*slot);
+                }
+            }
+        }
+        res
+    }
+}
+
+/// 解密份额的信封的关联数据，绑定发送者、请求者和组号
+fn share_aad(sender: usize, requester: usize, slots: &[usize]) -> Vec<u8> {
+    format!("DEC_SHARE {} {} {:?}", sender, requester, slots).into_bytes()
+}
+
+/// 明文的十六进制表示，每 4 个比特一位，低位在前
+pub fn to_hex(bits: &[bool]) -> String {
+    bits.chunks(4)
+        .map(|c| {
+            let v = c.iter().enumerate().map(|(i, b)| (*b as u32) << i).sum::<u32>();
+            std::char::from_digit(v, 16).unwrap()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DecryptNode;
+    use crate::client::avss::SHARE_KEM;
+    use crate::client::evaluation::tests::share;
+    use crate::msg::message::{decode_values, encode_values, Message, MessageType};
+    use util::algebra::field::Field;
+    use util::algebra::field::mersenne61_ext::Mersenne61Ext;
+    use util::algebra::lattice::{Lattice, LatticeParams};
+    use util::committee::Committee;
+    use util::params::ProtocolParams;
+    use util::SECURITY_BITS;
+
+    #[test]
+    fn threshold_decryption() {
+        let params = ProtocolParams::new(&Committee::uniform(7, 2, 2), SECURITY_BITS);
+        let lattice_params = LatticeParams::new(16, 2, 2);
+        let lattice = Lattice::new(lattice_params, b"seed");
+        let s = lattice_params.sample();
+        let public_key = lattice.public_key(&s, &lattice_params.sample());
+        let s_shares = share(&params, &s);
+        let groups = DecryptNode::groups(&params, 2);
+        let flooding_shares = share(&params, &lattice_params.sample_flooding(groups * 16));
+        let mut nodes: Vec<_> = (0..7)
+            .map(|j| {
+                let mut node = DecryptNode::new(j, &params, &lattice, 2);
+                node.set_key(s_shares[j].clone(), vec![flooding_shares[j].clone()]);
+                node
+            })
+            .collect();
+
+        // 节点 0 请求解密两个密文，节点 1 的解密份额是错误的
+        let messages: Vec<Vec<bool>> = (0..2).map(|k| (0..16).map(|i| (i + k) % 3 == 0).collect()).collect();
+        let request = nodes[0].request(&public_key, &messages).unwrap();
+        let mut shares: Vec<_> = nodes.iter_mut().map(|n| n.handle_request(request.clone()).unwrap()).collect();
+        let mut values = decode_values(&shares[1].additional).unwrap();
+        values[3] += Mersenne61Ext::from_int(1);
+        shares[1].additional = encode_values(&values);
+
+        // 同一组淹没噪声不会用于另一个密文，其它节点只能使用自己的淹没噪声
+        let values = decode_values(&request.additional).unwrap();
+        let other = encode_values(&values[values.len() / 2..]);
+        let again = Message::send_message_with_addi(0, vec![], MessageType::DecRequest, vec![0], other.clone());
+        assert!(nodes[2].handle_request(again).is_none());
+        let beyond = Message::send_message_with_addi(5, vec![], MessageType::DecRequest, vec![2], other.clone());
+        assert!(nodes[2].handle_request(beyond).is_none());
+        let other = Message::send_message_with_addi(5, vec![], MessageType::DecRequest, vec![0], other);
+        assert_eq!(nodes[2].handle_request(other).unwrap().msg_content, vec![5, 1, nodes[2].slot(5, 0)]);
+
+        let mut done = vec![];
+        for msg in shares {
+            done.extend(nodes[0].handle_share(msg));
+        }
+        done.sort();
+        assert_eq!(done, vec![0, 1]);
+        assert!(nodes[0].finished());
+        for k in 0..2 {
+            assert_eq!(nodes[0].plaintext(k), nodes[0].sent(k));
+        }
+        assert!(!nodes[5].finished());
+    }
+
+    #[test]
+    fn sealed_shares() {
+        // 解密份额以请求者的公钥加密，其它节点收到同样的消息也译码不出明文
+        let params = ProtocolParams::new(&Committee::uniform(7, 2, 2), SECURITY_BITS);
+        let lattice_params = LatticeParams::new(16, 2, 2);
+        let lattice = Lattice::new(lattice_params, b"seed");
+        let s = lattice_params.sample();
+        let public_key = lattice.public_key(&s, &lattice_params.sample());
+        let s_shares = share(&params, &s);
+        let flooding_shares = share(&params, &lattice_params.sample_flooding(DecryptNode::groups(&params, 1) * 16));
+        let (keys, dks): (Vec<_>, Vec<_>) = (0..7).map(|_| SHARE_KEM.keygen()).unzip();
+        let mut nodes: Vec<_> = (0..7)
+            .map(|j| {
+                let mut node = DecryptNode::new(j, &params, &lattice, 1);
+                node.set_encryption(dks[j].clone(), &keys);
+                node.set_key(s_shares[j].clone(), vec![flooding_shares[j].clone()]);
+                node
+            })
+            .collect();
+        let message: Vec<bool> = (0..16).map(|i| i % 3 == 0).collect();
+        let request = nodes[2].request(&public_key, std::slice::from_ref(&message)).unwrap();
+        let shares: Vec<_> = nodes.iter_mut().map(|n| n.handle_request(request.clone()).unwrap()).collect();
+        assert!(shares.iter().all(|msg| decode_values(&msg.additional).is_none()));
+        for msg in &shares {
+            assert!(nodes[3].handle_share(msg.clone()).is_empty());
+        }
+        assert!(nodes[3].plaintext(2).is_none());
+        let done: Vec<_> = shares.into_iter().flat_map(|msg| nodes[2].handle_share(msg)).collect();
+        assert_eq!(done, vec![2]);
+        assert_eq!(nodes[2].plaintext(2), Some(&message));
+    }
+}
@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+use util::algebra::field::Field;
+use util::algebra::field::mersenne61_ext::Mersenne61Ext;
+use util::algebra::lattice::Lattice;
+use util::algebra::reed_solomon::{OnlineDecoder, ReedSolomon};
+use util::envelope::Envelope;
+use util::kem::{DecapsulationKey, EncapsulationKey};
+use util::params::ProtocolParams;
+
+use super::adkg::AdkgNode;
+use super::avss::SHARE_KEM;
+use crate::msg::message::{decode_values, encode_values, Message};
+
+/// 门限计算 w = s^T·u + 淹没噪声，s 为 ADKG 生成的格上的密钥，不被任何节点重构
+/// 节点在自己的每个求值点上公开 s 的份额与 u 的内积加上第 slot 组淹没噪声的份额，它们位于同一个 t 次多项式上，
+/// 纠错译码得到的 w 是一个 LWE 样本，不泄露 s。门限解密和门限派生共用
+/// 每组淹没噪声只能用于一个 u，否则两个结果之差泄露 s^T·(u - u')
+pub struct ThresholdEvaluator {
+    lattice: Lattice,
+    code: ReedSolomon<Mersenne61Ext>,
+    max_errors: usize,
+    allocation: Vec<Vec<usize>>,
+    key_shares: Option<Vec<Mersenne61Ext>>,
+    flooding: Vec<Vec<Mersenne61Ext>>,
+    decoders: HashMap<usize, Vec<OnlineDecoder<Mersenne61Ext>>>,
+    combined: HashMap<usize, Vec<Option<Mersenne61Ext>>>,
+}
+
+impl ThresholdEvaluator {
+    pub fn new(params: &ProtocolParams<Mersenne61Ext>, lattice: &Lattice) -> ThresholdEvaluator {
+        ThresholdEvaluator {
+            lattice: lattice.clone(),
+            code: ReedSolomon::new(&params.coset_y(), params.t()),
+            max_errors: AdkgNode::max_errors(params.committee()),
+            allocation: params.committee().allocate(),
+            key_shares: None,
+            flooding: Vec::new(),
+            decoders: HashMap::new(),
+            combined: HashMap::new(),
+        }
+    }
+
+    /// ADKG 结束后设置自己的求值点上 s 的份额和淹没噪声的份额，格式与 `AdkgNode` 相同
+    pub fn set_key(&mut self, key_shares: Vec<Mersenne61Ext>, flooding: Vec<Vec<Mersenne61Ext>>) {
+        self.key_shares = Some(key_shares);
+        self.flooding = flooding;
+    }
+
+    pub fn lattice(&self) -> &Lattice {
+        &self.lattice
+    }
+
+    pub fn ready(&self) -> bool {
+        self.key_shares.is_some()
+    }
+
+    /// 自己的每个求值点上以第 `slot` 组淹没噪声计算 u 的份额，依次排列，没有密钥份额时返回 None
+    pub fn shares(&self, slot: usize, u: &[Mersenne61Ext]) -> Option<Vec<Mersenne61Ext>> {
+        let key_shares = self.key_shares.as_ref()?;
+        let (dimension, degree) = (self.lattice.params().dimension(), self.lattice.params().degree());
+        let mut values = vec![];
+        for (s, flooding) in key_shares.chunks(dimension).zip(&self.flooding) {
+            let share = self.lattice.inner_product(s, u);
+            values.extend(share.iter().zip(&flooding[slot * degree..]).map(|(x, f)| *x + *f));
+        }
+        Some(values)
+    }
+
+    /// 节点 `sender` 的一组份额的长度，节点不存在时返回 None
+    pub fn width(&self, sender: usize) -> Option<usize> {
+        Some(self.allocation.get(sender)?.len() * self.lattice.params().degree())
+    }
+
+    /// 收到节点 `sender` 在第 `slot` 组上的份额，纠错译码完成时返回 w
+    /// 调用者不再为已经返回过 w 的组调用
+    pub fn receive(&mut self, sender: usize, slot: usize, values: &[Mersenne61Ext]) -> Option<Vec<Mersenne61Ext>> {
+        if Some(values.len()) != self.width(sender) {
+            return None
+        }
+        let degree = self.lattice.params().degree();
+        let decoders = self.decoders.entry(slot).or_insert_with(|| {
+            (0..degree).map(|_| OnlineDecoder::new(self.code.clone(), self.max_errors)).collect()
+        });
+        let combined = self.combined.entry(slot).or_insert_with(|| vec![None; degree]);
+        for (p, values) in self.allocation[sender].iter().zip(values.chunks(degree)) {
+            for ((decoder, w), v) in decoders.iter_mut().zip(combined.iter_mut()).zip(values) {
+                if w.is_none() {
+                    *w = decoder
+                        .receive(*p, *v)
+                        .map(|d| d.polynomial.evaluation_at(Mersenne61Ext::from_int(0)));
+                }
+            }
+        }
+        let w = combined.iter().cloned().collect::<Option<Vec<_>>>()?;
+        self.decoders.remove(&slot);
+        self.combined.remove(&slot);
+        Some(w)
+    }
+}
+
+/// 以请求者的公钥加密一组份额，编码为 `封装的十六进制:密文的十六进制`
+pub fn seal(ek: &EncapsulationKey, aad: &[u8], values: &[Mersenne61Ext]) -> Option<String> {
+    let envelope = Envelope::seal(&SHARE_KEM, ek, aad, encode_values(values).as_bytes())?;
+    Some(format!("{}:{}", hex::encode(envelope.encapsulation), hex::encode(envelope.body)))
+}
+
+pub fn open(dk: &DecapsulationKey, aad: &[u8], entry: &str) -> Option<Vec<Mersenne61Ext>> {
+    let (encapsulation, body) = entry.split_once(':')?;
+    let envelope = Envelope {
+        encapsulation: hex::decode(encapsulation).ok()?,
+        body: hex::decode(body).ok()?,
+    };
+    let plaintext = envelope.open(&SHARE_KEM, dk, aad)?;
+    decode_values(std::str::from_utf8(&plaintext).ok()?)
+}
+
+/// 回复消息中发给每个请求者的一项：请求者、组号和份额
+/// `msg_content` 依次为每个请求者、组的个数和组号，`additional` 为以分号分隔的每个请求者的份额，格式错误时只返回之前的项
+pub fn entries(msg: &Message) -> Vec<(usize, Vec<usize>, &str)> {
+    let mut entries = msg.additional.split(';');
+    let mut content = &msg.msg_content[..];
+    let mut res = vec![];
+    while content.len() >= 2 {
+        let (requester, count) = (content[0], content[1]);
+        let (slots, entry) = match (content.get(2..2 + count), entries.next()) {
+            (Some(slots), Some(entry)) => (slots.to_vec(), entry),
+            _ => break,
+        };
+        content = &content[2 + count..];
+        res.push((requester, slots, entry));
+    }
+    res
+}
+
+#[cfg(test)]
+pub mod tests {
+    use util::algebra::field::mersenne61_ext::Mersenne61Ext;
+    use util::algebra::polynomial::Polynomial;
+    use util::params::ProtocolParams;
+
+    /// 系数为 `secrets` 的 t 次多项式在 n 个求值点上的取值，返回值的第 j 项为节点 j 的份额
+    pub fn share(params: &ProtocolParams<Mersenne61Ext>, secrets: &[Mersenne61Ext]) -> Vec<Vec<Mersenne61Ext>> {
+        let polynomials: Vec<_> = secrets
+            .iter()
+            .map(|s| {
+                let mut coefficients = Polynomial::random_polynomial(params.t() + 1).coefficients().clone();
+                coefficients[0] = *s;
+                Polynomial::new(coefficients)
+            })
+            .collect();
+        (0..params.committee().n())
+            .map(|j| polynomials.iter().map(|p| p.evaluation_at(params.share_point(j))).collect())
+            .collect()
+    }
+}
@@ -1,4 +1,7 @@
 use util::vec_check::{is_invector, is_subset, is_equal};
+use util::committee::Committee;
+use util::params::ProtocolParams;
+use util::algebra::field::mersenne61_ext::Mersenne61Ext;
 use crate::msg::message::{Message, MessageType};
 
 
@@ -8,7 +11,7 @@ use std::collections::HashMap;
 #[derive(Debug)]
 pub struct GatherNode {
     id: usize,
-    n_f: usize,
+    committee: Committee,
     state: usize,
     set_r: Vec<(usize, Vec<usize>)>,
     set_s: Vec<usize>,
@@ -19,10 +22,10 @@ pub struct GatherNode {
 }
 
 impl GatherNode {
-    pub fn new(id: usize, state: usize, n: usize, f: usize) -> GatherNode {
+    pub fn new(id: usize, state: usize, params: &ProtocolParams<Mersenne61Ext>) -> GatherNode {
         GatherNode {
             id,
-            n_f: n-f,
+            committee: params.committee().clone(),
             state,
             set_r: Vec::new(),
             set_s: Vec::new(),
@@ -51,6 +54,8 @@ impl GatherNode {
             msg_type: msg_type,
             msg_content: msg_content.clone(),
             additional: String::new(),
+            proofs: Default::default(),
+            signature: None,
          })
     }
 
@@ -62,7 +67,7 @@ impl GatherNode {
             return None
         }
 
-        if self.set_s.len() >= self.n_f {
+        if self.committee.reaches_quorum(&self.set_s) {
             self.send_message(MessageType::Gather2, self.set_s.clone())
         }else {
             None
@@ -79,7 +84,7 @@ impl GatherNode {
             return None;
         }
 
-        if self.set_t.len() >= self.n_f {
+        if self.committee.reaches_quorum(&self.set_t) {
             self.send_message(MessageType::Gather3, self.set_t.clone())
         }else {
             None
@@ -103,7 +108,7 @@ impl GatherNode {
         }
 
         // println!("Node {} set_u: {:?} ", self.id, self.set_u);
-        if self.set_u.len() >= self.n_f {
+        if self.committee.reaches_quorum(&self.set_u) {
             if self.fin {
                 return None
             }
@@ -0,0 +1,385 @@
+use std::collections::{HashMap, HashSet};
+use util::algebra::field::as_bytes_vec;
+use util::algebra::field::mersenne61_ext::Mersenne61Ext;
+use util::algebra::lattice::Lattice;
+use util::kem::{DecapsulationKey, EncapsulationKey};
+use util::params::ProtocolParams;
+
+use super::evaluation::{entries, open, seal, ThresholdEvaluator};
+use crate::msg::message::{decode_values, encode_values, Message, MessageType};
+use crate::msg::result::AdkgResult;
+
+/// 由标签展开公开向量 u 时的 blake3 上下文
+const INPUT_CONTEXT: &str = "PQC-ADKG threshold KDF input";
+
+/// 派生请求的标签：上下文字符串和 epoch，不同的标签得到相互独立的子密钥
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Label {
+    pub context: String,
+    pub epoch: u64,
+}
+
+impl Label {
+    pub fn new(context: &str, epoch: u64) -> Self {
+        Label { context: context.to_string(), epoch }
+    }
+
+    /// 标签对应的公开向量 u，由标签的哈希展开，任何人都不能选择 u
+    pub fn input(&self, lattice: &Lattice) -> Vec<Mersenne61Ext> {
+        let mut hasher = blake3::Hasher::new_derive_key(INPUT_CONTEXT);
+        hasher.update(&self.epoch.to_le_bytes());
+        hasher.update(self.context.as_bytes());
+        lattice.uniform_vector(&mut hasher.finalize_xof())
+    }
+}
+
+/// 派生出的子密钥：以标签的上下文字符串为 blake3 derive_key 模式的上下文，
+/// 由 epoch、密钥的指纹 `AdkgResult::pk` 和门限计算出的 w = s^T·u + 淹没噪声 得到
+/// 每个标签固定使用一组淹没噪声，节点译码出相同的 w，因此子密钥只取决于标签和这次 ADKG 生成的密钥
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DerivedKey {
+    pub label: Label,
+    pub key: [u8; 32],
+}
+
+impl DerivedKey {
+    pub fn new(label: &Label, group: &str, w: &[Mersenne61Ext]) -> Self {
+        let mut material = label.epoch.to_le_bytes().to_vec();
+        material.extend((group.len() as u64).to_le_bytes());
+        material.extend(group.as_bytes());
+        material.extend(as_bytes_vec(w));
+        DerivedKey {
+            label: label.clone(),
+            key: blake3::derive_key(&label.context, &material),
+        }
+    }
+
+    /// 由子密钥扩展出任意长度的种子
+    pub fn expand(&self, len: usize) -> Vec<u8> {
+        let mut seed = vec![0u8; len];
+        blake3::Hasher::new_keyed(&self.key).finalize_xof().fill(&mut seed);
+        seed
+    }
+
+    pub fn to_hex(&self) -> String {
+        self.key.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+}
+
+/// 无法派生子密钥的原因
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KdfError {
+    /// ADKG 生成的是域上的密钥，公开它与 u 之积就泄露密钥
+    NotLatticeKey,
+}
+
+impl std::fmt::Display for KdfError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KdfError::NotLatticeKey => write!(f, "the threshold KDF needs a lattice key"),
+        }
+    }
+}
+
+/// 由 ADKG 生成的密钥门限派生子密钥，密钥 s 不被任何节点重构：Dealer 的份额连同证明只加密给求值点的所有者，
+/// 广播中只有承诺，派生时每个节点公开的也只是 s^T·u 与淹没噪声之和的份额
+/// 只适用于格上的密钥（`AdkgResult::public_key` 不为空）：域上的密钥 x 与公开的 u 之积是线性的，公开它就泄露 x，
+/// 而 s^T·u 加上淹没噪声是一个 LWE 样本。门限计算由 `ThresholdEvaluator` 完成
+/// 标签在生成密钥之前确定，第 k 个标签固定使用 Dealer 分发的第 k 组淹没噪声，同一组淹没噪声因此只用于一个 u
+/// 任何节点都可以请求派生，派生份额以请求者的 KEM 公钥加密，只有请求者能译码出子密钥，路由和其它节点看不到
+/// 没有设置 KEM 密钥时派生份额以明文广播，只用于本地的单元测试
+pub struct KdfNode {
+    id: usize,
+    evaluator: ThresholdEvaluator,
+    labels: Vec<Label>,
+    /// 密钥的指纹 `AdkgResult::pk`，子密钥与它绑定
+    group: Option<String>,
+    decapsulation: Option<DecapsulationKey>,
+    keys: Vec<EncapsulationKey>,
+    /// 已经回复或者等待回复的请求，每项为请求者和组号
+    requests: HashSet<(usize, usize)>,
+    /// 已经收到但还没有回复的请求，节点得到密钥份额后回复
+    pending: Vec<(usize, usize)>,
+    derived: HashMap<usize, DerivedKey>,
+}
+
+impl KdfNode {
+    /// `labels` 为能派生的全部标签，Dealer 为每个标签分发一组淹没噪声
+    pub fn new(id: usize, params: &ProtocolParams<Mersenne61Ext>, lattice: &Lattice, labels: &[Label]) -> KdfNode {
+        KdfNode {
+            id,
+            evaluator: ThresholdEvaluator::new(params, lattice),
+            labels: labels.to_vec(),
+            group: None,
+            decapsulation: None,
+            keys: Vec::new(),
+            requests: HashSet::new(),
+            pending: Vec::new(),
+            derived: HashMap::new(),
+        }
+    }
+
+    /// 派生份额以请求者的 KEM 公钥加密：`dk` 为自己的 KEM 私钥，`keys` 为每个节点的 KEM 公钥
+    pub fn set_encryption(&mut self, dk: DecapsulationKey, keys: &[EncapsulationKey]) {
+        self.decapsulation = Some(dk);
+        self.keys = keys.to_vec();
+    }
+
+    /// ADKG 结束后设置密钥的指纹，以及自己的求值点上 s 的份额和派生用的淹没噪声的份额，格式与 `DecryptNode` 相同
+    /// 没有密钥份额的节点仍然可以请求派生，只是不回复请求。密钥不是格上的密钥时返回错误，不设置任何内容
+    pub fn set_key(
+        &mut self,
+        result: &AdkgResult,
+        key_shares: Option<Vec<Mersenne61Ext>>,
+        flooding: Vec<Vec<Mersenne61Ext>>,
+    ) -> Result<(), KdfError> {
+        if result.public_key.is_empty() {
+            return Err(KdfError::NotLatticeKey);
+        }
+        self.group = Some(result.pk.clone());
+        if let Some(key_shares) = key_shares {
+            self.evaluator.set_key(key_shares, flooding);
+        }
+        Ok(())
+    }
+
+    pub fn ready(&self) -> bool {
+        self.group.is_some()
+    }
+
+    /// 全部标签的子密钥都已经派生
+    pub fn finished(&self) -> bool {
+        self.derived.len() == self.labels.len()
+    }
+
+    /// 标签 `label` 派生出的子密钥
+    pub fn derived(&self, label: &Label) -> Option<&DerivedKey> {
+        self.derived.values().find(|k| k.label == *label)
+    }
+
+    /// 第 `slot` 个标签派生出的子密钥
+    pub fn derived_at(&self, slot: usize) -> Option<&DerivedKey> {
+        self.derived.get(&slot)
+    }
+
+    /// 按标签的顺序排列的全部子密钥
+    pub fn derived_keys(&self) -> Vec<DerivedKey> {
+        (0..self.labels.len()).filter_map(|k| self.derived_at(k).cloned()).collect()
+    }
+
+    /// 请求派生全部标签的子密钥
+    pub fn request(&mut self) -> Option<Message> {
+        println!("client_id:{} status:KDF_REQUEST labels:{}", self.id, self.labels.len());
+        Some(Message::send_message_with_addi(self.id, vec![], MessageType::KdfRequest, vec![], encode_labels(&self.labels)))
+    }
+
+    /// 收到派生请求，每个标签使用它在 `labels` 中的位置对应的淹没噪声，不在 `labels` 中的标签被忽略
+    /// 同一个请求者重复请求的标签只回复一次
+    pub fn handle_request(&mut self, msg: Message) -> Option<Message> {
+        let labels = decode_labels(&msg.additional)?;
+        self.evaluator.width(msg.sender_id)?;
+        for label in labels {
+            let slot = match self.labels.iter().position(|l| *l == label) {
+                Some(slot) => slot,
+                None => continue,
+            };
+            if self.requests.insert((msg.sender_id, slot)) {
+                self.pending.push((msg.sender_id, slot));
+            }
+        }
+        self.answer()
+    }
+
+    /// 得到密钥份额后，为所有未回复的请求发送派生份额
+    /// `msg_content` 依次为每个请求者、组的个数和组号，`additional` 为以分号分隔的每个请求者的派生份额
+    pub fn answer(&mut self) -> Option<Message> {
+        if !self.evaluator.ready() || self.pending.is_empty() {
+            return None
+        }
+        let mut requesters: Vec<usize> = self.pending.iter().map(|(r, _)| *r).collect();
+        requesters.sort();
+        requesters.dedup();
+        let (mut content, mut entries) = (vec![], vec![]);
+        for requester in requesters {
+            let slots: Vec<_> = self.pending.iter().filter(|(r, _)| *r == requester).map(|(_, k)| *k).collect();
+            let mut values = vec![];
+            for slot in &slots {
+                values.extend(self.evaluator.shares(*slot, &self.labels[*slot].input(self.evaluator.lattice()))?);
+            }
+            let entry = match self.keys.get(requester) {
+                Some(ek) => seal(ek, &share_aad(self.id, requester, &slots), &values)?,
+                None => encode_values(&values),
+            };
+            content.push(requester);
+            content.push(slots.len());
+            content.extend(slots);
+            entries.push(entry);
+        }
+        self.pending.clear();
+        Some(Message::send_message_with_addi(self.id, vec![], MessageType::KdfShare, content, entries.join(";")))
+    }
+
+    /// 收到派生份额，只处理发给自己的份额，返回这条消息使得派生完成的组号
+    pub fn handle_share(&mut self, msg: Message) -> Vec<usize> {
+        let (width, group) = match (self.evaluator.width(msg.sender_id), self.group.clone()) {
+            (Some(width), Some(group)) => (width, group),
+            _ => return vec![],
+        };
+        let mut res = vec![];
+        for (requester, slots, entry) in entries(&msg) {
+            if requester != self.id {
+                continue
+            }
+            let values = match &self.decapsulation {
+                Some(dk) => open(dk, &share_aad(msg.sender_id, requester, &slots), entry),
+                None => decode_values(entry),
+            };
+            let values = match values {
+                Some(values) if values.len() == slots.len() * width => values,
+                _ => return res,
+            };
+            for (slot, values) in slots.iter().zip(values.chunks(width)) {
+                if *slot >= self.labels.len() || self.derived.contains_key(slot) {
+                    continue
+                }
+                if let Some(w) = self.evaluator.receive(msg.sender_id, *slot, values) {
+                    self.derived.insert(*slot, DerivedKey::new(&self.labels[*slot], &group, &w));
+                    res.push(*slot);
+                }
+            }
+        }
+        res
+    }
+}
+
+/// 派生份额的信封的关联数据，绑定发送者、请求者和组号
+fn share_aad(sender: usize, requester: usize, slots: &[usize]) -> Vec<u8> {
+    format!("KDF_SHARE {} {} {:?}", sender, requester, slots).into_bytes()
+}
+
+/// 标签编码为 `epoch:上下文的十六进制`，以逗号分隔
+fn encode_labels(labels: &[Label]) -> String {
+    labels
+        .iter()
+        .map(|l| {
+            let context: String = l.context.bytes().map(|b| format!("{:02x}", b)).collect();
+            format!("{}:{}", l.epoch, context)
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn decode_labels(s: &str) -> Option<Vec<Label>> {
+    if s.is_empty() {
+        return Some(vec![]);
+    }
+    s.split(',')
+        .map(|l| {
+            let (epoch, context) = l.split_once(':')?;
+            if context.len() % 2 != 0 {
+                return None;
+            }
+            let bytes = (0..context.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(context.get(i..i + 2)?, 16).ok())
+                .collect::<Option<Vec<_>>>()?;
+            Some(Label { context: String::from_utf8(bytes).ok()?, epoch: epoch.parse().ok()? })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_labels, encode_labels, DerivedKey, KdfError, KdfNode, Label};
+    use crate::client::avss::SHARE_KEM;
+    use crate::client::evaluation::tests::share;
+    use crate::msg::message::{Message, MessageType};
+    use crate::msg::result::AdkgResult;
+    use util::algebra::field::Field;
+    use util::algebra::field::mersenne61_ext::Mersenne61Ext;
+    use util::algebra::lattice::{Lattice, LatticeParams};
+    use util::committee::Committee;
+    use util::params::ProtocolParams;
+    use util::SECURITY_BITS;
+
+    #[test]
+    fn threshold_derivation() {
+        let params = ProtocolParams::new(&Committee::uniform(7, 2, 2), SECURITY_BITS);
+        let lattice_params = LatticeParams::new(16, 2, 2);
+        let lattice = Lattice::new(lattice_params, b"seed");
+        let s = lattice_params.sample();
+        let flooding = lattice_params.sample_flooding(2 * 16);
+        let mut s_shares = share(&params, &s);
+        let flooding_shares = share(&params, &flooding);
+        let result = AdkgResult {
+            id: 0,
+            users: vec![],
+            sk: String::new(),
+            pk: "group".to_string(),
+            faulty: vec![],
+            public_key: lattice.public_key(&s, &lattice_params.sample()),
+            keys: vec![],
+        };
+        // 节点 1 的密钥份额是错误的，它的派生份额也是错误的
+        s_shares[1][3] += Mersenne61Ext::from_int(1);
+        let labels = vec![Label::new("session key", 7), Label::new("session key, v2", 7)];
+        assert_eq!(decode_labels(&encode_labels(&labels)), Some(labels.clone()));
+        let (keys, dks): (Vec<_>, Vec<_>) = (0..7).map(|_| SHARE_KEM.keygen()).unzip();
+        let mut nodes: Vec<_> = (0..7)
+            .map(|j| {
+                let mut node = KdfNode::new(j, &params, &lattice, &labels);
+                node.set_encryption(dks[j].clone(), &keys);
+                node.set_key(&result, Some(s_shares[j].clone()), vec![flooding_shares[j].clone()]).unwrap();
+                node
+            })
+            .collect();
+
+        // 节点 0 请求全部标签，派生份额以它的公钥加密，其它节点得不到子密钥
+        let request = nodes[0].request().unwrap();
+        let shares: Vec<_> = nodes.iter_mut().map(|n| n.handle_request(request.clone()).unwrap()).collect();
+        for msg in &shares {
+            assert!(nodes[2].handle_share(msg.clone()).is_empty());
+        }
+        let mut done = vec![];
+        for msg in &shares {
+            done.extend(nodes[0].handle_share(msg.clone()));
+        }
+        done.sort();
+        assert_eq!(done, vec![0, 1]);
+        assert!(nodes[0].finished() && !nodes[2].finished());
+
+        // 重复的请求和不在配置中的标签不会得到回复
+        assert!(nodes[2].handle_request(request).is_none());
+        let unknown = Message::send_message_with_addi(0, vec![], MessageType::KdfRequest, vec![], encode_labels(&[Label::new("other", 7)]));
+        assert!(nodes[2].handle_request(unknown).is_none());
+
+        // 节点 3 以另一个顺序请求，每个标签仍使用同一组淹没噪声，得到相同的子密钥
+        let reversed: Vec<_> = labels.iter().rev().cloned().collect();
+        let request = Message::send_message_with_addi(3, vec![], MessageType::KdfRequest, vec![], encode_labels(&reversed));
+        let shares: Vec<_> = nodes.iter_mut().map(|n| n.handle_request(request.clone()).unwrap()).collect();
+        for msg in shares {
+            nodes[3].handle_share(msg);
+        }
+        assert_eq!(nodes[3].derived_keys(), nodes[0].derived_keys());
+
+        // 子密钥由 s^T·u 与第 k 组淹没噪声之和以及密钥的指纹得到，标签不同时子密钥不同
+        for (k, label) in labels.iter().enumerate() {
+            let w: Vec<_> = lattice
+                .inner_product(&s, &label.input(&lattice))
+                .iter()
+                .zip(&flooding[k * 16..])
+                .map(|(x, f)| *x + *f)
+                .collect();
+            assert_eq!(nodes[0].derived(label), Some(&DerivedKey::new(label, "group", &w)));
+        }
+        let keys = nodes[0].derived_keys();
+        assert_ne!(keys[0].key, keys[1].key);
+        assert_eq!(keys[0].expand(64)[..32], keys[0].expand(32)[..]);
+
+        // 域上的密钥不能用于派生，返回错误而不设置密钥
+        let mut node = KdfNode::new(0, &params, &lattice, &labels);
+        let field = AdkgResult { public_key: vec![], ..result };
+        assert_eq!(node.set_key(&field, Some(s_shares[0].clone()), vec![]), Err(KdfError::NotLatticeKey));
+        assert!(!node.ready());
+    }
+}
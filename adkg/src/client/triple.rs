@@ -0,0 +1,379 @@
+use avss::avss::party::ShareProof;
+use util::algebra::field::Field;
+use util::algebra::field::mersenne61_ext::Mersenne61Ext;
+use util::algebra::reed_solomon::{OnlineDecoder, ReedSolomon};
+use util::committee::Committee;
+use util::params::ProtocolParams;
+use util::share::Share;
+use util::vec_check::is_invector;
+
+use crate::msg::message::{decode_values, encode_values, Message, MessageType};
+
+/// 协商出的 Dealer 集合中一个 Dealer 的两次分发在自己的每个求值点上的份额
+/// `single` 为门限 t 的份额 a ‖ b ‖ r ‖ p，`double` 为门限 2t 的份额 r ‖ p
+#[derive(Debug, Clone)]
+pub struct Dealing {
+    pub dealer: usize,
+    pub challenge: Mersenne61Ext,
+    pub single: Vec<Vec<Share<Mersenne61Ext>>>,
+    pub double: Vec<Vec<Share<Mersenne61Ext>>>,
+}
+
+/// 无法生成乘法三元组的原因
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TripleError {
+    /// 权重之和小于 2t + 2f + 1，2t 次的码无法纠正 f 个错误
+    TooFewNodes { n: usize, t: usize, f: usize },
+}
+
+impl std::fmt::Display for TripleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TripleError::TooFewNodes { n, t, f: faulty } => {
+                write!(f, "must have n >= 2t+2f+1 to generate triples, got n = {}, t = {}, f = {}", n, t, faulty)
+            }
+        }
+    }
+}
+
+/// 节点在一个求值点上的乘法三元组的份额，第 i 个三元组满足 c_i = a_i·b_i
+#[derive(Debug, Clone, PartialEq)]
+pub struct TripleShares {
+    pub a: Vec<Mersenne61Ext>,
+    pub b: Vec<Mersenne61Ext>,
+    pub c: Vec<Mersenne61Ext>,
+}
+
+/// 由 Dealer 两次分发的全部承诺得到的挑战，Dealer 分发之后才能确定，用于检查两次分发的 r 和 p 是否相同
+pub fn challenge(proofs: &[ShareProof<Mersenne61Ext>]) -> Mersenne61Ext {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(b"PQC-ADKG triple challenge");
+    for p in proofs {
+        hasher.update(&p.transcript.digest());
+    }
+    let bytes = hasher.finalize();
+    let word = |i: usize| u64::from_le_bytes(bytes.as_bytes()[i..i + 8].try_into().unwrap());
+    Mersenne61Ext::new(word(0), word(8))
+}
+
+/// 由 ADKG 协商出的 Dealer 集合生成乘法三元组，采用双重分享：
+/// 每个 Dealer 以门限 t 分发 a、b、r 和 p，以门限 2t 分发相同的 r 和 p
+/// 节点先公开每个 Dealer 的检查值 Σ γ^(i+1)·(r2_i - r_i) + (p2 - p) 的份额，它是常数项为 0 的随机 2t 次多项式，
+/// 译码后常数项不为 0 的 Dealer 被排除；再对其余 Dealer 的份额求和，公开 a·b - r2 的份额并以 2t 次的码译码，
+/// 加上 r 的份额得到 c = a·b 的 t 次份额。2t 次的码要纠正 f 个错误，需要 n >= 2t + 2f + 1
+pub struct TripleNode {
+    id: usize,
+    count: usize,
+    allocation: Vec<Vec<usize>>,
+    code: ReedSolomon<Mersenne61Ext>,
+    max_errors: usize,
+    dealings: Option<Vec<Dealing>>,
+    /// 得到 Dealer 集合之前收到的检查值
+    pending: Vec<Message>,
+    checkers: Vec<usize>,
+    check_decoders: Vec<OnlineDecoder<Mersenne61Ext>>,
+    checks: Vec<Option<Mersenne61Ext>>,
+    excluded: Option<Vec<usize>>,
+    /// 自己的每个求值点上 a、b 和 r 之和的份额
+    inputs: Option<Vec<[Vec<Mersenne61Ext>; 3]>>,
+    multipliers: Vec<usize>,
+    mul_decoders: Vec<OnlineDecoder<Mersenne61Ext>>,
+    products: Vec<Option<Mersenne61Ext>>,
+    triples: Option<Vec<TripleShares>>,
+}
+
+impl TripleNode {
+    /// `count` 为一次运行生成的三元组的个数，委员会不满足 `check` 时返回错误
+    pub fn new(id: usize, params: &ProtocolParams<Mersenne61Ext>, count: usize) -> Result<TripleNode, TripleError> {
+        let committee = params.committee();
+        Self::check(committee)?;
+        let code = ReedSolomon::new(&params.coset_y(), 2 * params.t());
+        let max_errors = Self::max_errors(committee);
+        Ok(TripleNode {
+            id,
+            count,
+            allocation: committee.allocate(),
+            code: code.clone(),
+            max_errors,
+            dealings: None,
+            pending: Vec::new(),
+            checkers: Vec::new(),
+            check_decoders: Vec::new(),
+            checks: Vec::new(),
+            excluded: None,
+            inputs: None,
+            multipliers: Vec::new(),
+            mul_decoders: (0..count).map(|_| OnlineDecoder::new(code.clone(), max_errors)).collect(),
+            products: vec![None; count],
+            triples: None,
+        })
+    }
+
+    /// 委员会能否生成乘法三元组：2t 次的码要纠正 f 个错误，需要 n >= 2t + 2f + 1
+    pub fn check(committee: &Committee) -> Result<(), TripleError> {
+        let (n, t, f) = (committee.total_weight(), committee.t(), committee.f());
+        if n < 2 * t + 2 * f + 1 {
+            return Err(TripleError::TooFewNodes { n, t, f })
+        }
+        Ok(())
+    }
+
+    /// 2t 次的码能够纠正的错误份额的个数，与 `AdkgNode::max_errors` 相同，但需要 2t + 1 个正确的份额
+    pub fn max_errors(committee: &Committee) -> usize {
+        let honest = committee.total_weight() - committee.f();
+        std::cmp::min(committee.f(), honest.saturating_sub(2 * committee.t() + 1))
+    }
+
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// 自己的每个求值点上的三元组份额，完成乘法后才有值
+    pub fn triples(&self) -> Option<&Vec<TripleShares>> {
+        self.triples.as_ref()
+    }
+
+    /// 两次分发不一致而被排除的 Dealer，检查值译码后才有值
+    pub fn excluded(&self) -> Option<&Vec<usize>> {
+        self.excluded.as_ref()
+    }
+
+    pub fn finished(&self) -> bool {
+        self.triples.is_some()
+    }
+
+    /// 协商出 Dealer 集合并收到它们的份额后，广播自己的每个求值点上每个 Dealer 的检查值的份额
+    pub fn start(&mut self, dealings: Vec<Dealing>) -> Option<Message> {
+        if self.dealings.is_some() || dealings.is_empty() {
+            return None
+        }
+        let k = self.count;
+        let mut values = vec![];
+        for j in 0..self.allocation[self.id].len() {
+            for d in &dealings {
+                let (single, double) = (&d.single[j], &d.double[j]);
+                let mut power = d.challenge;
+                let mut check = double[k].clone() - single[3 * k].clone();
+                for i in 0..k {
+                    check = check + (double[i].clone() - single[2 * k + i].clone()) * power;
+                    power *= d.challenge;
+                }
+                values.push(check.value());
+            }
+        }
+        self.check_decoders = (0..dealings.len()).map(|_| OnlineDecoder::new(self.code.clone(), self.max_errors)).collect();
+        self.checks = vec![None; dealings.len()];
+        self.dealings = Some(dealings);
+        for msg in std::mem::take(&mut self.pending) {
+            self.handle_check(msg);
+        }
+        println!("client_id:{} status:TRIPLE_CHECK", self.id);
+        Some(Message::send_message_with_addi(self.id, vec![], MessageType::TripleCheck, vec![], encode_values(&values)))
+    }
+
+    /// 收到检查值的份额，每个 Dealer 各用一个译码器
+    pub fn handle_check(&mut self, msg: Message) {
+        if self.dealings.is_none() {
+            self.pending.push(msg);
+            return
+        }
+        let width = self.checks.len();
+        Self::receive(&self.allocation, &mut self.checkers, &mut self.check_decoders, &mut self.checks, width, msg);
+    }
+
+    /// 收到 a·b - r2 的份额，每个三元组各用一个译码器
+    pub fn handle_mul(&mut self, msg: Message) {
+        Self::receive(&self.allocation, &mut self.multipliers, &mut self.mul_decoders, &mut self.products, self.count, msg);
+    }
+
+    /// 把一条消息中每个求值点上的 `width` 个值交给对应的译码器，每个节点只接受一次
+    fn receive(
+        allocation: &[Vec<usize>],
+        senders: &mut Vec<usize>,
+        decoders: &mut [OnlineDecoder<Mersenne61Ext>],
+        results: &mut [Option<Mersenne61Ext>],
+        width: usize,
+        msg: Message,
+    ) {
+        if is_invector(msg.sender_id, senders) || msg.sender_id >= allocation.len() {
+            return
+        }
+        let points = &allocation[msg.sender_id];
+        let values = match decode_values(&msg.additional) {
+            Some(values) if values.len() == points.len() * width => values,
+            _ => return,
+        };
+        senders.push(msg.sender_id);
+        for (p, values) in points.iter().zip(values.chunks(width)) {
+            for ((decoder, result), v) in decoders.iter_mut().zip(results.iter_mut()).zip(values) {
+                if result.is_none() {
+                    *result = decoder
+                        .receive(*p, *v)
+                        .map(|d| d.polynomial.evaluation_at(Mersenne61Ext::from_int(0)));
+                }
+            }
+        }
+    }
+
+    /// 检查值全部译码后广播 a·b - r2 的份额，乘积全部译码后得到三元组
+    pub fn poll(&mut self) -> Option<Message> {
+        let message = match self.inputs {
+            None => self.multiply(),
+            Some(_) => None,
+        };
+        self.finish();
+        message
+    }
+
+    fn multiply(&mut self) -> Option<Message> {
+        let dealings = self.dealings.as_ref()?;
+        let checks: Vec<_> = self.checks.iter().cloned().collect::<Option<_>>()?;
+        let k = self.count;
+        let accepted: Vec<_> = dealings.iter().zip(&checks).filter(|(_, c)| c.is_zero()).map(|(d, _)| d).collect();
+        let excluded = dealings.iter().zip(&checks).filter(|(_, c)| !c.is_zero()).map(|(d, _)| d.dealer).collect();
+        let mut inputs = vec![];
+        let mut products = vec![];
+        // 集合中至少有一个诚实的 Dealer，它的检查值为 0，`accepted` 不为空
+        for j in 0..self.allocation[self.id].len() {
+            let sum = |shares: &dyn Fn(&Dealing) -> &Share<Mersenne61Ext>| {
+                let shares: Vec<_> = accepted.iter().map(|d| shares(d).clone()).collect();
+                Share::sum(&shares).value()
+            };
+            let single: Vec<_> = (0..3 * k).map(|i| sum(&|d| &d.single[j][i])).collect();
+            let double: Vec<_> = (0..k).map(|i| sum(&|d| &d.double[j][i])).collect();
+            let (a, b, r) = (&single[..k], &single[k..2 * k], &single[2 * k..]);
+            products.extend(a.iter().zip(b).zip(&double).map(|((a, b), r)| *a * *b - *r));
+            inputs.push([a.to_vec(), b.to_vec(), r.to_vec()]);
+        }
+        println!("client_id:{} status:TRIPLE_MUL excluded:{:?}", self.id, excluded);
+        self.excluded = Some(excluded);
+        self.inputs = Some(inputs);
+        Some(Message::send_message_with_addi(self.id, vec![], MessageType::TripleMul, vec![], encode_values(&products)))
+    }
+
+    fn finish(&mut self) {
+        if self.triples.is_some() {
+            return
+        }
+        let (inputs, products) = match (&self.inputs, self.products.iter().cloned().collect::<Option<Vec<_>>>()) {
+            (Some(inputs), Some(products)) => (inputs, products),
+            _ => return,
+        };
+        self.triples = Some(
+            inputs
+                .iter()
+                .map(|[a, b, r]| TripleShares {
+                    a: a.clone(),
+                    b: b.clone(),
+                    c: products.iter().zip(r).map(|(p, r)| *p + *r).collect(),
+                })
+                .collect(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Dealing, TripleError, TripleNode};
+    use crate::msg::message::{decode_values, encode_values};
+    use util::algebra::field::Field;
+    use util::algebra::field::mersenne61_ext::Mersenne61Ext;
+    use util::algebra::polynomial::Polynomial;
+    use util::committee::Committee;
+    use util::params::ProtocolParams;
+    use util::share::{Domain, Share};
+    use util::SECURITY_BITS;
+
+    /// 常数项为 `secret` 的随机 `degree` 次多项式在节点 j 的求值点上的取值
+    fn share(params: &ProtocolParams<Mersenne61Ext>, secret: Mersenne61Ext, degree: usize) -> Vec<Mersenne61Ext> {
+        let mut coefficients = Polynomial::random_polynomial(degree + 1).coefficients().clone();
+        coefficients[0] = secret;
+        let poly = Polynomial::new(coefficients);
+        (0..params.committee().n()).map(|j| poly.evaluation_at(params.share_point(j))).collect()
+    }
+
+    /// Dealer 分发给 n 个节点的份额，`cheat` 时门限 2t 的 r 与门限 t 的 r 不同
+    fn deal(params: &ProtocolParams<Mersenne61Ext>, dealer: usize, count: usize, cheat: bool) -> Vec<Dealing> {
+        let (n, t) = (params.committee().n(), params.t());
+        let keys: Vec<_> = (0..3 * count + 1).map(|_| Mersenne61Ext::random_element()).collect();
+        let single: Vec<_> = keys.iter().map(|k| share(params, *k, t)).collect();
+        let double: Vec<_> = keys[2 * count..]
+            .iter()
+            .enumerate()
+            .map(|(i, k)| share(params, if cheat && i == 0 { *k + Mersenne61Ext::from_int(1) } else { *k }, 2 * t))
+            .collect();
+        let challenge = Mersenne61Ext::random_element();
+        let domains = (Domain::new(params), Domain::new(&params.clone().with_threshold(2 * t)));
+        (0..n)
+            .map(|j| Dealing {
+                dealer,
+                challenge,
+                single: vec![single.iter().map(|s| Share::dealt(domains.0, j, s[j], dealer)).collect()],
+                double: vec![double.iter().map(|s| Share::dealt(domains.1, j, s[j], dealer)).collect()],
+            })
+            .collect()
+    }
+
+    #[test]
+    fn triples() {
+        let params = ProtocolParams::new(&Committee::uniform(5, 1, 1), SECURITY_BITS);
+        let count = 3;
+        let dealings: Vec<_> = [(0, false), (2, true), (3, false)]
+            .iter()
+            .map(|(d, cheat)| deal(&params, *d, count, *cheat))
+            .collect();
+        let mut nodes: Vec<_> = (0..5).map(|j| TripleNode::new(j, &params, count).unwrap()).collect();
+
+        // 其他节点的检查值先于节点 4 自己的 Dealer 集合到达
+        let mut checks: Vec<_> = (0..4)
+            .map(|j| nodes[j].start(dealings.iter().map(|d| d[j].clone()).collect()).unwrap())
+            .collect();
+        for msg in &checks {
+            nodes[4].handle_check(msg.clone());
+        }
+        checks.push(nodes[4].start(dealings.iter().map(|d| d[4].clone()).collect()).unwrap());
+        for (j, node) in nodes.iter_mut().enumerate() {
+            for msg in &checks {
+                if j < 4 || msg.sender_id == 4 {
+                    node.handle_check(msg.clone());
+                }
+            }
+        }
+
+        // 节点 1 公开错误的乘积，其余节点仍然能够纠错
+        let mut muls: Vec<_> = nodes.iter_mut().map(|n| n.poll().unwrap()).collect();
+        let mut values = decode_values(&muls[1].additional).unwrap();
+        values[0] += Mersenne61Ext::from_int(1);
+        muls[1].additional = encode_values(&values);
+        for node in nodes.iter_mut() {
+            assert_eq!(node.excluded(), Some(&vec![2]));
+            for msg in &muls {
+                node.handle_mul(msg.clone());
+            }
+            assert!(node.poll().is_none());
+            assert!(node.finished());
+        }
+
+        // 由份额重构的 a、b、c 满足 c = a·b
+        let reconstruct = |f: &dyn Fn(usize) -> Mersenne61Ext| {
+            let shares: Vec<_> = (0..5).map(|j| vec![params.share_point(j), f(j)]).collect();
+            Polynomial::reconstruct(&shares, params.t()).unwrap()
+        };
+        for i in 0..count {
+            let triple = |j: usize| nodes[j].triples().unwrap()[0].clone();
+            let a = reconstruct(&|j| triple(j).a[i]);
+            let b = reconstruct(&|j| triple(j).b[i]);
+            let c = reconstruct(&|j| triple(j).c[i]);
+            assert_eq!(c, a * b);
+        }
+    }
+
+    #[test]
+    fn too_few_nodes() {
+        // n = 7 < 2t + 2f + 1 = 9 时返回错误而不是 panic
+        let params = ProtocolParams::new(&Committee::uniform(7, 2, 2), SECURITY_BITS);
+        let error = TripleNode::new(0, &params, 1).err();
+        assert_eq!(error, Some(TripleError::TooFewNodes { n: 7, t: 2, f: 2 }));
+        assert!(TripleNode::new(0, &ProtocolParams::new(&Committee::uniform(5, 1, 1), SECURITY_BITS), 1).is_ok());
+    }
+}
@@ -1,41 +1,47 @@
 // use rand::Rng;
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::rc::Rc;
 
 use util::vec_check::{is_invector, is_subset, is_equal};
+use util::committee::Committee;
+use util::params::ProtocolParams;
+use util::algebra::field::mersenne61_ext::Mersenne61Ext;
 // use util::algebra::field::mersenne61_ext::Mersenne61Ext;
-use super::avss::AvssNode;
+use super::avss::{AvssNode, COIN_SECRET};
 use crate::msg::message::Message;
 use crate::msg::message::MessageType;
 
 pub struct VabaNode {
     id: usize,
     state: usize,
-    f: usize,
+    committee: Committee,
     // secret: Vec<usize>,
     set_dealer: Vec<usize>,
     set_attached: Vec<usize>,
     set_sig: Vec<(usize, Message)>,
     set_indice: Vec<usize>,
     set_fin: HashMap<usize, u64>,
-    avss: AvssNode,
+    avss: Rc<RefCell<AvssNode>>,
     pub res: (usize, u64),
     fin: bool,
 }
 
 impl VabaNode {
     /// new 新建一个 VABA 节点，其中包含所需的 gather 节点
-    pub fn new(id: usize, state: usize, n: usize, f: usize) -> VabaNode {
+    /// 公共随机数取自与 ADKG 共享的批量分发节点中的第 `COIN_SECRET` 个秘密
+    pub fn new(id: usize, state: usize, params: &ProtocolParams<Mersenne61Ext>, avss: Rc<RefCell<AvssNode>>) -> VabaNode {
         VabaNode {
             id,
             state,
-            f,
+            committee: params.committee().clone(),
             // secret: (0..n).map(|_| rand::thread_rng().gen_range(1..usize::MAX/n)).collect(),
             set_dealer: Vec::new(),
             set_attached: Vec::new(),
             set_sig: Vec::new(),
             set_indice: Vec::new(),
             set_fin: HashMap::new(),
-            avss: AvssNode::new(id, log_2_n(n), 1),
+            avss,
             res: (0, 0),
             fin: false,
         }
@@ -54,19 +60,20 @@ impl VabaNode {
             return None
         }
         println!("client_id:{} status:VABA_SHARE_FIN", self.id);
-        self.avss.send_and_verify(MessageType::VabaAvssFin)
+        self.avss.borrow_mut().send_and_verify(MessageType::VabaAvssFin)
     }
     
     /// 作为 Dealer 进行秘密分享，当完成其他参与者的 Share 过程时，将其添加到 set_dealer 中
-    /// 当 set_dealer 中的参与者数量达到 f+1 时，将 set_dealer 赋值给 set_attached，并发送消息 <VABA_ATTACH>
+    /// 当 set_dealer 中的参与者权重达到 f+1 时，将 set_dealer 赋值给 set_attached，并发送消息 <VABA_ATTACH>
     /// 这里进行模拟，表示参数 id 的参与者已经完成了 Share 过程，将其添加到 set_dealer 中
     pub fn handle_share_fin(&mut self, id: usize) -> Option<Message> {
         if is_invector(id, &self.set_dealer) {
             return None
         }
+        let enough = self.committee.exceeds_faulty(&self.set_dealer);
         self.set_dealer.push(id);
 
-        if self.set_dealer.len() == self.f + 1 {
+        if !enough && self.committee.exceeds_faulty(&self.set_dealer) {
             self.set_attached = self.set_dealer.clone();
             return self.send_message(vec![], MessageType::VabaAttach, self.set_attached.clone());
         }
@@ -86,7 +93,7 @@ impl VabaNode {
     }
 
     /// 收到他人的签名信息 <VABA_SIG> 后，将其添加到 set_sig 中
-    /// 如果 set_sig 中的签名数量达到 f+1，则调用 GatherStart 进行求交
+    /// 如果 set_sig 中签名者的权重达到 f+1，则调用 GatherStart 进行求交
     pub fn handle_sig(&mut self, msg: Message) -> Option<Message> {
         if msg.msg_content.len() == 0 || self.set_attached.len() == 0 {
             return None
@@ -96,6 +103,7 @@ impl VabaNode {
                                                     .collect::<String>().chars().rev()
                                                     .collect::<String>().parse::<usize>();
 
+        let enough = self.committee.exceeds_faulty(&self.signers());
         if let Ok(number) = number {
             if number == msg.sender_id {
                 self.set_sig.push((msg.sender_id, msg));
@@ -109,7 +117,7 @@ impl VabaNode {
             return None
         }
 
-        if self.set_sig.len() == self.f + 1 {
+        if !enough && self.committee.exceeds_faulty(&self.signers()) {
             println!("client_id:{} status:VABA_SIG_ENOUGH set:{:?}", self.id, self.set_attached);
             return self.send_message(vec![self.id], MessageType::GatherStart, msg_content);
         }
@@ -138,7 +146,14 @@ impl VabaNode {
                 //     sum = sum.wrapping_add(self.secret[i].into());
                 // }
 
-                let secret = self.avss.reconstruct().get_real().to_string();
+                // 自己分发的份额不一致时不公开随机数
+                let secret = match self.avss.borrow().reconstruct_of(COIN_SECRET) {
+                    Ok(secret) => secret.get_real().to_string(),
+                    Err(e) => {
+                        println!("client_id:{} status:COIN_FAILED error:{:?}", self.id, e);
+                        return None;
+                    }
+                };
                 
                 // return self.send_message(vec![], MessageType::VabaEval, vec![sum])
                 return Some(Message::send_message_with_addi(
@@ -188,19 +203,12 @@ impl VabaNode {
         is_equal(&indice, &self.set_indice)
     }
 
-    
-}
-
-
-/// 计算log2(n)
-fn log_2_n (n: usize) -> usize {
-    let mut i = 0;
-    let mut tmp = n;
-    while tmp > 1 {
-        tmp = tmp >> 1;
-        i += 1;
+    /// 已经为自己签名的参与者
+    fn signers(&self) -> Vec<usize> {
+        self.set_sig.iter().map(|(id, _)| *id).collect()
     }
-    i+1
+
+    
 }
 
 #[cfg(test)]
@@ -8,6 +8,11 @@ pub mod client{
     pub mod vaba;
     pub mod adkg;
     pub mod avss;
+    pub mod decrypt;
+    pub mod evaluation;
+    pub mod beacon;
+    pub mod triple;
+    pub mod kdf;
 }
 
 pub mod msg{
@@ -17,13 +22,204 @@ pub mod msg{
 
 use std::thread;
 use std::sync::mpsc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::collections::HashMap;
+use std::time::{Duration, Instant};
 
 use crate::server::servers::{BroadcastServer, UserThread};
+use crate::client::beacon::BeaconTranscript;
+use crate::client::avss::SHARE_KEM;
 use crate::client::clients::Client;
+use crate::client::kdf::{DerivedKey, Label};
+use crate::client::triple::{TripleError, TripleNode, TripleShares};
+use util::committee::Committee;
+use util::params::ProtocolParams;
+use util::algebra::field::mersenne61_ext::Mersenne61Ext;
+use util::algebra::lattice::{Lattice, LatticeParams};
+use util::signature::{self, VerifyingKey};
+use util::SECURITY_BITS;
 
-/// 运行协议，参数 `n` 为参与方总数量，`f` 为恶意参与方数量
-pub fn run(n: usize, f: usize) {
+/// 等待所有诚实节点得到密钥的最长时间
+pub const RUN_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// 展开格上的公开矩阵 A 的种子
+pub const LATTICE_SEED: &[u8] = b"PQC-ADKG lattice matrix";
+
+/// 运行协议，参数 `n` 为参与方总数量，`f` 为恶意参与方数量，门限取 `t = max(f, 1)`
+pub fn run(n: usize, f: usize) -> Vec<Option<Vec<Mersenne61Ext>>> {
+    run_with_params(&ProtocolParams::new(&Committee::uniform(n, f, f.max(1)), SECURITY_BITS))
+}
+
+/// 按照协议参数运行协议，委员会中权重之和不超过 `f` 的若干节点不参与协议
+/// 返回每个节点在自己的求值点上的密钥份额，没有得到份额的节点为 None
+pub fn run_with_params(params: &ProtocolParams<Mersenne61Ext>) -> Vec<Option<Vec<Mersenne61Ext>>> {
+    run_epoch(params, None)
+}
+
+/// 生成密钥之后再进行 `refreshes` 次主动刷新，每次刷新后节点的份额改变而组密钥不变
+pub fn run_with_refresh(
+    params: &ProtocolParams<Mersenne61Ext>,
+    refreshes: usize,
+) -> Vec<Option<Vec<Mersenne61Ext>>> {
+    let mut shares = run_epoch(params, None);
+    for i in 0..refreshes {
+        println!("refresh: {}", i + 1);
+        shares = run_epoch(params, Some(&shares));
+    }
+    shares
+}
+
+/// 运行一轮协议，返回每个节点在自己的求值点上的密钥份额，没有得到份额的节点为 None
+/// `shares` 为上一轮的结果时进行主动刷新，上一轮没有得到份额的节点不参与刷新
+pub fn run_epoch(
+    params: &ProtocolParams<Mersenne61Ext>,
+    shares: Option<&Vec<Option<Vec<Mersenne61Ext>>>>,
+) -> Vec<Option<Vec<Mersenne61Ext>>> {
+    let faulty = params.committee().faulty_nodes();
+    let honest = faulty.iter().filter(|x| !**x).count();
+    let params = params.clone();
+    let shares = shares.cloned();
+    let n = faulty.len();
+    let client = move |id: usize| {
+        let state = if faulty[id] {0} else {1};
+        match shares.as_ref().map(|s| s[id].clone()) {
+            None => Client::new(id, state, &params),
+            Some(Some(key_shares)) => Client::refresh(id, state, &params, key_shares),
+            Some(None) => Client::new(id, 0, &params),
+        }
+    };
+    run_clients(n, honest, client, Client::key_shares)
+}
+
+/// 一次运行生成 `count` 个独立的密钥，所有密钥共用一次批量分发和一次 Dealer 集合的协商
+/// 返回每个节点的密钥份额，每个求值点上依次为 `count` 个密钥的份额
+pub fn run_batch(params: &ProtocolParams<Mersenne61Ext>, count: usize) -> Vec<Option<Vec<Mersenne61Ext>>> {
+    let faulty = params.committee().faulty_nodes();
+    let honest = faulty.iter().filter(|x| !**x).count();
+    let params = params.clone();
+    run_clients(faulty.len(), honest, move |id| {
+        let state = if faulty[id] {0} else {1};
+        Client::batch(id, state, &params, count)
+    }, Client::key_shares)
+}
+
+/// 随机信标：运行 `epochs` 个 epoch，每个 epoch 重新分发秘密并协商 Dealer 集合，输出秘密之和的哈希
+/// 每个 epoch 每个节点生成一对一次性签名密钥，签名自己公开的份额之和
+/// 返回每个 epoch 中第一个通过验证的记录，以及验证它所用的每个节点的验证密钥
+pub fn run_beacon(params: &ProtocolParams<Mersenne61Ext>, epochs: u64) -> Vec<(BeaconTranscript, Vec<VerifyingKey>)> {
+    let faulty = params.committee().faulty_nodes();
+    let honest = faulty.iter().filter(|x| !**x).count();
+    (0..epochs)
+        .map(|epoch| {
+            let (epoch_params, faulty) = (params.clone(), faulty.clone());
+            let n = faulty.len();
+            let (keys, signing): (Vec<_>, Vec<_>) = (0..n).map(|_| signature::keygen()).unzip();
+            let verifying = keys.clone();
+            let client = move |id: usize| {
+                Client::beacon(id, if faulty[id] {0} else {1}, &epoch_params, epoch, signing[id].clone(), &verifying)
+            };
+            let transcripts = run_clients(n, honest, client, Client::beacon_transcript);
+            let transcript = transcripts
+                .into_iter()
+                .flatten()
+                .find(|t| t.verify(params, &keys))
+                .expect("no honest node produced a beacon value");
+            println!("beacon epoch: {} value: {}", epoch, transcript.to_hex());
+            (transcript, keys)
+        })
+        .collect()
+}
+
+/// 生成格上的密钥，返回每个节点在自己的求值点上的私钥 s 的份额，每个求值点依次有 s 的全部系数的份额
+/// 公开矩阵由 `LATTICE_SEED` 展开，节点输出公钥 A·s + e，之后门限解密 `decryptions` 个随机明文
+pub fn run_lattice(
+    params: &ProtocolParams<Mersenne61Ext>,
+    lattice: &LatticeParams,
+    decryptions: usize,
+) -> Vec<Option<Vec<Mersenne61Ext>>> {
+    let faulty = params.committee().faulty_nodes();
+    let honest = faulty.iter().filter(|x| !**x).count();
+    let params = params.clone();
+    let lattice = Lattice::new(*lattice, LATTICE_SEED);
+    run_clients(faulty.len(), honest, move |id| {
+        let state = if faulty[id] {0} else {1};
+        Client::lattice(id, state, &params, &lattice, decryptions)
+    }, Client::key_shares)
+}
+
+/// 生成格上的密钥后，由私钥 s 门限派生 `labels` 中每个标签的子密钥，s 不被重构
+/// 返回每个节点得到的子密钥，按标签的顺序排列
+pub fn run_kdf(
+    params: &ProtocolParams<Mersenne61Ext>,
+    lattice: &LatticeParams,
+    labels: &[Label],
+) -> Vec<Vec<DerivedKey>> {
+    let faulty = params.committee().faulty_nodes();
+    let honest = faulty.iter().filter(|x| !**x).count();
+    let params = params.clone();
+    let lattice = Lattice::new(*lattice, LATTICE_SEED);
+    let labels = labels.to_vec();
+    run_clients(faulty.len(), honest, move |id| {
+        let state = if faulty[id] {0} else {1};
+        Client::kdf(id, state, &params, &lattice, &labels)
+    }, Client::derived_keys)
+}
+
+/// 生成 `count` 个乘法三元组，返回每个节点在自己的每个求值点上的三元组份额
+/// 门限 2t 的参数由 `params` 得到，求值点相同，n < 2t + 2f + 1 时返回错误
+pub fn run_triples(params: &ProtocolParams<Mersenne61Ext>, count: usize) -> Result<Vec<Option<Vec<TripleShares>>>, TripleError> {
+    TripleNode::check(params.committee())?;
+    let faulty = params.committee().faulty_nodes();
+    let honest = faulty.iter().filter(|x| !**x).count();
+    let params = params.clone();
+    let double = params.clone().with_threshold(2 * params.t());
+    Ok(run_clients(faulty.len(), honest, move |id| {
+        let state = if faulty[id] {0} else {1};
+        Client::triples(id, state, &params, &double, count).expect("committee checked above")
+    }, Client::triple_shares))
+}
+
+/// 把旧委员会 `old` 的密钥重新分享给新委员会 `params`，`shares` 为旧委员会中每个节点的份额
+/// `shares` 须为生成密钥的结果：生成密钥时每个节点在 SUM_AND_REC 中公开了自己的份额，新委员会以此检查 Dealer
+/// 新委员会中的恶意节点在重新分享时也是旧委员会中的节点，必须是旧委员会的恶意节点，否则旧委员会的恶意权重超过 f
+/// 返回新委员会中每个节点的份额，旧份额不再使用
+pub fn run_reshare(
+    old: &ProtocolParams<Mersenne61Ext>,
+    params: &ProtocolParams<Mersenne61Ext>,
+    shares: &[Option<Vec<Mersenne61Ext>>],
+) -> Vec<Option<Vec<Mersenne61Ext>>> {
+    let faulty = params.committee().faulty_nodes();
+    let old_faulty = old.committee().faulty_nodes();
+    if faulty.iter().zip(&old_faulty).any(|(new, old)| *new && !*old) {
+        panic!("faulty nodes of the new committee must be faulty in the old committee");
+    }
+    let honest = faulty.iter().filter(|x| !**x).count();
+    let members = params.committee().n();
+    let n = std::cmp::max(old.committee().n(), members);
+    let (old, params, shares) = (old.clone(), params.clone(), shares.to_vec());
+    let mut res = run_clients(n, honest, move |id| {
+        let state = if faulty.get(id) == Some(&true) {0} else {1};
+        let key_shares = shares.get(id).cloned().flatten();
+        Client::reshare(id, state, &params, &old, key_shares, shares.clone())
+    }, Client::key_shares);
+    res.truncate(members);
+    res
+}
+
+/// 创建 `n` 个线程，`client` 在线程中创建编号为 i 的节点，等待 `honest` 个节点得到密钥，至多等待 RUN_TIMEOUT
+/// 每个节点生成 ML-KEM 密钥对，Dealer 以每个求值点的所有者的公钥加密份额，经过广播服务器的消息中没有明文份额
+/// 返回 `output` 在每个节点结束时的值，例如节点在自己的求值点上的密钥份额；超时时打印没有完成的诚实节点
+fn run_clients<F, G, R>(n: usize, honest: usize, client: F, output: G) -> Vec<R>
+where
+    F: Fn(usize) -> Client + Send + Sync + 'static,
+    G: Fn(&Client) -> R + Send + Sync + 'static,
+    R: Send + 'static,
+{
+    let client = Arc::new(client);
+    let output = Arc::new(output);
+    let (keys, dks): (Vec<_>, Vec<_>) = (0..n).map(|_| SHARE_KEM.keygen()).unzip();
+    let keys = Arc::new(keys);
     // 创建通道，用于线程向服务器发送消息
     let (tx_to_server, rx_to_server) = mpsc::channel();
 
@@ -58,18 +254,22 @@ pub fn run(n: usize, f: usize) {
     });
 
     let mut join_handles = Vec::new();
+    // 诚实节点得到密钥后通过 `tx_done` 通知主线程，主线程设置 `stop` 后所有线程退出
+    let (tx_done, rx_done) = mpsc::channel();
+    let stop = Arc::new(AtomicBool::new(false));
 
     // 创建 n 个线程执行用户操作
-    for i in 0..n {
+    for _ in 0..n {
         let user = threads.pop().unwrap();
+        let client = client.clone();
+        let output = output.clone();
+        let keys = keys.clone();
+        let dk = dks[user.thread_id].clone();
+        let tx_done = tx_done.clone();
+        let stop = stop.clone();
         join_handles.push(thread::spawn( move || {
 
-            let mut user_node = Client::new(
-                user.thread_id, 
-                if i < n-f {1} else {0},
-                n,
-                f,
-            );
+            let mut user_node = client(user.thread_id).with_encryption(dk, &keys);
             print!("thread id: {}, state: {}\n", user.thread_id, user_node.state);
             // 向服务器发送一条广播消息，开始协议
             let message = user_node.start();
@@ -79,8 +279,17 @@ pub fn run(n: usize, f: usize) {
             }
             
             // 等待服务器返回消息，处理消息，然后再向服务器发送消息
-            while let Ok(msg) = user.rx_from_server.recv() {
+            while !stop.load(Ordering::Relaxed) {
+                let msg = match user.rx_from_server.recv_timeout(Duration::from_millis(100)) {
+                    Ok(msg) => msg,
+                    Err(mpsc::RecvTimeoutError::Timeout) => continue,
+                    Err(mpsc::RecvTimeoutError::Disconnected) => break,
+                };
+                let finished = user_node.finished();
                 let new_msg = user_node.handle_message(msg);
+                if !finished && user_node.finished() {
+                    tx_done.send(user.thread_id).unwrap();
+                }
                 match new_msg {
                     Some(m) =>{ 
                         // println!("Thread {} send message to {:?}\n{}", user.thread_id, m.receiver_id, m);
@@ -90,20 +299,49 @@ pub fn run(n: usize, f: usize) {
                 }
             }
             // println!("Thread {} finished", user.thread_id);
+            (user.thread_id, user_node.honest() && !user_node.finished(), output(&user_node))
         }));
     }
 
-    // 等待所有线程完成
-    thread::sleep(std::time::Duration::from_secs(2));
-    
+    // 等待所有诚实节点得到密钥，至多等待 RUN_TIMEOUT
+    let deadline = Instant::now() + RUN_TIMEOUT;
+    for _ in 0..honest {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if rx_done.recv_timeout(remaining).is_err() {
+            break;
+        }
+    }
+    stop.store(true, Ordering::Relaxed);
+    let mut outputs: Vec<_> = (0..n).map(|_| None).collect();
+    let mut unfinished = vec![];
+    for handle in join_handles {
+        let (id, waiting, res) = handle.join().unwrap();
+        if waiting {
+            unfinished.push(id);
+        }
+        outputs[id] = Some(res);
+    }
+    if !unfinished.is_empty() {
+        unfinished.sort();
+        println!("status:UNFINISHED nodes:{:?}", unfinished);
+    }
+    outputs.into_iter().map(Option::unwrap).collect()
 }
 
 
 
 #[cfg(test)]
 mod tests {
-    use super::run;
+    use super::{run, run_batch, run_beacon, run_epoch, run_kdf, run_lattice, run_reshare, run_triples, run_with_params, RUN_TIMEOUT};
+    use crate::client::kdf::Label;
+    use crate::client::triple::TripleShares;
+    use util::algebra::lattice::LatticeParams;
     use std::time::Instant;
+    use util::algebra::field::mersenne61_ext::Mersenne61Ext;
+    use util::algebra::polynomial::Polynomial;
+    use util::committee::Committee;
+    use util::params::ProtocolParams;
+    use util::SECURITY_BITS;
     // use colored::*;
     // use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
     // use std::io::Write;
@@ -113,13 +351,185 @@ mod tests {
             let f = (n-1)/3;
             println!("n: {}, f: {}", n, f);
             let start = Instant::now();
-            run(n, f);
+            let shares = run(n, f);
             let end = start.elapsed();
             println!("Time elapsed in run() is: {:?}", end);
+            agreed(&ProtocolParams::new(&Committee::uniform(n, f, f.max(1)), SECURITY_BITS), &shares);
         }
         
     }
 
+    #[test]
+    fn no_faults() {
+        // f = 0 时门限取 t = 1
+        let shares = run(4, 0);
+        agreed(&ProtocolParams::new(&Committee::uniform(4, 0, 1), SECURITY_BITS), &shares);
+    }
+
+    #[test]
+    fn weighted() {
+        let committee = Committee::new(vec![1, 3, 1, 2, 1, 1, 2], 3, 4);
+        let params = ProtocolParams::new(&committee, SECURITY_BITS);
+        let shares = run_with_params(&params);
+        agreed(&params, &shares);
+    }
+
+    /// 每个诚实节点都得到份额，全部份额位于同一个 t 次多项式上，即重构出同一个组密钥
+    fn agreed(params: &ProtocolParams<Mersenne61Ext>, shares: &[Option<Vec<Mersenne61Ext>>]) -> Mersenne61Ext {
+        for (i, faulty) in params.committee().faulty_nodes().iter().enumerate() {
+            assert!(*faulty || shares[i].is_some(), "honest node {} has no share", i);
+        }
+        key(params, shares)
+    }
+
+    /// 由各个节点的份额重构组密钥
+    fn key(params: &ProtocolParams<Mersenne61Ext>, shares: &[Option<Vec<Mersenne61Ext>>]) -> Mersenne61Ext {
+        let allocation = params.committee().allocate();
+        let points: Vec<Vec<_>> = shares
+            .iter()
+            .enumerate()
+            .filter_map(|(i, s)| Some(allocation[i].iter().zip(s.as_ref()?)))
+            .flatten()
+            .map(|(p, v)| vec![params.share_point(*p), *v])
+            .collect();
+        Polynomial::reconstruct(&points, params.t()).unwrap()
+    }
+
+    #[test]
+    fn refresh() {
+        let params = ProtocolParams::new(&Committee::uniform(7, 2, 2), SECURITY_BITS);
+
+        // 刷新后诚实节点的份额都改变，而组密钥不变
+        let shares = run_epoch(&params, None);
+        let refreshed = run_epoch(&params, Some(&shares));
+        for (old, new) in shares.iter().zip(&refreshed) {
+            assert_eq!(old.is_some(), new.is_some());
+            assert!(old.is_none() || old != new);
+        }
+        assert_eq!(key(&params, &shares), key(&params, &refreshed));
+    }
+
+    #[test]
+    fn batch() {
+        // 加权的委员会中节点有多个求值点，每个求值点上依次为 3 个密钥的份额
+        let committee = Committee::new(vec![1, 3, 1, 2, 1, 1, 2], 3, 4);
+        let params = ProtocolParams::new(&committee, SECURITY_BITS);
+        let shares = run_batch(&params, 3);
+        let keys: Vec<_> = (0..3)
+            .map(|k| {
+                let shares: Vec<_> = shares
+                    .iter()
+                    .map(|s| Some(s.as_ref()?.iter().skip(k).step_by(3).copied().collect()))
+                    .collect();
+                key(&params, &shares)
+            })
+            .collect();
+        assert_ne!(keys[0], keys[1]);
+        assert_ne!(keys[1], keys[2]);
+    }
+
+    #[test]
+    fn packed() {
+        // 一次生成 3 个密钥，刷新后份额改变而全部密钥不变
+        let params = ProtocolParams::new(&Committee::uniform(7, 1, 3), SECURITY_BITS).with_packing(3);
+        let keys = |shares: &[Option<Vec<Mersenne61Ext>>]| {
+            let points: Vec<Vec<_>> = shares
+                .iter()
+                .enumerate()
+                .filter_map(|(i, s)| Some(vec![params.share_point(i), s.as_ref()?[0]]))
+                .collect();
+            Polynomial::reconstruct_packed(&points, params.t(), &params.secret_points()).unwrap()
+        };
+        let shares = run_epoch(&params, None);
+        let refreshed = run_epoch(&params, Some(&shares));
+        assert_eq!(keys(&shares).len(), 3);
+        assert_ne!(keys(&shares)[0], keys(&shares)[1]);
+        assert_eq!(keys(&shares), keys(&refreshed));
+        assert_ne!(shares, refreshed);
+    }
+
+    #[test]
+    fn reshare() {
+        // 重新分享给更小的、门限不同的委员会，以及更大的委员会，组密钥都不变
+        let old = ProtocolParams::new(&Committee::uniform(7, 2, 2), SECURITY_BITS);
+        let shares = run_epoch(&old, None);
+        for committee in [Committee::uniform(5, 1, 2), Committee::uniform(10, 2, 3)] {
+            let new = ProtocolParams::new(&committee, SECURITY_BITS);
+            let reshared = run_reshare(&old, &new, &shares);
+            assert_eq!(reshared.len(), committee.n());
+            assert_eq!(key(&old, &shares), key(&new, &reshared));
+        }
+    }
+
+    #[test]
+    fn lattice() {
+        // 诚实节点在自己的求值点上得到 s 的全部系数的份额，并各自完成两次解密，编号最大的节点是恶意节点时也能结束
+        let lattice = LatticeParams::new(8, 2, 2);
+        for committee in [Committee::uniform(7, 1, 2), Committee::new(vec![3, 3, 1], 1, 1)] {
+            let params = ProtocolParams::new(&committee, SECURITY_BITS);
+            let start = Instant::now();
+            let shares = run_lattice(&params, &lattice, 2);
+            assert!(start.elapsed() < RUN_TIMEOUT);
+            let faulty = committee.faulty_nodes();
+            for (s, (faulty, points)) in shares.iter().zip(faulty.iter().zip(committee.allocate())) {
+                assert_eq!(s.as_ref().map(|s| s.len()), (!faulty).then_some(points.len() * lattice.dimension()));
+            }
+        }
+    }
+
+    #[test]
+    fn kdf() {
+        // 诚实节点得到相同的子密钥，不同的标签得到不同的子密钥
+        let params = ProtocolParams::new(&Committee::uniform(7, 1, 2), SECURITY_BITS);
+        let labels = vec![Label::new("storage", 1), Label::new("storage", 2)];
+        let keys = run_kdf(&params, &LatticeParams::new(8, 2, 2), &labels);
+        let faulty = params.committee().faulty_nodes();
+        let honest: Vec<_> = keys.iter().zip(faulty).filter(|(_, f)| !f).map(|(k, _)| k).collect();
+        assert_eq!(honest[0].len(), 2);
+        assert!(honest.iter().all(|k| *k == honest[0]));
+        assert_eq!(honest[0][1].label, labels[1]);
+        assert_ne!(honest[0][0].key, honest[0][1].key);
+    }
+
+    #[test]
+    fn beacon() {
+        // 每个 epoch 的信标都能通过验证，且各不相同
+        let params = ProtocolParams::new(&Committee::uniform(7, 2, 2), SECURITY_BITS);
+        let transcripts = run_beacon(&params, 2);
+        assert_eq!(transcripts.len(), 2);
+        for (epoch, (transcript, keys)) in transcripts.iter().enumerate() {
+            assert_eq!(transcript.epoch, epoch as u64);
+            assert!(transcript.verify(&params, keys));
+        }
+        // 另一个 epoch 的验证密钥不能验证这个 epoch 的记录
+        assert!(!transcripts[0].0.verify(&params, &transcripts[1].1));
+        assert_ne!(transcripts[0].0.value, transcripts[1].0.value);
+    }
+
+    #[test]
+    fn triples() {
+        // 诚实节点得到 3 个三元组的份额，重构出的 a、b、c 满足 c = a·b
+        let params = ProtocolParams::new(&Committee::uniform(7, 1, 2), SECURITY_BITS);
+        let shares = run_triples(&params, 3).unwrap();
+        // 2t 次的码无法纠正 f 个错误时返回错误，不启动节点
+        let small = ProtocolParams::new(&Committee::uniform(7, 2, 2), SECURITY_BITS);
+        assert!(run_triples(&small, 3).is_err());
+        let allocation = params.committee().allocate();
+        for i in 0..3 {
+            let reconstruct = |f: fn(&TripleShares) -> &Vec<Mersenne61Ext>| {
+                let points: Vec<Vec<_>> = shares
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(j, s)| Some(allocation[j].iter().zip(s.as_ref()?)))
+                    .flatten()
+                    .map(|(p, s)| vec![params.share_point(*p), f(s)[i]])
+                    .collect();
+                Polynomial::reconstruct(&points, params.t()).unwrap()
+            };
+            assert_eq!(reconstruct(|s| &s.c), reconstruct(|s| &s.a) * reconstruct(|s| &s.b));
+        }
+    }
+
     // #[test]
     // fn t2() {
     //     let mut stdout = StandardStream::stdout(ColorChoice::Always);
@@ -1,45 +1,276 @@
 use std::env;
-use adkg::run;
+use adkg::{run_batch, run_beacon, run_kdf, run_lattice, run_reshare, run_triples, run_with_refresh};
+use adkg::client::adkg::AdkgNode;
+use adkg::client::kdf::Label;
+use util::algebra::lattice::LatticeParams;
+use util::algebra::field::mersenne61_ext::Mersenne61Ext;
+use util::committee::Committee;
+use util::params::ProtocolParams;
+use util::security::SecurityConfig;
+use util::SECURITY_BITS;
 
 pub fn main() {
     let args: Vec<String> = env::args().collect();
-    
-    let (n, f) = get_args(args);
-    println!("n: {}, f: {}", n, f);
-    
-    // 运行协议，参数 `n` 为参与方总数量，`f` 为恶意参与方数量
-    run(n, f);
+    // `-p` 为生成密钥之后主动刷新份额的次数，缺省时不刷新
+    let refreshes = args
+        .iter()
+        .position(|a| a == "-p")
+        .map_or(0, |i| value(&args, i));
+    let reshare = get_reshare(&args);
+    let lattice = get_lattice(&args);
+    // `-d` 为生成格上的密钥之后门限解密的随机明文的个数
+    let decryptions = args
+        .iter()
+        .position(|a| a == "-d")
+        .map_or(0, |i| value(&args, i));
+    if decryptions > 0 && lattice.is_none() {
+        panic!("-d requires -l");
+    }
+    let labels = get_labels(&args);
+    if !labels.is_empty() && (lattice.is_none() || decryptions > 0) {
+        panic!("-e requires -l and cannot be combined with -d");
+    }
+    // `-b` 为随机信标的 epoch 数
+    let epochs = args
+        .iter()
+        .position(|a| a == "-b")
+        .map_or(0, |i| value(&args, i) as u64);
+    if epochs > 0 && (lattice.is_some() || refreshes > 0 || reshare.is_some()) {
+        panic!("-b cannot be combined with -l, -p or -s");
+    }
+    // `-m` 为生成的乘法三元组的个数
+    let triples = args
+        .iter()
+        .position(|a| a == "-m")
+        .map_or(0, |i| value(&args, i));
+    if triples > 0 && (epochs > 0 || lattice.is_some() || refreshes > 0 || reshare.is_some()) {
+        panic!("-m cannot be combined with -b, -l, -p or -s");
+    }
+    // `-c` 为一次运行生成的独立密钥的个数，缺省时为 1
+    let count = args
+        .iter()
+        .position(|a| a == "-c")
+        .map_or(1, |i| value(&args, i));
+    if count == 0 {
+        panic!("must generate at least one key");
+    }
+    if count > 1 && (epochs > 0 || triples > 0 || lattice.is_some() || refreshes > 0 || reshare.is_some()) {
+        panic!("-c cannot be combined with -b, -m, -l, -p or -s");
+    }
+    if lattice.is_some() && (refreshes > 0 || reshare.is_some()) {
+        panic!("-l cannot be combined with -p or -s");
+    }
+    // 重新分享以生成密钥时公开的份额检查 Dealer，刷新后的份额没有公开
+    if refreshes > 0 && reshare.is_some() {
+        panic!("-s cannot be combined with -p");
+    }
+
+    let params = get_args(args);
+    if params.packing() > 1 && (epochs > 0 || triples > 0 || lattice.is_some() || reshare.is_some()) {
+        panic!("-k cannot be combined with -b, -m, -l or -s");
+    }
+    let committee = params.committee();
+    if lattice.is_some() && committee.total_weight() < 2 * committee.t() + 2 * committee.f() + 1 {
+        panic!("-l requires n >= 2t + 2f + 1");
+    }
+    println!("n: {}, f: {}, t: {}, weights: {:?}", committee.n(), committee.f(), committee.t(), committee.weights());
+    println!("terminate_round: {}, optimal: {}", params.terminate_round(), params.optimal_terminate_round());
+    println!("query_num: {}, security bits: {:.1}, zero knowledge: {}", params.query_num(), params.achieved_bits(), params.zero_knowledge());
+    if params.packing() > 1 {
+        println!("packing: {}, privacy: {}", params.packing(), params.privacy());
+    }
+
+    if epochs > 0 {
+        run_beacon(&params, epochs);
+        return;
+    }
+
+    if triples > 0 {
+        if let Err(e) = run_triples(&params, triples) {
+            panic!("-m: {}", e);
+        }
+        return;
+    }
+
+    if count > 1 {
+        run_batch(&params, count);
+        return;
+    }
+
+    if let Some(lattice) = lattice {
+        println!("lattice: degree: {}, rank: {}, eta: {}", lattice.degree(), lattice.rank(), lattice.eta());
+        if !labels.is_empty() {
+            run_kdf(&params, &lattice, &labels);
+            return;
+        }
+        run_lattice(&params, &lattice, decryptions);
+        return;
+    }
+
+    // 运行协议，参数 `n` 为参与方总数量，`f` 为恶意参与方数量（或恶意参与方的权重之和）
+    let shares = run_with_refresh(&params, refreshes);
+
+    if let Some(committee) = reshare {
+        let mut new_params = ProtocolParams::with_security(&committee, params.security().clone());
+        if params.zero_knowledge() {
+            new_params = new_params.with_zero_knowledge();
+        }
+        println!("reshare: n: {}, f: {}, t: {}", committee.n(), committee.f(), committee.t());
+        run_reshare(&params, &new_params, &shares);
+    }
+}
+
+/// `-s n,f[,t]` 在生成密钥之后把密钥重新分享给 n 个节点组成的新委员会，门限缺省时取 `t = max(f, 1)`
+/// 委员会的条件由 `Committee::uniform` 和 `AdkgNode::max_errors` 检查
+fn get_reshare(args: &[String]) -> Option<Committee> {
+    let i = args.iter().position(|a| a == "-s")?;
+    let values: Vec<usize> = match args.get(i + 1).map(|v| v.split(',').map(|v| v.parse::<usize>()).collect()) {
+        Some(Ok(values)) => values,
+        _ => panic!("-s requires n,f[,t]"),
+    };
+    if values.len() < 2 {
+        panic!("must give n and f of the new committee");
+    }
+    let (n, f) = (values[0], values[1]);
+    if n < 4 {
+        panic!("must have n >= 4");
+    }
+    let committee = Committee::uniform(n, f, values.get(2).copied().unwrap_or(f.max(1)));
+    AdkgNode::max_errors(&committee);
+    Some(committee)
+}
+
+/// `-l degree,rank[,eta]` 生成格上的密钥，环的次数为 degree，模的秩为 rank，eta 缺省时取 2
+fn get_lattice(args: &[String]) -> Option<LatticeParams> {
+    let i = args.iter().position(|a| a == "-l")?;
+    let values: Vec<usize> = args[i + 1].split(',').map(|v| v.parse::<usize>().unwrap()).collect();
+    if values.len() < 2 {
+        panic!("must give the degree and rank of the lattice");
+    }
+    Some(LatticeParams::new(values[0], values[1], values.get(2).copied().unwrap_or(2)))
+}
+
+/// `-e context@epoch[,context@epoch...]` 生成格上的密钥之后门限派生每个标签的子密钥
+/// 最后一个 '@' 之后不是整数时整个字符串都是上下文，epoch 取 0，例如 `user@host`
+fn get_labels(args: &[String]) -> Vec<Label> {
+    let i = match args.iter().position(|a| a == "-e") {
+        Some(i) => i,
+        None => return vec![],
+    };
+    let labels = match args.get(i + 1) {
+        Some(labels) => labels,
+        None => panic!("-e requires context@epoch[,context@epoch...]"),
+    };
+    labels
+        .split(',')
+        .map(|l| match l.rsplit_once('@').map(|(context, epoch)| (context, epoch.parse::<u64>())) {
+            Some((context, Ok(epoch))) => Label::new(context, epoch),
+            _ => Label::new(l, 0),
+        })
+        .collect()
 }
 
-fn get_args(args: Vec<String>) -> (usize, usize) {
+/// 参数 `args[i]` 之后的非负整数，缺少或者不是整数时报错
+fn value(args: &[String], i: usize) -> usize {
+    match args.get(i + 1).map(|v| v.parse::<usize>()) {
+        Some(Ok(v)) => v,
+        _ => panic!("{} requires a non-negative integer", args[i]),
+    }
+}
+
+/// 解析命令行参数，`-w` 以逗号分隔给出每个节点的权重，缺省时每个节点的权重均为 1
+/// `-t` 为秘密分享的门限，缺省时取 `t = f`
+/// `-r` 为参与方最终收到的多项式的变量数，缺省时取 1
+/// `-g` 为 Dealer 工作量证明的位数，缺省时不进行工作量证明
+/// `-z` 开启零知识模式
+/// `-k` 为打包秘密分享时一次生成的密钥个数，缺省时为 1
+fn get_args(args: Vec<String>) -> ProtocolParams<Mersenne61Ext> {
     let mut n = 0;
     let mut f = 0;
+    let mut t = None;
+    let mut weights = vec![];
+    let mut terminate_round = None;
+    let mut grinding_bits = 0;
+    let mut zero_knowledge = false;
+    let mut packing = 1;
     for i in 0..args.len() {
         if args[i] == "-n" {
-            n = args[i+1].parse::<usize>().unwrap();
+            n = value(&args, i);
         }
         if args[i] == "-f" {
-            f = args[i+1].parse::<usize>().unwrap();
+            f = value(&args, i);
+        }
+        if args[i] == "-t" {
+            t = Some(value(&args, i));
+        }
+        if args[i] == "-w" {
+            weights = args[i+1].split(',').map(|w| w.parse::<usize>().unwrap()).collect();
+        }
+        if args[i] == "-r" {
+            terminate_round = Some(value(&args, i));
+        }
+        if args[i] == "-g" {
+            grinding_bits = value(&args, i);
         }
+        if args[i] == "-z" {
+            zero_knowledge = true;
+        }
+        if args[i] == "-k" {
+            packing = value(&args, i);
+        }
+    }
+
+    if weights.is_empty() {
+        weights = vec![1; n];
+    } else if n != 0 && n != weights.len() {
+        panic!("must give one weight for each node");
     }
+    let total: usize = weights.iter().sum();
 
-    if 3*f+1 > n {
+    if 3*f+1 > total {
         panic!("must have 3f+1 <= n");
     }
 
-    if n < 4 {
+    if total < 4 {
         panic!("must have n >= 4");
     }
 
-    (n, f)
+    let t = t.unwrap_or(f.max(1));
+    if t < f || t + f >= total || t == 0 {
+        panic!("must have f <= t < n-f and t >= 1");
+    }
+    if total < t + 2 * f + 1 {
+        panic!("must have n >= t + 2f + 1 to correct f errors");
+    }
+
+    let security = SecurityConfig::new(SECURITY_BITS).with_grinding_bits(grinding_bits);
+    let mut params = ProtocolParams::with_security(&Committee::new(weights, f, t), security);
+    if let Some(terminate_round) = terminate_round {
+        params = params.with_terminate_round(terminate_round);
+    }
+    if zero_knowledge {
+        params = params.with_zero_knowledge();
+    }
+    params.with_packing(packing)
 }
 
 
 
 #[cfg(test)]
 mod tests {
+    use super::get_labels;
+    use adkg::client::kdf::Label;
     use adkg::run;
 
+    #[test]
+    fn labels() {
+        let args: Vec<String> = ["adkg", "-e", "storage@1,user@host,a@b@2,plain"].iter().map(|a| a.to_string()).collect();
+        let expected = vec![Label::new("storage", 1), Label::new("user@host", 0), Label::new("a@b", 2), Label::new("plain", 0)];
+        assert_eq!(get_labels(&args), expected);
+        assert!(get_labels(&args[..1]).is_empty());
+        assert!(std::panic::catch_unwind(|| get_labels(&args[..2])).is_err());
+    }
+
     #[test]
     fn t() {
         for _ in 0..3{
@@ -49,4 +280,4 @@ mod tests {
             run(n, f);
         }
     }
-}
\ No newline at end of file
+}
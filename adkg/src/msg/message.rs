@@ -1,3 +1,8 @@
+use avss::avss::party::ShareProof;
+use std::sync::Arc;
+use util::algebra::field::mersenne61_ext::Mersenne61Ext;
+use util::signature::Signature;
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum MessageType {
     NonType,
@@ -17,6 +22,17 @@ pub enum MessageType {
     Gather3,
     GatherFin,
     SumAndRec,
+    AdkgComplaint,
+    DecRequest,
+    DecShare,
+    TripleStart,
+    TripleCheck,
+    TripleMul,
+    KdfRequest,
+    KdfShare,
+    RangeCheck,
+    RecoverRequest,
+    RecoverResponse,
 }
 
 #[derive(Clone, Debug)]
@@ -26,6 +42,10 @@ pub struct Message {
     pub msg_type: MessageType,
     pub msg_content: Vec<usize>,
     pub additional: String,
+    /// Dealer 发送的份额和证明，投诉时为验证失败的证明；广播时各个接收者共享同一份
+    pub proofs: Arc<Vec<ShareProof<Mersenne61Ext>>>,
+    /// 随机信标中节点对自己公开的份额之和的签名
+    pub signature: Option<Signature>,
 }
 
 impl std::fmt::Display for MessageType {
@@ -48,6 +68,17 @@ impl std::fmt::Display for MessageType {
             MessageType::Gather3 => write!(f, "GATHER_3"),
             MessageType::GatherFin => write!(f, "GATHER_FIN"),
             MessageType::SumAndRec => write!(f, "SUM_AND_REC"),
+            MessageType::AdkgComplaint => write!(f, "ADKG_COMPLAINT"),
+            MessageType::DecRequest => write!(f, "DEC_REQUEST"),
+            MessageType::DecShare => write!(f, "DEC_SHARE"),
+            MessageType::TripleStart => write!(f, "TRIPLE_START"),
+            MessageType::TripleCheck => write!(f, "TRIPLE_CHECK"),
+            MessageType::TripleMul => write!(f, "TRIPLE_MUL"),
+            MessageType::KdfRequest => write!(f, "KDF_REQUEST"),
+            MessageType::KdfShare => write!(f, "KDF_SHARE"),
+            MessageType::RangeCheck => write!(f, "RANGE_CHECK"),
+            MessageType::RecoverRequest => write!(f, "RECOVER_REQUEST"),
+            MessageType::RecoverResponse => write!(f, "RECOVER_RESPONSE"),
         }
     }
 }
@@ -77,6 +108,8 @@ impl Message {
             msg_type: MessageType::NonType,
             msg_content: Vec::new(),
             additional: String::new(),
+            proofs: Arc::default(),
+            signature: None,
         }
     }
 
@@ -87,6 +120,8 @@ impl Message {
             msg_type,
             msg_content,
             additional: addi,
+            proofs: Arc::default(),
+            signature: None,
         }
     }
 
@@ -97,6 +132,8 @@ impl Message {
             msg_type,
             msg_content,
             additional: String::new(),
+            proofs: Arc::default(),
+            signature: None,
         })
     }
 
@@ -107,6 +144,29 @@ impl Message {
             msg_type,
             msg_content,
             additional: String::new(),
+            proofs: Arc::default(),
+            signature: None,
         })
     }
-}
\ No newline at end of file
+}
+/// 将域元素编码到消息的 `additional` 中，每个元素写作 `real:image`，以逗号分隔
+pub fn encode_values(values: &[Mersenne61Ext]) -> String {
+    values
+        .iter()
+        .map(|v| format!("{}:{}", v.get_real(), v.get_image()))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// `encode_values` 的逆运算，格式错误时返回 None
+pub fn decode_values(s: &str) -> Option<Vec<Mersenne61Ext>> {
+    if s.is_empty() {
+        return Some(vec![]);
+    }
+    s.split(',')
+        .map(|v| {
+            let (real, image) = v.split_once(':')?;
+            Some(Mersenne61Ext::new(real.parse().ok()?, image.parse().ok()?))
+        })
+        .collect()
+}
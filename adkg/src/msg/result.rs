@@ -1,10 +1,19 @@
+use util::algebra::field::mersenne61_ext::Mersenne61Ext;
+
 
 #[derive(Clone, Debug)]
 pub struct AdkgResult {
     pub id: usize,
     pub users: Vec<usize>,
     pub sk: String,
+    /// 密钥的指纹：格上的密钥为公钥 A·s + e 的哈希，否则为全部密钥的哈希，诚实节点得到相同的值
     pub pk: String,
+    /// 重构时发送了错误份额的节点
+    pub faulty: Vec<usize>,
+    /// 格上的密钥的公钥 A·s + e 的全部系数，生成单个密钥时为空
+    pub public_key: Vec<Mersenne61Ext>,
+    /// 打包秘密分享时一次生成的全部密钥，`sk` 为其中的第一个
+    pub keys: Vec<Mersenne61Ext>,
 }
 
 impl std::fmt::Display for AdkgResult {
@@ -14,7 +23,7 @@ impl std::fmt::Display for AdkgResult {
             users.push_str(&i.to_string());
             users.push_str(" ");
         }
-        write!(f, "id: {}, users: {}\n >>> sk: {}, pk: {}",
-               self.id, users, self.sk, self.pk)
+        write!(f, "id: {}, users: {}\n >>> sk: {}, pk: {}, faulty: {:?}",
+               self.id, users, self.sk, self.pk, self.faulty)
     }
 }
\ No newline at end of file
@@ -1,13 +1,15 @@
 use super::party::AvssParty;
 use crate::one2many::prover::One2ManyProver;
 use util::algebra::{coset::Coset, field::Field, polynomial::MultilinearPolynomial};
+use util::merkle_tree::MerkleTreeVerifier;
+use util::params::ProtocolParams;
 use util::query_result::QueryResult;
 use util::random_oracle::RandomOracle;
 
 /// `Dealer` 为 AVSS 协议的 Dealer 部分
 pub struct Dealer<T: Field> {
     prover: One2ManyProver<T>,
-    evaluations: Vec<MultilinearPolynomial<T>>,
+    evaluations: Vec<Vec<MultilinearPolynomial<T>>>,
 }
 
 impl<T: Field + 'static> Dealer<T> {
@@ -62,26 +64,100 @@ impl<T: Field + 'static> Dealer<T> {
         (res, evaluations)
     }
 
-    /// 
+    /// 根据协议参数对多项式 `polynomial` 进行分发，`oracle` 为本次分发的随机数
+    /// 零知识模式下先插入掩码变量，所有承诺的取值都被随机多项式掩盖，而份额多项式不变
     pub fn new(
-        total_round: usize,
+        params: &ProtocolParams<T>,
         polynomial: &MultilinearPolynomial<T>,
-        interpolate_coset: &Vec<Coset<T>>,
         oracle: &RandomOracle<T>,
-        folding_parameter: &Vec<Vec<T>>,
     ) -> Self {
-        let (functions, evaluations) = Self::batch_folding(
-            total_round,
-            polynomial,
-            folding_parameter,
-            interpolate_coset,
-        );
+        Self::batch(params, std::slice::from_ref(polynomial), oracle)
+    }
+
+    /// 分发秘密 `secret`，多项式的其余系数随机选取
+    pub fn with_secret(params: &ProtocolParams<T>, secret: T, oracle: &RandomOracle<T>) -> Self {
+        Self::with_secrets(params, &[secret], oracle)
+    }
+
+    /// 批量分发多个指定的秘密
+    pub fn with_secrets(params: &ProtocolParams<T>, secrets: &[T], oracle: &RandomOracle<T>) -> Self {
+        let polynomials: Vec<_> = secrets.iter().map(|s| params.secret_polynomial(*s)).collect();
+        Self::batch(params, &polynomials, oracle)
+    }
+
+    /// 批量分发多个多项式，第 0 轮和最后一轮的默克尔树同时承诺所有多项式，
+    /// 之后只对它们以 `oracle.rlc` 的幂次为系数的线性组合进行低次测试，k 个秘密只需要一份证明
+    pub fn batch(
+        params: &ProtocolParams<T>,
+        polynomials: &[MultilinearPolynomial<T>],
+        oracle: &RandomOracle<T>,
+    ) -> Self {
+        let total_round = params.total_round();
+        let interpolate_coset = params.interpolate_cosets();
+        let folding_parameter = params.folding_parameter();
+        let mut functions = vec![];
+        let mut evaluations = vec![];
+        for polynomial in polynomials {
+            let polynomial = if params.zero_knowledge() {
+                polynomial.blind(params.blinding_position(), params.blinding_points())
+            } else {
+                polynomial.clone()
+            };
+            let (function, evaluation) = Self::batch_folding(
+                total_round,
+                &polynomial,
+                &folding_parameter,
+                &interpolate_coset,
+            );
+            functions.push(function);
+            evaluations.push(evaluation);
+        }
+        let batch = if polynomials.len() > 1 {
+            functions.iter().map(|f| f[0][0].clone()).collect()
+        } else {
+            vec![]
+        };
+        // 第 0 轮已经承诺了各个多项式的取值，只有一轮时不需要再承诺最后一轮
+        let last = total_round - 1;
+        let final_batch = if polynomials.len() > 1 && last > 0 {
+            (0..functions[0][last].len())
+                .map(|i| functions.iter().map(|f| f[last][i].clone()).collect())
+                .collect()
+        } else {
+            vec![]
+        };
         Dealer {
             evaluations,
-            prover: One2ManyProver::new(total_round, interpolate_coset, functions, oracle),
+            prover: One2ManyProver::new(
+                total_round,
+                &interpolate_coset,
+                Self::combine(functions, oracle.rlc),
+                oracle,
+                params.zero_knowledge(),
+                batch,
+                final_batch,
+            ),
         }
     }
 
+    /// 多个多项式的全部取值以 `rlc` 的幂次为系数的线性组合
+    fn combine(functions: Vec<Vec<Vec<Vec<T>>>>, rlc: T) -> Vec<Vec<Vec<T>>> {
+        let mut functions = functions.into_iter();
+        let mut res = functions.next().unwrap();
+        let mut power = T::from_int(1);
+        for function in functions {
+            power *= rlc;
+            for (r, f) in res.iter_mut().zip(function) {
+                for (r, f) in r.iter_mut().zip(f) {
+                    for (r, f) in r.iter_mut().zip(f) {
+                        *r += power * f;
+                    }
+                }
+            }
+        }
+        res
+    }
+
    /// 提交多项式评估值的根哈希值给参与方的验证器。 
     pub fn commit_functions(&self, avss_party: &Vec<AvssParty<T>>) {
         // 将 `avss_party` 中的每个元素的 `verifier` 放入 `verifiers`
@@ -98,7 +174,12 @@ impl<T: Field + 'static> Dealer<T> {
     /// 
     pub fn send_evaluations(&self, avss_party: &mut Vec<AvssParty<T>>) {
         for i in 0..avss_party.len() {
-            avss_party[i].set_share(&self.evaluations[i % self.evaluations.len()]);
+            let shares: Vec<_> = self
+                .evaluations
+                .iter()
+                .map(|e| e[i % e.len()].clone())
+                .collect();
+            avss_party[i].set_shares(&shares);
         }
     }
 
@@ -107,9 +188,152 @@ impl<T: Field + 'static> Dealer<T> {
         self.prover.prove();
     }
 
+    /// 工作量证明的 nonce，在 `commit_foldings` 时发送给参与方
+    pub fn nonce(&self) -> u64 {
+        self.prover.nonce()
+    }
+
+    /// 参与方 `index` 在最后一轮的多项式的承诺，用于验证参与方公开的份额
+    pub fn function_root(&self, index: usize) -> MerkleTreeVerifier {
+        self.prover.final_function_root(index)
+    }
+
+    /// 参与方 `index` 在最后一轮的多项式在前 2 ^ terminate_round 个位置上的打开，
+    /// 这些位置足以唯一确定份额多项式，参与方公开份额时附带这个打开
+    pub fn share_proof(&self, index: usize) -> QueryResult<T> {
+        let len = self.evaluations[0][0].coefficients().len();
+        self.prover.open_final_function(index, &(0..len).collect())
+    }
+
     /// `query` 为 Dealer 向参与方发送协议的证明信息，包括折叠和插值部分的证明信息，以供验证。
     pub fn query(&self) -> (Vec<Vec<QueryResult<T>>>, Vec<Vec<QueryResult<T>>>) {
         self.prover.query()
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use util::algebra::field::mersenne61_ext::Mersenne61Ext;
+    use util::committee::Committee;
+    use util::security::SecurityConfig;
+
+    /// 记录中一个取值的位置：第几个查询结果中的第几个位置，最终多项式的系数记在 `usize::MAX` 之下
+    type Position = (usize, usize);
+
+    /// 分发 `polynomial` 并返回参与方 `id` 看到的除份额以外的全部取值及其位置：查询结果中的取值和最终多项式的系数
+    fn view(
+        params: &ProtocolParams<Mersenne61Ext>,
+        polynomial: &MultilinearPolynomial<Mersenne61Ext>,
+        id: usize,
+    ) -> Vec<(Position, Mersenne61Ext)> {
+        let oracle = params.oracle();
+        let mut parties = AvssParty::from_params(params, &oracle);
+        let mut dealer = Dealer::new(params, polynomial, &oracle);
+        dealer.send_evaluations(&mut parties);
+        dealer.commit_functions(&parties);
+        dealer.prove();
+        dealer.commit_foldings(&parties);
+        let (folding, function) = dealer.query();
+        let folding: Vec<_> = folding.iter().map(|x| x[id % x.len()].clone()).collect();
+        let function: Vec<_> = function.iter().map(|x| x[id % x.len()].clone()).collect();
+        assert!(parties[id].verify(&folding, &function));
+
+        let mut res = vec![];
+        for (i, result) in folding.iter().chain(function.iter()).enumerate() {
+            res.extend(result.proof_values.iter().map(|(p, v)| ((i, *p), *v)));
+        }
+        let final_value = parties[id].verifier.borrow().final_value().unwrap();
+        res.extend(final_value.coefficients().iter().enumerate().map(|(i, v)| ((usize::MAX, i), *v)));
+        res
+    }
+
+    /// 参与方 `id` 的份额多项式：秘密多项式按它的开点折叠到掩码变量之前
+    fn share_of(
+        params: &ProtocolParams<Mersenne61Ext>,
+        polynomial: &MultilinearPolynomial<Mersenne61Ext>,
+        id: usize,
+    ) -> MultilinearPolynomial<Mersenne61Ext> {
+        let mut folded = polynomial.clone();
+        for z in &params.open_points()[id][..params.blinding_position()] {
+            folded = folded.folding(*z);
+        }
+        folded
+    }
+
+    /// 模拟器只知道参与方 `id` 的份额多项式 `share`，生成一个随机的、份额相同的多项式
+    fn simulate(
+        params: &ProtocolParams<Mersenne61Ext>,
+        share: &MultilinearPolynomial<Mersenne61Ext>,
+        id: usize,
+    ) -> MultilinearPolynomial<Mersenne61Ext> {
+        let position = params.blinding_position();
+        let random = MultilinearPolynomial::random_polynomial(params.log_d());
+        let folded = share_of(params, &random, id);
+        let mut coefficients = random.coefficients().clone();
+        for (i, (s, f)) in share.coefficients().iter().zip(folded.coefficients()).enumerate() {
+            coefficients[i << position] += *s - *f;
+        }
+        MultilinearPolynomial::new(coefficients)
+    }
+
+    /// 两组记录中取值的分布不同的位置，以及两组记录都出现过的位置的个数
+    /// 每个取值都是秘密多项式和掩码的线性函数：含有掩码的取值在域上均匀分布，几乎不会重复；
+    /// 不含掩码的取值在同一个位置上总是相同，它只取决于份额时两组相同，取决于秘密的其它部分时两组不同
+    /// 因此一个取值在一组中重复出现而从未在另一组中出现时，这个位置的分布依赖于秘密
+    fn dependent_positions(a: &[Vec<(Position, Mersenne61Ext)>], b: &[Vec<(Position, Mersenne61Ext)>]) -> (Vec<Position>, usize) {
+        let mut counts: HashMap<Position, Vec<(Mersenne61Ext, usize, usize)>> = HashMap::new();
+        for (group, views) in [a, b].iter().enumerate() {
+            for (position, value) in views.iter().flatten() {
+                let values = counts.entry(*position).or_default();
+                match values.iter_mut().find(|(v, _, _)| v == value) {
+                    Some((_, ca, cb)) => *[ca, cb][group] += 1,
+                    None => values.push((*value, (group == 0) as usize, (group == 1) as usize)),
+                }
+            }
+        }
+        let common: Vec<_> = counts
+            .into_iter()
+            .filter(|(_, values)| values.iter().any(|(_, ca, _)| *ca > 0) && values.iter().any(|(_, _, cb)| *cb > 0))
+            .collect();
+        let mut dependent: Vec<_> = common
+            .iter()
+            .filter(|(_, values)| values.iter().any(|(_, ca, cb)| ca + cb >= 2 && (*ca == 0 || *cb == 0)))
+            .map(|(position, _)| *position)
+            .collect();
+        dependent.sort();
+        (dependent, common.len())
+    }
+
+    #[test]
+    fn zero_knowledge_simulation() {
+        let committee = Committee::uniform(4, 1, 1);
+        let security = SecurityConfig::new(12);
+        let plain: ProtocolParams<Mersenne61Ext> = ProtocolParams::with_security(&committee, security);
+        let zk = plain.clone().with_zero_knowledge();
+        let id = 5;
+
+        // 两个份额相同而其余系数不同的随机秘密多项式，模拟器只使用这个份额
+        let secret = MultilinearPolynomial::random_polynomial(zk.log_d());
+        let share = share_of(&zk, &secret, id);
+        let other = simulate(&zk, &share, id);
+        assert_eq!(share_of(&zk, &other, id).coefficients(), share.coefficients());
+        assert_ne!(other.coefficients(), secret.coefficients());
+        let views = |params: &ProtocolParams<Mersenne61Ext>, polynomial: &dyn Fn() -> MultilinearPolynomial<Mersenne61Ext>| {
+            (0..40).map(|_| view(params, &polynomial(), id)).collect::<Vec<_>>()
+        };
+
+        // 零知识模式下每个位置上的分布都与秘密无关：换一个份额相同的秘密，或者每次由份额模拟一个新的秘密，分布都相同
+        let real = views(&zk, &|| secret.clone());
+        for other in [views(&zk, &|| other.clone()), views(&zk, &|| simulate(&zk, &share, id))] {
+            let (dependent, common) = dependent_positions(&real, &other);
+            assert!(common > 0);
+            assert!(dependent.is_empty(), "{:?}", dependent);
+        }
+
+        // 普通模式下查询到的取值就是秘密多项式及其折叠的取值，同样的两个秘密可以区分
+        let (dependent, _) = dependent_positions(&views(&plain, &|| secret.clone()), &views(&plain, &|| other.clone()));
+        assert!(!dependent.is_empty());
+    }
+}
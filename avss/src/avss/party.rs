@@ -1,6 +1,14 @@
-use crate::one2many::verifier::One2ManyVerifier;
+use crate::one2many::verifier::{One2ManyVerifier, Transcript};
 use std::{cell::RefCell, rc::Rc};
-use util::algebra::{coset::Coset, field::Field, polynomial::MultilinearPolynomial};
+use util::algebra::{
+    coset::Coset,
+    field::Field,
+    polynomial::{MultilinearPolynomial, Polynomial, ReconstructError},
+};
+use util::envelope::Envelope;
+use util::merkle_tree::{MerkleTreeVerifier, MERKLE_ROOT_SIZE};
+use util::params::ProtocolParams;
+use util::share::{Domain, Share};
 use util::query_result::QueryResult;
 use util::random_oracle::RandomOracle;
 
@@ -9,39 +17,165 @@ use util::random_oracle::RandomOracle;
 pub struct AvssParty<T: Field> {
     pub verifier: Rc<RefCell<One2ManyVerifier<T>>>,
     open_point: Vec<T>,
-    final_poly: Option<MultilinearPolynomial<T>>,
+    share_point: T,
+    final_polys: Vec<MultilinearPolynomial<T>>,
+    recovered: Vec<T>,
+    rlc: T,
 }
 
 impl<T: Field + 'static> AvssParty<T> {
-    /// `share` 为参与方的秘密份额
+    /// `share` 为参与方的秘密份额，批量分发时为第一个秘密的份额
     pub fn share(&self) -> T {
-        let poly = self.final_poly.as_ref().unwrap();
+        self.share_of(0)
+    }
+
+    /// 批量分发时第 `index` 个秘密的份额
+    pub fn share_of(&self, index: usize) -> T {
+        if self.final_polys.is_empty() {
+            return self.recovered[index];
+        }
+        let poly = &self.final_polys[index];
         let variable_num = poly.variable_num();
         let n = self.open_point.len();
         poly.evaluate(&self.open_point[n - variable_num..].to_vec())
     }
 
+    /// 批量分发的秘密的个数
+    pub fn secret_num(&self) -> usize {
+        std::cmp::max(self.final_polys.len(), self.recovered.len())
+    }
+
+    /// 用于插值的份额 `[y, f(x, y)]`，其中 `y` 为参与方在 y 方向的求值点
     pub fn interpolate_share(&self) -> Vec<T> {
-        let poly = self.final_poly.as_ref().unwrap();
-        let variable_num = poly.variable_num();
-        let n = self.open_point.len();
-        let opn = self.open_point[n - variable_num..].to_vec();
-        let eva = poly.evaluate(&opn);
-        vec![opn[0], eva]
+        self.interpolate_share_of(0)
+    }
+
+    /// 批量分发时第 `index` 个秘密用于插值的份额
+    pub fn interpolate_share_of(&self, index: usize) -> Vec<T> {
+        vec![self.share_point, self.share_of(index)]
     }
 
     pub fn has_share(&self) -> bool {
-        self.final_poly.is_some()
+        !self.final_polys.is_empty() || !self.recovered.is_empty()
     }
 
     pub fn all_share(&self) -> MultilinearPolynomial<T>  {
-        self.final_poly.as_ref().unwrap().clone()
+        self.all_share_of(0)
+    }
+
+    /// 批量分发时第 `index` 个秘密的份额多项式
+    pub fn all_share_of(&self, index: usize) -> MultilinearPolynomial<T> {
+        self.final_polys[index].clone()
     }
 
     /// `set_share` 为设置参与方的秘密份额
     /// `final_poly` 为多项式 `f`，`f` 的次数为 `log_d`
     pub fn set_share(&mut self, final_poly: &MultilinearPolynomial<T>) {
-        self.final_poly = Some(final_poly.clone());
+        self.set_shares(std::slice::from_ref(final_poly));
+    }
+
+    /// 批量分发时设置每个秘密的份额多项式
+    pub fn set_shares(&mut self, final_polys: &[MultilinearPolynomial<T>]) {
+        self.final_polys = final_polys.to_vec();
+    }
+
+    /// 各个份额多项式以 `rlc` 的幂次为系数的线性组合，与 Dealer 证明的多项式对应
+    fn combined_share(&self) -> MultilinearPolynomial<T> {
+        combine(&self.final_polys, self.rlc)
+    }
+
+    /// 没有收到 Dealer 的份额时，由同一行（相同的 y）或同一列（相同的 x）的参与方公开的份额恢复自己的份额
+    /// `party` 为自己的下标，`helpers` 中的份额需要事先以 `ShareOpening::verify` 与 Dealer 的承诺验证一致
+    /// f 在 x 和 y 上的次数都为 t，同一列的份额对 y 插值，同一行的份额对 x 插值，都至少需要 t + 1 个份额；
+    /// 两者都足够时要求结果相同。同一行和同一列都不足 t + 1 个份额时返回 `NotEnoughShares`，
+    /// 份额不一致时返回 `Inconsistent`，其中为 `helpers` 中的下标，行和列的结果不同时为全部参与插值的份额
+    /// 这里在一个节点内模拟恢复；ADKG 中节点之间以 `RECOVER_REQUEST` 和 `RECOVER_RESPONSE` 消息公开同一列的份额
+    pub fn recover(
+        &mut self,
+        params: &ProtocolParams<T>,
+        party: usize,
+        helpers: &[ShareOpening<T>],
+    ) -> Result<(), ReconstructError> {
+        let log_n = params.log_n();
+        let mask = (1 << log_n) - 1;
+        let (coset_x, coset_y) = (params.coset_x(), params.coset_y());
+        let column: Vec<usize> = (0..helpers.len())
+            .filter(|i| helpers[*i].party & mask == party & mask && helpers[*i].party != party)
+            .collect();
+        let row: Vec<usize> = (0..helpers.len())
+            .filter(|i| helpers[*i].party >> log_n == party >> log_n && helpers[*i].party != party)
+            .collect();
+        let t = params.t();
+        if column.len() <= t && row.len() <= t {
+            return Err(ReconstructError::NotEnoughShares);
+        }
+        // 所有份额的秘密个数必须相同
+        let count = helpers[column.first().or(row.first()).copied().unwrap()].shares.len();
+        let malformed: Vec<usize> = column
+            .iter()
+            .chain(&row)
+            .filter(|i| helpers[**i].shares.len() != count)
+            .copied()
+            .collect();
+        if !malformed.is_empty() {
+            return Err(ReconstructError::Inconsistent(malformed));
+        }
+        let mut recovered = vec![];
+        for index in 0..count {
+            let interpolate = |ids: &Vec<usize>, point: &dyn Fn(usize) -> T, target: T| {
+                let shares: Vec<Vec<T>> = ids
+                    .iter()
+                    .map(|i| vec![point(helpers[*i].party), helpers[*i].share_of(params, index)[1]])
+                    .collect();
+                Polynomial::reconstruct_at(&shares, t, target).map_err(|e| match e {
+                    ReconstructError::Inconsistent(e) => {
+                        ReconstructError::Inconsistent(e.iter().map(|j| ids[*j]).collect())
+                    }
+                    _ => ReconstructError::Inconsistent(ids.clone()),
+                })
+            };
+            let mut results = vec![];
+            if column.len() > t {
+                let y = coset_y.element_at(party >> log_n);
+                results.push(interpolate(&column, &|h| coset_y.element_at(h >> log_n), y)?);
+            }
+            if row.len() > t {
+                let x = coset_x.element_at(party & mask);
+                results.push(interpolate(&row, &|h| coset_x.element_at(h & mask), x)?);
+            }
+            if results.iter().any(|r| *r != results[0]) {
+                return Err(ReconstructError::Inconsistent([column.clone(), row.clone()].concat()));
+            }
+            recovered.push(results[0]);
+        }
+        self.recovered = recovered;
+        Ok(())
+    }
+
+    /// 公开自己的份额，`party` 为自己的下标，`function_proof` 为 Dealer 发来的 `share_proof`
+    pub fn open(&self, party: usize, function_proof: &QueryResult<T>) -> ShareOpening<T> {
+        ShareOpening {
+            party,
+            shares: self.final_polys.clone(),
+            proof: function_proof.clone(),
+        }
+    }
+
+    /// 参与方收到的全部证明，`party` 为自己的下标，验证失败时可以作为投诉公开
+    pub fn proof(
+        &self,
+        party: usize,
+        folding_proofs: &[QueryResult<T>],
+        function_proofs: &[QueryResult<T>],
+    ) -> ShareProof<T> {
+        ShareProof {
+            party,
+            shares: self.final_polys.clone(),
+            transcript: self.verifier.borrow().transcript(),
+            folding_proofs: folding_proofs.to_vec(),
+            function_proofs: function_proofs.to_vec(),
+            sealed: None,
+        }
     }
 
     /// `open_point` 为参与方的开点
@@ -52,6 +186,7 @@ impl<T: Field + 'static> AvssParty<T> {
     /// `new` 为构造函数
     /// `total_round` 为协议的总轮数，`interpolate_coset` 为插值所用的 `2^i` 次根的集合
     /// `open_point` 为参与方的开点，`oracle` 用于生成随机数
+    /// y 方向的求值点取开点的最后一个分量，`from_params` 会根据协议参数重新设置
     pub fn new(
         total_round: usize,
         interpolate_coset: &Vec<Coset<T>>,
@@ -65,11 +200,31 @@ impl<T: Field + 'static> AvssParty<T> {
                 interpolate_coset,
                 oracle,
             ))),
+            share_point: *open_point.last().unwrap(),
             open_point,
-            final_poly: None,
+            final_polys: vec![],
+            recovered: vec![],
+            rlc: oracle.rlc,
         }
     }
 
+    /// 根据协议参数创建全部参与方，第 i 个参与方的开点为 `params.open_points()[i]`
+    /// 所有参与方共享同一组插值余元集合
+    pub fn from_params(params: &ProtocolParams<T>, oracle: &RandomOracle<T>) -> Vec<AvssParty<T>> {
+        let interpolate_cosets = params.interpolate_cosets();
+        params
+            .open_points()
+            .into_iter()
+            .enumerate()
+            .map(|(i, open_point)| {
+                let mut party =
+                    AvssParty::new(params.total_round(), &interpolate_cosets, open_point, oracle);
+                party.share_point = params.share_point(i >> params.log_n());
+                party
+            })
+            .collect()
+    }
+
     /// `verify` 为验证函数
     /// `folding_proofs` 为折叠证明，`function_proofs` 为函数证明
     /// 返回验证结果
@@ -82,7 +237,164 @@ impl<T: Field + 'static> AvssParty<T> {
             &folding_proofs,
             &function_proofs,
             &self.open_point,
-            self.final_poly.as_ref().unwrap(),
+            &self.combined_share(),
         )
     }
 }
+
+fn combine<T: Field>(polys: &[MultilinearPolynomial<T>], rlc: T) -> MultilinearPolynomial<T> {
+    let mut coefficients = polys[0].coefficients().clone();
+    let mut power = T::from_int(1);
+    for poly in &polys[1..] {
+        power *= rlc;
+        for (c, v) in coefficients.iter_mut().zip(poly.coefficients()) {
+            *c += power * *v;
+        }
+    }
+    MultilinearPolynomial::new(coefficients)
+}
+
+/// 参与方公开的份额多项式，以及 Dealer 在最后一轮承诺的多项式在若干位置上的默克尔打开
+/// 批量分发时最后一轮的默克尔树承诺每个秘密各自的取值，每个份额多项式分别与承诺比较
+#[derive(Clone)]
+pub struct ShareOpening<T: Field> {
+    pub party: usize,
+    pub shares: Vec<MultilinearPolynomial<T>>,
+    pub proof: QueryResult<T>,
+}
+
+impl<T: Field> ShareOpening<T> {
+    /// 验证公开的每个份额多项式都与 Dealer 在最后一轮的承诺 `root` 一致
+    /// 份额多项式的次数小于 2 ^ terminate_round，在至少这么多个位置上与承诺一致时由承诺唯一确定
+    pub fn verify(&self, params: &ProtocolParams<T>, root: &MerkleTreeVerifier) -> bool {
+        let r = params.terminate_round();
+        if self.shares.is_empty() || self.shares.iter().any(|s| s.variable_num() != r) {
+            return false;
+        }
+        let values = if self.proof.batch_values.is_empty() {
+            vec![self.proof.proof_values.clone()]
+        } else {
+            self.proof.batch_values.clone()
+        };
+        if values.len() != self.shares.len() {
+            return false;
+        }
+        let len = root.leave_number;
+        let mut leaves: Vec<usize> = values[0]
+            .keys()
+            .filter(|j| **j < len && values[0].contains_key(&(**j + len)))
+            .cloned()
+            .collect();
+        leaves.sort();
+        if leaves.len() < 1 << r || !self.proof.verify_merkle_tree(&leaves, root) {
+            return false;
+        }
+        let round = params.total_round() - 1;
+        let cosets = params.interpolate_cosets();
+        let challenge = params.open_point(self.party)[round];
+        self.shares.iter().zip(&values).all(|(share, values)| {
+            leaves.iter().all(|j| {
+                let x = values[j];
+                let nx = values[&(j + len)];
+                let v = x + nx + challenge * (x - nx) * cosets[round].element_inv_at(*j);
+                v == share.evaluate_as_polynomial(cosets[round + 1].element_at(*j)) * T::from_int(2)
+            })
+        })
+    }
+
+    /// 第 `index` 个秘密用于插值的份额 `[y, f(x, y)]`
+    pub fn share_of(&self, params: &ProtocolParams<T>, index: usize) -> Vec<T> {
+        let open_point = params.open_point(self.party);
+        let n = open_point.len();
+        let poly = &self.shares[index];
+        let share = poly.evaluate(&open_point[n - poly.variable_num()..].to_vec());
+        vec![params.share_point(self.party >> params.log_n()), share]
+    }
+}
+
+/// Dealer 发给参与方 `party` 的份额多项式和证明，`transcript` 为 Dealer 公开的承诺
+#[derive(Debug, Clone)]
+pub struct ShareProof<T: Field> {
+    pub party: usize,
+    pub shares: Vec<MultilinearPolynomial<T>>,
+    pub transcript: Transcript<T>,
+    pub folding_proofs: Vec<QueryResult<T>>,
+    pub function_proofs: Vec<QueryResult<T>>,
+    /// 加密分发时以接收者的 KEM 公钥加密的 `shares`、`transcript.final_value` 和查询结果，此时它们为空，
+    /// 广播的证明中只有公开的承诺
+    pub sealed: Option<Envelope>,
+}
+
+impl<T: Field> ShareProof<T> {
+    /// 由协议参数和 Dealer 公开的信息验证份额，与 `AvssParty::verify` 的结果相同
+    pub fn verify(&self, params: &ProtocolParams<T>) -> bool {
+        let r = params.terminate_round();
+        if self.shares.is_empty() || self.shares.iter().any(|s| s.variable_num() != r) {
+            return false;
+        }
+        let open_point = params.open_point(self.party);
+        let verifier = One2ManyVerifier::from_transcript(
+            params.total_round(),
+            open_point.len(),
+            &params.interpolate_cosets(),
+            &self.transcript,
+        );
+        verifier.verify_with_extra_folding(
+            &self.folding_proofs,
+            &self.function_proofs,
+            &open_point,
+            &combine(&self.shares, self.transcript.oracle.rlc),
+        )
+    }
+
+    /// 第 `index` 个秘密的份额
+    pub fn share_of(&self, params: &ProtocolParams<T>, index: usize) -> T {
+        let open_point = params.open_point(self.party);
+        let n = open_point.len();
+        let poly = &self.shares[index];
+        poly.evaluate(&open_point[n - poly.variable_num()..].to_vec())
+    }
+
+    /// 第 `index` 个秘密的份额，位于参与方所在的求值点上，来自 Dealer `dealer`
+    pub fn share(&self, params: &ProtocolParams<T>, index: usize, dealer: usize) -> Share<T> {
+        Share::dealt(Domain::new(params), self.party >> params.log_n(), self.share_of(params, index), dealer)
+    }
+}
+
+/// 参与方验证 Dealer 的证明失败时公开的投诉，任何节点都可以检查
+/// `proof` 为参与方收到的加密的证明，`revealed` 为它解密信封时得到的 KEM 随机数，
+/// 其他节点由随机数和投诉者的公钥重新封装并打开信封，因此投诉中的份额只能是 Dealer 加密给投诉者的份额
+#[derive(Debug, Clone)]
+pub struct Complaint<T: Field> {
+    pub proof: ShareProof<T>,
+    pub revealed: [u8; 32],
+}
+
+impl<T: Field> Complaint<T> {
+    pub fn new(proof: ShareProof<T>, revealed: [u8; 32]) -> Self {
+        Complaint { proof, revealed }
+    }
+
+    /// 投诉成立当且仅当：投诉中的承诺和信封与 Dealer 广播的承诺 `commitment` 和信封 `sealed` 相同，
+    /// 而打开信封得到的证明无法通过验证。查询结果在信封中，投诉者无法伪造
+    /// `open` 以公开的随机数打开信封，随机数与信封中的密文不一致时返回 None，此时投诉不成立
+    pub fn verify<F>(
+        &self,
+        params: &ProtocolParams<T>,
+        commitment: &[u8; MERKLE_ROOT_SIZE],
+        sealed: &Envelope,
+        open: F,
+    ) -> bool
+    where
+        F: FnOnce(&ShareProof<T>, &[u8; 32]) -> Option<ShareProof<T>>,
+    {
+        let proof = &self.proof;
+        if proof.sealed.as_ref() != Some(sealed) || proof.transcript.digest() != *commitment {
+            return false;
+        }
+        match open(proof, &self.revealed) {
+            Some(opened) => opened.party == proof.party && !opened.verify(params),
+            None => false,
+        }
+    }
+}
@@ -9,82 +9,23 @@ pub mod avss {
 
 use avss::dealer::Dealer;
 use avss::party::AvssParty;
-use util::algebra::field::Field;
 use util::algebra::polynomial::MultilinearPolynomial;
-use util::random_oracle::RandomOracle;
-
-use util::algebra::coset::Coset;
 use util::algebra::field::mersenne61_ext::Mersenne61Ext;
-use util::split_n;
-use util::{CODE_RATE, SECURITY_BITS};
-
+use util::params::ProtocolParams;
 
-pub fn avss_deal(log_n: usize, terminate_round: usize) {
-    
-    let log_t = log_n - 2;          // 余元集合的大小
-    let log_d = log_t * 2;          // 多项式的次数（变量数）
 
+/// 模拟一次 AVSS 的分发过程，多项式在 x 和 y 上的次数均为门限 t
+pub fn avss_deal(params: &ProtocolParams<Mersenne61Ext>) {
     // oracle 用于生成随机数
-    let oracle = RandomOracle::new(log_d - terminate_round, SECURITY_BITS / CODE_RATE);
-    
-    // interpolate_cosets 为余元集合，第一个余元是随机生成的，后面的余元是前一个余元的平方
-    let mut interpolate_cosets = vec![Coset::new(
-        1 << (log_t * 2 + CODE_RATE),
-        Mersenne61Ext::random_element(),
-    )];
-    for i in 1..log_d {
-        interpolate_cosets.push(interpolate_cosets[i - 1].pow(2));
-    }
+    let oracle = params.oracle();
 
     // polynomial 为多项式
-    let polynomial = MultilinearPolynomial::random_polynomial(log_d);
-
-    //生成变换参数 coset_x 和 coset_y，使用这些参数创建两个余元集合 coset_x 和 coset_y
-    let x_shift = Mersenne61Ext::random_element();
-    let coset_x = Coset::new(1 << log_n, x_shift);
-    let y_shift = Mersenne61Ext::random_element();
-    let coset_y = Coset::new(1 << log_n, y_shift);
-
-    // folding_parameter 存储多轮的折叠参数
-    let mut folding_parameter = vec![];
-    let v = split_n((1 << log_t) - 1);
-    for i in &v {
-        folding_parameter.push(coset_x.pow(*i).all_elements());
-    }
-    let last_len = folding_parameter.last().unwrap().len();
-    for i in &v {
-        folding_parameter.push(
-            coset_y
-                .pow(*i)
-                .all_elements()
-                .iter()
-                .map(|x| (0..last_len).into_iter().map(|_| *x).collect::<Vec<_>>())
-                .flatten()
-                .collect(),
-        );
-    }
+    let polynomial = MultilinearPolynomial::random_polynomial(params.log_d());
 
-    let mut parties = vec![];
-    for i in 0..(1 << (log_n * 2)) {
-        let mut open_point = vec![];
-        for j in 0..log_d {
-            open_point.push(folding_parameter[j][i % folding_parameter[j].len()]);
-        }
-        parties.push(AvssParty::new(
-            log_d - terminate_round,
-            &interpolate_cosets,
-            open_point,
-            &oracle,
-        ));
-    }
+    // 根据协议参数创建全部参与方，共有 n^2 个参与方
+    let mut parties = AvssParty::from_params(params, &oracle);
 
-    let mut dealer = Dealer::new(
-        log_d - terminate_round,
-        &polynomial,
-        &interpolate_cosets,
-        &oracle,
-        &folding_parameter,
-    );
+    let mut dealer = Dealer::new(params, &polynomial, &oracle);
 
     // 为每个参与方分配秘密份额
     dealer.send_evaluations(&mut parties);
@@ -100,84 +41,23 @@ pub fn avss_deal(log_n: usize, terminate_round: usize) {
 
 
 
-pub fn avss_verify(log_n: usize, terminate_round: usize) {
-    let log_t = log_n - 2;
-    let log_d = log_t * 2;
-    let oracle = RandomOracle::new(log_d - terminate_round, SECURITY_BITS / CODE_RATE);
-    
-    // interpolate_cosets 首先生成一个生成元，阶为 2 ^ (log_t * 2 + CODE_RATE)
-    let mut interpolate_cosets = vec![Coset::new(
-        1 << (log_t * 2 + CODE_RATE),
-        Mersenne61Ext::random_element(),
-    )];
-    // 然后生成 log_d - 1 个余元，每个余元是前一个余元的平方
-    for i in 1..log_d {
-        interpolate_cosets.push(interpolate_cosets[i - 1].pow(2));
-    }
+/// 模拟一次 AVSS 的分发过程，并由第一个参与方进行验证
+pub fn avss_verify(params: &ProtocolParams<Mersenne61Ext>) {
+    let oracle = params.oracle();
 
-    // 生成一个随机多项式，次数为 2 ^ log_d，即 n^2/16
-    let polynomial = MultilinearPolynomial::random_polynomial(log_d);
-
-    // 生成两个生成元 coset_x 和 coset_y，阶为 2 ^ log_n，即 n
-    // 使用这些参数创建两个余元集合 coset_x 和 coset_y
-    let x_shift = Mersenne61Ext::random_element();
-    let coset_x = Coset::new(1 << log_n, x_shift);
-    let y_shift = Mersenne61Ext::random_element();
-    let coset_y = Coset::new(1 << log_n, y_shift);
-
-    // folding_parameter 存储多轮的折叠参数
-    // 首先得到 t - 1 的二进制表达的数组，比如 t = 4，那么 v = [1, 2]
-    // 然后计算出 coset_x 的 v 次方，得到一个二维数组，每一行都是 coset_x 的 v[i] 次方的所有元素
-    let mut folding_parameter = vec![];
-    let v = split_n((1 << log_t) - 1);
-    for i in &v {
-        folding_parameter.push(coset_x.pow(*i).all_elements());
-    }
-    
-    // 计算最后一个元素的长度
-    // 遍历 v 中的每一个元素，计算 coset_y 的 v[i] 次方，得到一个二维数组，每一行都是 coset_y 的 v[i] 次方的所有元素
-    // 第二层map函数使得每个元素都重复 last_len 次
-    let last_len = folding_parameter.last().unwrap().len();
-    for i in &v {
-        folding_parameter.push(
-            coset_y
-                .pow(*i)
-                .all_elements()
-                .iter()
-                .map(|x| (0..last_len).into_iter().map(|_| *x).collect::<Vec<_>>())
-                .flatten()
-                .collect(),
-        );
-    }
+    // 生成一个随机多项式，共有 2 ^ log_d 个系数
+    let polynomial = MultilinearPolynomial::random_polynomial(params.log_d());
 
     // 输出折叠参数
+    let folding_parameter = params.folding_parameter();
     for i in 0..folding_parameter.len() {
         println!("{}: {:?}", i, folding_parameter[i]);
     }
 
     // parties 存储参与方，有 n^2 个参与方
-    let mut parties = vec![];
-    for i in 0..(1 << (log_n * 2)) {
-        let mut open_point = vec![];
-        // 每个参与方有 log_d 个开放点，每个开放点都是折叠参数的一个元素
-        for j in 0..log_d {
-            open_point.push(folding_parameter[j][i % folding_parameter[j].len()]);
-        }
-        parties.push(AvssParty::new(
-            log_d - terminate_round,
-            &interpolate_cosets,
-            open_point,
-            &oracle,
-        ));
-    }
+    let mut parties = AvssParty::from_params(params, &oracle);
 
-    let mut dealer = Dealer::new(
-        log_d - terminate_round,
-        &polynomial,
-        &interpolate_cosets,
-        &oracle,
-        &folding_parameter,
-    );
+    let mut dealer = Dealer::new(params, &polynomial, &oracle);
     dealer.send_evaluations(&mut parties);
     dealer.commit_functions(&parties);
     dealer.prove();
@@ -185,8 +65,9 @@ pub fn avss_verify(log_n: usize, terminate_round: usize) {
     let (folding, function) = dealer.query();
     let mut folding0 = vec![];
     let mut function0 = vec![];
-    for i in 0..(log_d - terminate_round) {
-        if i < log_d - terminate_round - 1 {
+    let total_round = params.total_round();
+    for i in 0..total_round {
+        if i < total_round - 1 {
             folding0.push(folding[i][0].clone());
         }
         function0.push(function[i][0].clone());
@@ -1,7 +1,61 @@
+use std::env;
 use avss::{avss_deal, avss_verify};
+use util::committee::Committee;
+use util::params::ProtocolParams;
+use util::SECURITY_BITS;
 
 fn main() {
-    avss_deal(4, 2);
-    avss_verify(3, 1);
+    let args: Vec<String> = env::args().collect();
+
+    // 缺省时分别以 (n, t) = (13, 4) 和 (7, 2) 运行分发和验证
+    let (n, t, terminate_round) = get_args(args);
+    let params = |n: usize, t: usize| {
+        let params = ProtocolParams::new(&Committee::uniform(n, (n - 1) / 3, t), SECURITY_BITS);
+        let r = terminate_round.unwrap_or_else(|| params.terminate_round());
+        println!("n: {}, t: {}, terminate_round: {}, optimal: {}, cost: {:?}", n, t, r, params.optimal_terminate_round(), params.proof_cost(r));
+        println!("query_num: {}, security bits: {:.1}", params.query_num(), params.achieved_bits());
+        params.with_terminate_round(r)
+    };
+    match n {
+        Some(n) => {
+            if n < 4 {
+                panic!("must have n >= 4");
+            }
+            let t = t.unwrap_or((n - 1) / 3);
+            avss_deal(&params(n, t));
+            avss_verify(&params(n, t));
+        }
+        None => {
+            avss_deal(&params(13, 4));
+            avss_verify(&params(7, 2));
+        }
+    }
+}
+
+/// 解析命令行参数，`-n` 为参与方数量，`-t` 为门限，缺省时取 `t = (n - 1) / 3`
+/// `-r` 为参与方最终收到的多项式的变量数，与 adkg 相同缺省时取 1
+fn get_args(args: Vec<String>) -> (Option<usize>, Option<usize>, Option<usize>) {
+    let mut n = None;
+    let mut t = None;
+    let mut terminate_round = None;
+    for i in 0..args.len() {
+        if args[i] == "-n" {
+            n = Some(value(&args, i));
+        }
+        if args[i] == "-t" {
+            t = Some(value(&args, i));
+        }
+        if args[i] == "-r" {
+            terminate_round = Some(value(&args, i));
+        }
+    }
+    (n, t, terminate_round)
 }
 
+/// 第 i 个参数之后的取值
+fn value(args: &[String], i: usize) -> usize {
+    match args.get(i + 1).map(|v| v.parse::<usize>()) {
+        Some(Ok(v)) => v,
+        _ => panic!("{} requires a non-negative integer", args[i]),
+    }
+}
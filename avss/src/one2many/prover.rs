@@ -1,9 +1,12 @@
 use std::{cell::RefCell, rc::Rc};
 
-use super::verifier::One2ManyVerifier;
+use super::verifier::{view_leaf, One2ManyVerifier};
 use util::algebra::polynomial::Polynomial;
 
-use util::merkle_tree::MERKLE_ROOT_SIZE;
+use rand::Rng;
+use std::collections::HashMap;
+use util::merkle_tree::{MerkleTreeVerifier, MERKLE_ROOT_SIZE, SALT_SIZE};
+use util::pow;
 use util::query_result::QueryResult;
 use util::{
     algebra::{
@@ -16,22 +19,54 @@ use util::{
 
 
 /// 插值值，包含插值值和Merkle树
+/// 批量承诺时 `batch` 为各个多项式的取值，默克尔树建立在这些取值上，`value` 为它们的线性组合
 struct InterpolateValue<T: Field> {
     value: Vec<T>,
     merkle_tree: MerkleTreeProver,
+    salts: Vec<Vec<u8>>,
+    batch: Vec<Vec<T>>,
 }
 
 impl<T: Field> InterpolateValue<T> {
     /// 创建一个 InterpolateValue 实例，其中包含了多项式在余元集合上的取值，以及对应的 Merkle 树。
     /// value中的前半部分为多项式在余元集合上的取值，后半部分为多项式在余元集合上的取值的逆变换。
-    fn new(value: Vec<T>) -> Self {
+    /// `salted` 为真时每个叶子节点附加一个随机盐，使得未打开的叶子节点的哈希不泄露取值
+    fn new(value: Vec<T>, salted: bool) -> Self {
+        Self::new_batch(value, vec![], salted)
+    }
+
+    /// `batch` 为空时默克尔树建立在 `value` 上，否则建立在 `batch` 中各个多项式的取值上
+    fn new_batch(value: Vec<T>, batch: Vec<Vec<T>>, salted: bool) -> Self {
         let len = value.len() / 2;
+        let salts: Vec<Vec<u8>> = if salted {
+            (0..len)
+                .map(|_| rand::thread_rng().gen::<[u8; SALT_SIZE]>().to_vec())
+                .collect()
+        } else {
+            vec![]
+        };
         let merkle_tree = MerkleTreeProver::new(
             (0..len)
-                .map(|i| as_bytes_vec(&[value[i], value[i + len]]))
+                .map(|i| {
+                    let mut leaf = if batch.is_empty() {
+                        as_bytes_vec(&[value[i], value[i + len]])
+                    } else {
+                        let values: Vec<T> = batch.iter().flat_map(|v| [v[i], v[i + len]]).collect();
+                        as_bytes_vec(&values)
+                    };
+                    if salted {
+                        leaf.extend(&salts[i]);
+                    }
+                    leaf
+                })
                 .collect(),
         );
-        Self { value, merkle_tree }
+        Self {
+            value,
+            merkle_tree,
+            salts,
+            batch,
+        }
     }
 
     /// 多项式在余元集合上的取值的个数，实际上为 value 的长度的一半。
@@ -48,15 +83,32 @@ impl<T: Field> InterpolateValue<T> {
     /// 返回值中的 proof_bytes 为需要证明的叶子结点的证明路径，proof_values 为证明的取值。
     fn query(&self, leaf_indices: &Vec<usize>) -> QueryResult<T> {
         let len = self.merkle_tree.leave_num();
-        let proof_values = leaf_indices
-            .iter()
-            // .map(|j| [(*j, self.value[*j]), (*j + len, self.value[*j + len])])
-            .flat_map(|j| [(*j, self.value[*j]), (*j + len, self.value[*j + len])])
-            .collect();
+        let open = |value: &Vec<T>| -> HashMap<usize, T> {
+            leaf_indices
+                .iter()
+                .flat_map(|j| [(*j, value[*j]), (*j + len, value[*j + len])])
+                .collect()
+        };
+        // 批量承诺时只发送各个多项式的取值，线性组合由验证者计算
+        let (proof_values, batch_values) = if self.batch.is_empty() {
+            (open(&self.value), vec![])
+        } else {
+            (HashMap::new(), self.batch.iter().map(open).collect())
+        };
         let proof_bytes = self.merkle_tree.open(&leaf_indices);
+        let salts = if self.salts.is_empty() {
+            HashMap::new()
+        } else {
+            leaf_indices
+                .iter()
+                .map(|j| (*j, self.salts[*j].clone()))
+                .collect()
+        };
         QueryResult {
             proof_bytes,
             proof_values,
+            salts,
+            batch_values,
         }
     }
 }
@@ -72,11 +124,11 @@ impl<T: Field> CosetInterpolate<T> {
         self.interpolates.len()
     }
 
-    fn new(functions: Vec<Vec<T>>) -> Self {
+    fn new(functions: Vec<Vec<T>>, salted: bool) -> Self {
         CosetInterpolate {
             interpolates: functions
                 .into_iter()
-                .map(|values| InterpolateValue::new(values))
+                .map(|values| InterpolateValue::new(values, salted))
                 .collect(),
         }
     }
@@ -106,24 +158,54 @@ pub struct One2ManyProver<T: Field> {
     foldings: Vec<CosetInterpolate<T>>,
     oracle: RandomOracle<T>,
     final_value: Vec<Polynomial<T>>,
+    nonce: u64,
+    salted: bool,
+    transcript_tree: Option<MerkleTreeProver>,
 }
 
 impl<T: Field> One2ManyProver<T> {
     
+    /// `salted` 为真时所有默克尔树的叶子节点都附加随机盐
+    /// `batch` 不为空时为批量证明：`batch` 为各个多项式在第一个余元上的取值，第 0 轮的默克尔树同时承诺这些取值，
+    /// 而 `functions` 为它们以 `oracle.rlc` 的幂次为系数的线性组合
+    /// `final_batch[i]` 为各个多项式在最后一轮第 i 个插值上的取值，最后一轮的默克尔树同样承诺这些取值，
+    /// 参与方公开份额时可以逐个检查每个秘密的份额
     pub fn new(
         total_round: usize,
         interpolate_coset: &Vec<Coset<T>>,
         functions: Vec<Vec<Vec<T>>>,
         oracle: &RandomOracle<T>,
+        salted: bool,
+        batch: Vec<Vec<T>>,
+        final_batch: Vec<Vec<Vec<T>>>,
     ) -> One2ManyProver<T> {
         assert_eq!(total_round, functions.len());
         // functions: Vec<CosetInterpolate<T>>，是参数中functions的每个元素转换成CosetInterpolate<T>的结果
         // 每个CosetInterpolate中包含了多个InterpolateValue
         // 每个InterpolateValue包含了多项式在余元集合上的取值，以及对应的Merkle树
-        let functions: Vec<CosetInterpolate<T>> = functions
-            .into_iter()
-            .map(|x| CosetInterpolate::new(x))
+        let mut functions = functions.into_iter();
+        let first = functions.next().unwrap().into_iter().next().unwrap();
+        let mut functions: Vec<CosetInterpolate<T>> = functions
+            .enumerate()
+            .map(|(i, x)| {
+                if i + 2 == total_round && !final_batch.is_empty() {
+                    CosetInterpolate::from_interpolates(
+                        x.into_iter()
+                            .zip(final_batch.clone())
+                            .map(|(value, batch)| InterpolateValue::new_batch(value, batch, salted))
+                            .collect(),
+                    )
+                } else {
+                    CosetInterpolate::new(x, salted)
+                }
+            })
             .collect();
+        functions.insert(
+            0,
+            CosetInterpolate::from_interpolates(vec![InterpolateValue::new_batch(
+                first, batch, salted,
+            )]),
+        );
 
         One2ManyProver {
             total_round,
@@ -132,6 +214,9 @@ impl<T: Field> One2ManyProver<T> {
             foldings: vec![],
             oracle: oracle.clone(),
             final_value: vec![],
+            nonce: 0,
+            salted,
+            transcript_tree: None,
         }
     }
 
@@ -147,10 +232,26 @@ impl<T: Field> One2ManyProver<T> {
         }
     }
 
+    /// 最后一轮第 `index` 个验证者对应的多项式的承诺，Dealer 将它公开给所有参与方
+    pub fn final_function_root(&self, index: usize) -> MerkleTreeVerifier {
+        let function = self.functions[self.total_round - 1].get_interpolation(index);
+        MerkleTreeVerifier::new(function.leave_num(), &function.commit())
+    }
+
+    /// 最后一轮第 `index` 个验证者对应的多项式在 `leaf_indices` 处的打开
+    pub fn open_final_function(&self, index: usize, leaf_indices: &Vec<usize>) -> QueryResult<T> {
+        self.functions[self.total_round - 1]
+            .get_interpolation(index)
+            .query(leaf_indices)
+    }
+
     /// 前 total_round - 1 轮，向每个验证者的 `folding_root` 中添加 total_round - 1 个默克尔树的验证器，用于验证折叠的根。
-    /// 最后一轮，向每个验证者的 `final_value` 中添加一个多项式，用于验证最终值。
+    /// 最后一轮，向每个验证者的 `final_value` 中添加一个多项式，用于验证最终值，并发送工作量证明的 nonce
+    /// 以及所有验证者的记录的默克尔树根和验证者自己的记录在其中的证明。
     /// 函数中需要self.foldings中有(total_round - 1) * len(verifiers)个InterpolateValue
     pub fn commit_foldings(&self, verifiers: &Vec<Rc<RefCell<One2ManyVerifier<T>>>>) {
+        let tree = self.transcript_tree.as_ref().expect("must prove before committing the foldings");
+        let root = MerkleTreeVerifier::new(tree.leave_num(), &tree.commit());
         for i in 0..(self.total_round - 1) {
             for (idx, j) in verifiers.into_iter().enumerate() {
                 let interpolation = self.foldings[i].get_interpolation(idx);
@@ -162,6 +263,9 @@ impl<T: Field> One2ManyProver<T> {
             verifiers[i]
                 .borrow_mut()
                 .set_final_value(&self.final_value[i % self.final_value.len()]);
+            verifiers[i].borrow_mut().set_nonce(self.nonce);
+            let proof = tree.open(&vec![i % tree.leave_num()]);
+            verifiers[i].borrow_mut().set_transcript_root(i, root.clone(), proof);
         }
     }
 
@@ -208,7 +312,7 @@ impl<T: Field> One2ManyProver<T> {
                 // 对于每个需要进行插值的函数，计算插值值，并将其添加到 interpolates 中
                 for j in 0..self.functions[i].len() {
                     let next_evalutation = self.evaluation_next_domain(i, j, challenge);
-                    let interpolate_value = InterpolateValue::new(next_evalutation);
+                    let interpolate_value = InterpolateValue::new(next_evalutation, self.salted);
                     interpolates.push(interpolate_value);
                 }
                 self.foldings
@@ -222,13 +326,52 @@ impl<T: Field> One2ManyProver<T> {
                 }
             }
         }
+        // 每个验证者的记录取决于它在各轮的插值下标，记录的个数为各轮插值个数的最大值
+        let views = self.functions.iter().map(|f| f.len()).max().unwrap();
+        let leaves = (0..views).map(|v| self.view_leaf(v)).collect();
+        self.transcript_tree = Some(MerkleTreeProver::new(leaves));
+        if self.oracle.grinding_bits > 0 {
+            self.nonce = pow::grind(&self.transcript_seed(), self.oracle.grinding_bits);
+        }
+    }
+
+    /// 第 `index` 个验证者的记录，与验证者收到的承诺和最终多项式相同
+    fn view_leaf(&self, index: usize) -> Vec<u8> {
+        let root = |interpolation: &InterpolateValue<T>| {
+            MerkleTreeVerifier::new(interpolation.leave_num(), &interpolation.commit())
+        };
+        let function_root: Vec<_> = self.functions.iter().map(|f| root(f.get_interpolation(index))).collect();
+        let folding_root: Vec<_> = self.foldings.iter().map(|f| root(f.get_interpolation(index))).collect();
+        view_leaf(&function_root, &folding_root, &self.final_value[index % self.final_value.len()])
+    }
+
+    /// 工作量证明绑定的记录：所有验证者的记录的默克尔树根和折叠的挑战值
+    fn transcript_seed(&self) -> [u8; MERKLE_ROOT_SIZE] {
+        pow::transcript_seed(
+            &self.transcript_tree.as_ref().unwrap().commit(),
+            &self.oracle.folding_challenges,
+        )
+    }
+
+    /// 工作量证明的 nonce，不进行工作量证明时为 0
+    pub fn nonce(&self) -> u64 {
+        self.nonce
+    }
+
+    /// 查询的下标，进行工作量证明时由记录和 nonce 导出
+    fn query_list(&self) -> Vec<usize> {
+        if self.oracle.grinding_bits > 0 {
+            pow::query_indices(&self.transcript_seed(), self.nonce, self.oracle.query_list.len())
+        } else {
+            self.oracle.query_list.clone()
+        }
     }
 
     /// 查询证明中的信息，包括插值和折叠部分的证明信息，以便供验证器验证。
     pub fn query(&self) -> (Vec<Vec<QueryResult<T>>>, Vec<Vec<QueryResult<T>>>) {
         let mut folding_res = vec![];
         let mut functions_res = vec![];
-        let mut leaf_indices = self.oracle.query_list.clone();
+        let mut leaf_indices = self.query_list();
 
         for i in 0..self.total_round {
             let len = self.functions[i].field_size();
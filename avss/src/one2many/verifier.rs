@@ -1,5 +1,7 @@
+use util::algebra::field::as_bytes_vec;
 use util::algebra::polynomial::{MultilinearPolynomial, Polynomial};
 use util::merkle_tree::MERKLE_ROOT_SIZE;
+use util::pow;
 use util::random_oracle::RandomOracle;
 use util::{
     algebra::{coset::Coset, field::Field},
@@ -7,6 +9,64 @@ use util::{
     query_result::QueryResult,
 };
 
+/// Dealer 对一个验证者公开的全部信息：公共随机数、每轮的默克尔树根、最终多项式和工作量证明的 nonce
+/// `transcript_root` 承诺所有验证者的记录，`transcript_proof` 证明第 `index` 个验证者的记录在其中
+/// 验证者据此可以重新验证查询结果，其他节点通过比较摘要确认它与 Dealer 广播的承诺相同
+#[derive(Debug, Clone)]
+pub struct Transcript<T: Field> {
+    pub oracle: RandomOracle<T>,
+    pub function_root: Vec<MerkleTreeVerifier>,
+    pub folding_root: Vec<MerkleTreeVerifier>,
+    pub final_value: Option<Polynomial<T>>,
+    pub nonce: u64,
+    pub index: usize,
+    pub transcript_root: Option<MerkleTreeVerifier>,
+    pub transcript_proof: Vec<u8>,
+}
+
+impl<T: Field> Transcript<T> {
+    pub fn digest(&self) -> [u8; MERKLE_ROOT_SIZE] {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(&as_bytes_vec(&[self.oracle.beta, self.oracle.rlc]));
+        hasher.update(&as_bytes_vec(&self.oracle.folding_challenges));
+        for i in &self.oracle.query_list {
+            hasher.update(&(*i as u64).to_le_bytes());
+        }
+        hasher.update(&(self.oracle.grinding_bits as u64).to_le_bytes());
+        for root in self.function_root.iter().chain(&self.folding_root) {
+            hasher.update(&(root.leave_number as u64).to_le_bytes());
+            hasher.update(&root.merkle_root);
+        }
+        if let Some(value) = &self.final_value {
+            hasher.update(&as_bytes_vec(value.coefficients()));
+        }
+        hasher.update(&self.nonce.to_le_bytes());
+        hasher.update(&(self.index as u64).to_le_bytes());
+        if let Some(root) = &self.transcript_root {
+            hasher.update(&(root.leave_number as u64).to_le_bytes());
+            hasher.update(&root.merkle_root);
+        }
+        hasher.update(&self.transcript_proof);
+        hasher.finalize().into()
+    }
+}
+
+/// 一个验证者的记录作为默克尔树的叶子：每轮多项式和折叠的默克尔树根以及最终多项式
+/// Dealer 在工作量证明之前承诺所有验证者的叶子，查询的下标由这棵树的根导出
+pub fn view_leaf<T: Field>(
+    function_root: &[MerkleTreeVerifier],
+    folding_root: &[MerkleTreeVerifier],
+    final_value: &Polynomial<T>,
+) -> Vec<u8> {
+    let mut leaf = vec![];
+    for root in function_root.iter().chain(folding_root) {
+        leaf.extend((root.leave_number as u64).to_le_bytes());
+        leaf.extend(root.merkle_root);
+    }
+    leaf.extend(as_bytes_vec(final_value.coefficients()));
+    leaf
+}
+
 #[derive(Clone)]                            // 编译器会自动实现 Clone trait
 pub struct One2ManyVerifier<T: Field> {
     total_round: usize,                     // 协议的总轮数
@@ -16,6 +76,10 @@ pub struct One2ManyVerifier<T: Field> {
     folding_root: Vec<MerkleTreeVerifier>,  // 一个默克尔树的验证器，用于验证折叠的根
     oracle: RandomOracle<T>,                // 一个随机数生成器
     final_value: Option<Polynomial<T>>,     // 多项式的最终值，Option<T> 表示一个可能存在的值，如果存在则为 Some(T)，否则为 None
+    nonce: u64,                             // 工作量证明的 nonce
+    index: usize,                           // 验证者的记录在 `transcript_root` 中的下标
+    transcript_root: Option<MerkleTreeVerifier>, // 所有验证者的记录的默克尔树根
+    transcript_proof: Vec<u8>,              // 自己的记录在 `transcript_root` 中的证明
 }
 
 impl<T: Field> One2ManyVerifier<T> {
@@ -33,6 +97,10 @@ impl<T: Field> One2ManyVerifier<T> {
             folding_root: vec![],
             oracle: oracle.clone(),
             final_value: None,
+            nonce: 0,
+            index: 0,
+            transcript_root: None,
+            transcript_proof: vec![],
         }
     }
 
@@ -50,6 +118,46 @@ impl<T: Field> One2ManyVerifier<T> {
             folding_root: vec![],
             oracle: oracle.clone(),
             final_value: None,
+            nonce: 0,
+            index: 0,
+            transcript_root: None,
+            transcript_proof: vec![],
+        }
+    }
+
+    /// 由 Dealer 公开的信息构造验证者，用于检查其他验证者的投诉
+    pub fn from_transcript(
+        total_round: usize,
+        log_max_degree: usize,
+        coset: &[Coset<T>],
+        transcript: &Transcript<T>,
+    ) -> Self {
+        One2ManyVerifier {
+            total_round,
+            log_max_degree,
+            interpolate_cosets: coset.to_vec(),
+            function_root: transcript.function_root.clone(),
+            folding_root: transcript.folding_root.clone(),
+            oracle: transcript.oracle.clone(),
+            final_value: transcript.final_value.clone(),
+            nonce: transcript.nonce,
+            index: transcript.index,
+            transcript_root: transcript.transcript_root.clone(),
+            transcript_proof: transcript.transcript_proof.clone(),
+        }
+    }
+
+    /// 收到的 Dealer 公开的信息
+    pub fn transcript(&self) -> Transcript<T> {
+        Transcript {
+            oracle: self.oracle.clone(),
+            function_root: self.function_root.clone(),
+            folding_root: self.folding_root.clone(),
+            final_value: self.final_value.clone(),
+            nonce: self.nonce,
+            index: self.index,
+            transcript_root: self.transcript_root.clone(),
+            transcript_proof: self.transcript_proof.clone(),
         }
     }
 
@@ -75,14 +183,46 @@ impl<T: Field> One2ManyVerifier<T> {
         self.final_value = Some(value.clone());
     }
 
-    pub fn verify_with_extra_folding(
-        &self,
-        folding_proofs: &Vec<QueryResult<T>>,
-        function_proofs: &Vec<QueryResult<T>>,
-        extra_folding_param: &Vec<T>,
-        extra_final_poly: &MultilinearPolynomial<T>,
-    ) -> bool {
-        let mut leaf_indices = self.oracle.query_list.clone();
+    /// Dealer 以明文发送的最终多项式
+    pub fn final_value(&self) -> Option<Polynomial<T>> {
+        self.final_value.clone()
+    }
+
+    pub fn set_nonce(&mut self, nonce: u64) {
+        self.nonce = nonce;
+    }
+
+    /// 所有验证者的记录的默克尔树根，以及自己（第 `index` 个验证者）的记录在其中的证明
+    pub fn set_transcript_root(&mut self, index: usize, root: MerkleTreeVerifier, proof: Vec<u8>) {
+        self.index = index;
+        self.transcript_root = Some(root);
+        self.transcript_proof = proof;
+    }
+
+    /// 查询的下标，进行工作量证明时先检查自己的记录包含在 `transcript_root` 中，再检查 nonce，
+    /// 最后由 `transcript_root`、折叠的挑战值和 nonce 导出下标，记录或 nonce 无效时返回 None
+    fn query_list(&self) -> Option<Vec<usize>> {
+        if self.oracle.grinding_bits == 0 {
+            return Some(self.oracle.query_list.clone());
+        }
+        let root = self.transcript_root.as_ref()?;
+        let leaf = view_leaf(&self.function_root, &self.folding_root, self.final_value.as_ref()?);
+        if root.leave_number == 0
+            || !root.verify(self.transcript_proof.clone(), &vec![self.index % root.leave_number], &vec![leaf])
+        {
+            return None;
+        }
+        let seed = pow::transcript_seed(&root.merkle_root, &self.oracle.folding_challenges);
+        if !pow::verify(&seed, self.nonce, self.oracle.grinding_bits) {
+            return None;
+        }
+        Some(pow::query_indices(&seed, self.nonce, self.oracle.query_list.len()))
+    }
+
+    /// 每一轮查询的下标，nonce 无效时返回 None
+    fn leaf_indices(&self) -> Option<Vec<Vec<usize>>> {
+        let mut leaf_indices = self.query_list()?;
+        let mut res = vec![];
         for i in 0..self.total_round {
             let domain_size = self.interpolate_cosets[i].size();
             leaf_indices = leaf_indices
@@ -91,40 +231,79 @@ impl<T: Field> One2ManyVerifier<T> {
                 .collect();
             leaf_indices.sort();
             leaf_indices.dedup();
+            res.push(leaf_indices.clone());
+        }
+        Some(res)
+    }
 
-            if i == 0 {
-                function_proofs[i].verify_merkle_tree(&leaf_indices, &self.function_root[0]);
-            } else {
-                folding_proofs[i - 1].verify_merkle_tree(&leaf_indices, &self.folding_root[i - 1]);
-            }
+    /// 只检查查询结果与默克尔树根一致，不检查折叠关系
+    /// 通过检查后查询结果中包含验证所需的全部取值
+    pub fn verify_merkle_proofs(
+        &self,
+        folding_proofs: &[QueryResult<T>],
+        function_proofs: &[QueryResult<T>],
+    ) -> bool {
+        if function_proofs.len() != self.total_round
+            || folding_proofs.len() + 1 != self.total_round
+            || self.function_root.len() != self.total_round
+            || self.folding_root.len() + 1 != self.total_round
+        {
+            return false;
+        }
+        let leaf_indices = match self.leaf_indices() {
+            Some(leaf_indices) => leaf_indices,
+            None => return false,
+        };
+        (0..self.total_round).all(|i| {
+            function_proofs[i].verify_merkle_tree(&leaf_indices[i], &self.function_root[i])
+                && (i == 0
+                    || folding_proofs[i - 1].verify_merkle_tree(&leaf_indices[i], &self.folding_root[i - 1]))
+        })
+    }
 
+    /// 验证查询结果，Dealer 的证明或参与方的份额不正确时返回 false
+    pub fn verify_with_extra_folding(
+        &self,
+        folding_proofs: &Vec<QueryResult<T>>,
+        function_proofs: &Vec<QueryResult<T>>,
+        extra_folding_param: &Vec<T>,
+        extra_final_poly: &MultilinearPolynomial<T>,
+    ) -> bool {
+        if !self.verify_merkle_proofs(folding_proofs, function_proofs) {
+            return false;
+        }
+        let final_value = match &self.final_value {
+            Some(value) if value.degree() <= 1 << (self.log_max_degree - self.total_round) => value,
+            _ => return false,
+        };
+        let leaf_indices = self.leaf_indices().unwrap();
+        // 批量证明时第 0 轮的取值为各个多项式取值的线性组合
+        let function_values: Vec<_> = function_proofs
+            .iter()
+            .map(|x| x.values(self.oracle.rlc))
+            .collect();
+        for i in 0..self.total_round {
+            let domain_size = self.interpolate_cosets[i].size();
             let challenge = self.oracle.folding_challenges[i];
             let get_folding_value = if i == 0 {
-                &function_proofs[i].proof_values
+                &function_values[i]
             } else {
                 &folding_proofs[i - 1].proof_values
             };
 
-            let function_values = if i != 0 {
-                let function_query_result = &function_proofs[i];
-                function_query_result.verify_merkle_tree(&leaf_indices, &self.function_root[i]);
-                Some(&function_query_result.proof_values)
-            } else {
-                None
-            };
-            for j in &leaf_indices {
+            for j in &leaf_indices[i] {
                 let x = get_folding_value[j];
                 let nx = get_folding_value[&(j + domain_size / 2)];
                 let v =
                     x + nx + challenge * (x - nx) * self.interpolate_cosets[i].element_inv_at(*j);
                 if i != 0 {
-                    let x = function_values.as_ref().unwrap()[j];
-                    let nx = function_values.as_ref().unwrap()[&(j + domain_size / 2)];
+                    let x = function_values[i][j];
+                    let nx = function_values[i][&(j + domain_size / 2)];
                     let v = (v * challenge + (x + nx)) * challenge
                         + (x - nx) * self.interpolate_cosets[i].element_inv_at(*j);
                     if i == self.total_round - 1 {
                         let x = self.interpolate_cosets[i + 1].element_at(*j);
-                        if v != self.final_value.as_ref().unwrap().evaluation_at(x) {
+                        if v != final_value.evaluation_at(x) {
                             return false;
                         }
                     } else if v != folding_proofs[i].proof_values[j] {
@@ -135,19 +314,23 @@ impl<T: Field> One2ManyVerifier<T> {
                         return false;
                     }
                 }
-                let x = function_proofs[i].proof_values[j];
-                let nx = function_proofs[i].proof_values[&(j + domain_size / 2)];
+                let x = function_values[i][j];
+                let nx = function_values[i][&(j + domain_size / 2)];
                 let v = x
                     + nx
                     + extra_folding_param[i]
                         * (x - nx)
                         * self.interpolate_cosets[i].element_inv_at(*j);
                 if i < self.total_round - 1 {
-                    assert_eq!(v, function_proofs[i + 1].proof_values[j] * T::from_int(2));
+                    if v != function_values[i + 1][j] * T::from_int(2) {
+                        return false;
+                    }
                 } else {
                     let x = self.interpolate_cosets[i + 1].element_at(*j);
                     let poly_v = extra_final_poly.evaluate_as_polynomial(x);
-                    assert_eq!(v, poly_v * T::from_int(2));
+                    if v != poly_v * T::from_int(2) {
+                        return false;
+                    }
                 }
             }
         }
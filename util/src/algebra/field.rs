@@ -19,6 +19,8 @@ pub trait Field:
     const LOG_ORDER: u64;
     const ROOT_OF_UNITY: Self;
     const INVERSE_2: Self;
+    /// 域的大小的对数（向下取整），用于估计低次测试的安全性
+    const FIELD_BITS: usize;
 
     fn from_int(x: u64) -> Self;
     fn random_element() -> Self;
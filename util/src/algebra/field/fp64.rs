@@ -111,6 +111,7 @@ impl Field for Fp64 {
     const INVERSE_2: Self = Fp64 {
         real: 9223372034707292161,
     };
+    const FIELD_BITS: usize = 63;
 
     fn from_int(x: u64) -> Fp64 {
         if x >= MOD {
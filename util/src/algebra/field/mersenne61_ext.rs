@@ -14,9 +14,21 @@ pub struct Mersenne61Ext {
 const MOD: u64 = (1u64 << 61) - 1;
 
 impl Mersenne61Ext {
+    /// 由实部和虚部构造元素，两者都对 2^61 - 1 取模
+    pub fn new(real: u64, image: u64) -> Self {
+        Mersenne61Ext {
+            real: real % MOD,
+            image: image % MOD,
+        }
+    }
+
     pub fn get_real(&self) -> u64 {
         self.real
     }
+
+    pub fn get_image(&self) -> u64 {
+        self.image
+    }
 }
 
 #[inline]
@@ -136,6 +148,7 @@ impl Field for Mersenne61Ext {
         real: 1152921504606846976,
         image: 0,
     };
+    const FIELD_BITS: usize = 121;
 
     #[inline]
     fn from_int(x: u64) -> Self {
@@ -0,0 +1,302 @@
+use super::field::mersenne61_ext::Mersenne61Ext;
+use super::field::Field;
+use rand::Rng;
+
+/// 模数 q = 2 ^ 61 - 1，格上的运算在 Mersenne61Ext 的实部上进行
+const MODULUS: u64 = (1u64 << 61) - 1;
+
+/// 门限解密时淹没噪声的取值范围 [-2 ^ FLOODING_BITS, 2 ^ FLOODING_BITS]，
+/// 远大于解密噪声，而至多 2 ^ 17 个节点的淹没噪声之和仍小于 q / 4
+pub const FLOODING_BITS: u32 = 40;
+
+/// 格上密钥的参数：环 R_q = Z_q[X] / (X ^ degree + 1) 上秩为 rank 的模，q = 2 ^ 61 - 1
+/// 私钥 s 和噪声 e 的每个系数服从参数为 eta 的中心二项分布，取值在 [-eta, eta] 之间
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LatticeParams {
+    degree: usize,
+    rank: usize,
+    eta: usize,
+}
+
+impl LatticeParams {
+    pub fn new(degree: usize, rank: usize, eta: usize) -> Self {
+        if !degree.is_power_of_two() || rank == 0 || eta == 0 {
+            panic!("degree must be a power of 2, rank and eta must be positive");
+        }
+        LatticeParams { degree, rank, eta }
+    }
+
+    /// q 约为 2 ^ 61 时，按照同态加密标准，128 比特安全需要维数 rank * degree 达到 4096
+    pub fn recommended() -> Self {
+        Self::new(1024, 4, 2)
+    }
+
+    pub fn degree(&self) -> usize {
+        self.degree
+    }
+
+    pub fn rank(&self) -> usize {
+        self.rank
+    }
+
+    pub fn eta(&self) -> usize {
+        self.eta
+    }
+
+    /// 私钥、噪声和公钥的系数个数 rank * degree
+    pub fn dimension(&self) -> usize {
+        self.rank * self.degree
+    }
+
+    /// 一个私钥或噪声向量：每个系数为 eta 个随机比特之和减去另外 eta 个随机比特之和
+    pub fn sample(&self) -> Vec<Mersenne61Ext> {
+        self.from_bits(&self.sample_bits())
+    }
+
+    /// 一个私钥或噪声向量的中心二项分布的比特，每个系数依次为 eta 个加上的比特和 eta 个减去的比特
+    pub fn sample_bits(&self) -> Vec<Mersenne61Ext> {
+        let mut rng = rand::thread_rng();
+        (0..2 * self.eta * self.dimension())
+            .map(|_| Mersenne61Ext::from_int(rng.gen::<bool>() as u64))
+            .collect()
+    }
+
+    /// 由 `sample_bits` 的比特得到系数，运算是线性的，因此可以直接作用在比特的份额上
+    /// 每个比特确实是 0 或 1 时系数的绝对值不超过 eta
+    pub fn from_bits(&self, bits: &[Mersenne61Ext]) -> Vec<Mersenne61Ext> {
+        let zero = Mersenne61Ext::from_int(0);
+        bits.chunks(2 * self.eta)
+            .map(|c| {
+                let (a, b) = c.split_at(self.eta);
+                a.iter().fold(zero, |s, x| s + *x) - b.iter().fold(zero, |s, x| s + *x)
+            })
+            .collect()
+    }
+
+    /// `count` 个淹没噪声，在 [-2 ^ FLOODING_BITS, 2 ^ FLOODING_BITS] 中均匀选取
+    pub fn sample_flooding(&self, count: usize) -> Vec<Mersenne61Ext> {
+        let mut rng = rand::thread_rng();
+        let bound = 1i64 << FLOODING_BITS;
+        (0..count).map(|_| embed(rng.gen_range(-bound..=bound))).collect()
+    }
+}
+
+/// 公钥加密的密文 (u, v)，u = A^T·r + e1 为 rank 个环元素，v = b^T·r + e2 + ⌊q / 2⌋·m 为一个环元素
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ciphertext {
+    pub u: Vec<Mersenne61Ext>,
+    pub v: Vec<Mersenne61Ext>,
+}
+
+/// 有符号整数在 Z_q 中的表示
+pub fn embed(x: i64) -> Mersenne61Ext {
+    let v = Mersenne61Ext::from_int(x.unsigned_abs() % MODULUS);
+    if x < 0 {
+        -v
+    } else {
+        v
+    }
+}
+
+/// `embed` 的逆运算：Z_q 中的元素在 (-q / 2, q / 2] 中的代表元，元素不在 Z_q 中时返回 None
+pub fn centered(x: Mersenne61Ext) -> Option<i64> {
+    if x.get_image() != 0 {
+        return None;
+    }
+    let v = x.get_real();
+    if v > MODULUS / 2 {
+        Some(-((MODULUS - v) as i64))
+    } else {
+        Some(v as i64)
+    }
+}
+
+/// 公开矩阵 A 由公开的种子展开得到，所有节点得到相同的矩阵，没有人知道它的陷门
+#[derive(Debug, Clone)]
+pub struct Lattice {
+    params: LatticeParams,
+    /// rank × rank 个环元素，每个环元素为 degree 个系数
+    matrix: Vec<Vec<Vec<Mersenne61Ext>>>,
+}
+
+impl Lattice {
+    pub fn new(params: LatticeParams, seed: &[u8]) -> Self {
+        let mut reader = blake3::Hasher::new().update(seed).finalize_xof();
+        let mut sample = || uniform(&mut reader);
+        let matrix = (0..params.rank)
+            .map(|_| {
+                (0..params.rank)
+                    .map(|_| (0..params.degree).map(|_| sample()).collect())
+                    .collect()
+            })
+            .collect();
+        Lattice { params, matrix }
+    }
+
+    pub fn params(&self) -> &LatticeParams {
+        &self.params
+    }
+
+    /// 由 `reader` 展开 rank 个系数均匀分布的环元素，所有节点由相同的输入得到相同的向量
+    pub fn uniform_vector(&self, reader: &mut blake3::OutputReader) -> Vec<Mersenne61Ext> {
+        (0..self.params.dimension()).map(|_| uniform(reader)).collect()
+    }
+
+    /// 计算 A·s + e，s 和 e 为依次排列的 rank 个环元素的系数
+    /// 运算是线性的，因此可以直接作用在 s 和 e 的份额上，得到公钥的份额
+    pub fn public_key(&self, s: &[Mersenne61Ext], e: &[Mersenne61Ext]) -> Vec<Mersenne61Ext> {
+        let d = self.params.degree;
+        assert_eq!(s.len(), self.params.dimension());
+        assert_eq!(e.len(), self.params.dimension());
+        let mut res = e.to_vec();
+        for (i, row) in self.matrix.iter().enumerate() {
+            for (a, s) in row.iter().zip(s.chunks(d)) {
+                for (r, v) in res[i * d..(i + 1) * d].iter_mut().zip(multiply(a, s)) {
+                    *r += v;
+                }
+            }
+        }
+        res
+    }
+
+    /// 用公钥 b = A·s + e 加密 degree 个比特，r、e1 和 e2 都服从中心二项分布
+    pub fn encrypt(&self, public_key: &[Mersenne61Ext], message: &[bool]) -> Ciphertext {
+        let d = self.params.degree;
+        assert_eq!(public_key.len(), self.params.dimension());
+        assert_eq!(message.len(), d);
+        let r = self.params.sample();
+        let mut u = self.params.sample();
+        for (i, row) in self.matrix.iter().enumerate() {
+            for (a, u) in row.iter().zip(u.chunks_mut(d)) {
+                for (u, v) in u.iter_mut().zip(multiply(a, &r[i * d..(i + 1) * d])) {
+                    *u += v;
+                }
+            }
+        }
+        let half = Mersenne61Ext::from_int(MODULUS / 2);
+        let mut v = self.inner_product(public_key, &r);
+        for ((v, e), m) in v.iter_mut().zip(self.params.sample()).zip(message) {
+            *v += e;
+            if *m {
+                *v += half;
+            }
+        }
+        Ciphertext { u, v }
+    }
+
+    /// 两个由 rank 个环元素组成的向量的内积，解密时为 s^T·u，对 s 是线性的
+    pub fn inner_product(&self, a: &[Mersenne61Ext], b: &[Mersenne61Ext]) -> Vec<Mersenne61Ext> {
+        let d = self.params.degree;
+        let mut res = vec![Mersenne61Ext::from_int(0); d];
+        for (a, b) in a.chunks(d).zip(b.chunks(d)) {
+            for (r, v) in res.iter_mut().zip(multiply(a, b)) {
+                *r += v;
+            }
+        }
+        res
+    }
+
+    /// 由 `w` ≈ s^T·u 还原明文：v - w 的系数接近 q / 2 时为 1，接近 0 时为 0
+    pub fn decode(&self, ciphertext: &Ciphertext, w: &[Mersenne61Ext]) -> Vec<bool> {
+        ciphertext
+            .v
+            .iter()
+            .zip(w)
+            .map(|(v, w)| centered(*v - *w).is_some_and(|x| x.unsigned_abs() > MODULUS / 4))
+            .collect()
+    }
+
+    pub fn decrypt(&self, s: &[Mersenne61Ext], ciphertext: &Ciphertext) -> Vec<bool> {
+        self.decode(ciphertext, &self.inner_product(s, &ciphertext.u))
+    }
+}
+
+/// 由哈希的输出拒绝采样得到 Z_q 中均匀分布的元素
+fn uniform(reader: &mut blake3::OutputReader) -> Mersenne61Ext {
+    loop {
+        let mut bytes = [0u8; 8];
+        reader.fill(&mut bytes);
+        let v = u64::from_le_bytes(bytes) & MODULUS;
+        if v != MODULUS {
+            return Mersenne61Ext::from_int(v);
+        }
+    }
+}
+
+/// 环 Z_q[X] / (X ^ d + 1) 中的乘法
+fn multiply(a: &[Mersenne61Ext], b: &[Mersenne61Ext]) -> Vec<Mersenne61Ext> {
+    let d = a.len();
+    let mut res = vec![Mersenne61Ext::from_int(0); d];
+    for (i, x) in a.iter().enumerate() {
+        if x.is_zero() {
+            continue;
+        }
+        for (j, y) in b.iter().enumerate() {
+            if i + j < d {
+                res[i + j] += *x * *y;
+            } else {
+                res[i + j - d] -= *x * *y;
+            }
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn negacyclic() {
+        // X ^ 3 · X = X ^ 4 = -1，(1 + X) · (1 - X ^ 3) = 1 + X - X ^ 3 + 1
+        let one = embed(1);
+        let zero = embed(0);
+        assert_eq!(multiply(&[zero, zero, zero, one], &[zero, one, zero, zero]), vec![embed(-1), zero, zero, zero]);
+        assert_eq!(multiply(&[one, one, zero, zero], &[one, zero, zero, embed(-1)]), vec![embed(2), one, zero, embed(-1)]);
+    }
+
+    #[test]
+    fn public_key() {
+        let params = LatticeParams::new(16, 2, 2);
+        let s = params.sample();
+        let e = params.sample();
+        assert!(s.iter().chain(&e).all(|x| centered(*x).unwrap().abs() <= 2));
+        assert_eq!(centered(embed(-5)), Some(-5));
+        let bits = params.sample_bits();
+        assert_eq!(bits.len(), 4 * params.dimension());
+        assert!(bits.iter().all(|b| b.is_zero() || *b == embed(1)));
+        let one = [embed(1), embed(1), embed(0), embed(1)];
+        assert_eq!(params.from_bits(&one), vec![embed(1)]);
+
+        // 相同的种子得到相同的矩阵，公钥对 (s, e) 是线性的
+        let lattice = Lattice::new(params, b"seed");
+        assert_eq!(lattice.matrix, Lattice::new(params, b"seed").matrix);
+        assert_ne!(lattice.matrix, Lattice::new(params, b"other").matrix);
+        let s2 = params.sample();
+        let e2 = params.sample();
+        let sum = |a: &[Mersenne61Ext], b: &[Mersenne61Ext]| -> Vec<_> { a.iter().zip(b).map(|(x, y)| *x + *y).collect() };
+        assert_eq!(
+            lattice.public_key(&sum(&s, &s2), &sum(&e, &e2)),
+            sum(&lattice.public_key(&s, &e), &lattice.public_key(&s2, &e2)),
+        );
+    }
+
+    #[test]
+    fn encrypt() {
+        let params = LatticeParams::new(16, 2, 2);
+        let lattice = Lattice::new(params, b"seed");
+        let s = params.sample();
+        let b = lattice.public_key(&s, &params.sample());
+        let message: Vec<bool> = (0..16).map(|i| i % 3 == 0).collect();
+        let ciphertext = lattice.encrypt(&b, &message);
+        assert_eq!(lattice.decrypt(&s, &ciphertext), message);
+
+        // 加上淹没噪声之后仍然能够正确解密
+        let mut w = lattice.inner_product(&s, &ciphertext.u);
+        for (w, f) in w.iter_mut().zip(params.sample_flooding(16)) {
+            *w += f;
+        }
+        assert_eq!(lattice.decode(&ciphertext, &w), message);
+        assert_ne!(lattice.decrypt(&params.sample(), &ciphertext), message);
+    }
+}
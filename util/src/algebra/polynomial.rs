@@ -1,5 +1,6 @@
 use super::coset::Coset;
 use super::field::Field;
+use super::reed_solomon::berlekamp_welch;
 
 #[derive(Debug, Clone)]
 pub struct Polynomial<T: Field> {
@@ -9,7 +10,7 @@ pub struct Polynomial<T: Field> {
 impl<T: Field> Polynomial<T> {
     pub fn new(mut coefficients: Vec<T>) -> Polynomial<T> {
         let zero = T::from_int(0);
-        while *coefficients.last().unwrap() == zero {
+        while coefficients.last() == Some(&zero) {
             coefficients.pop();
         }
         Polynomial { coefficients }
@@ -63,6 +64,110 @@ impl<T: Field> Polynomial<T> {
         coeff.reverse();
         Polynomial::new(coeff)
     }
+
+    /// 拉格朗日插值：计算经过点 `(xs[i], ys[i])` 的次数小于 `xs.len()` 的多项式在 `point` 处的值
+    /// `xs` 中的元素必须互不相同
+    pub fn lagrange_evaluate(xs: &[T], ys: &[T], point: T) -> T {
+        assert_eq!(xs.len(), ys.len());
+        let mut res = T::from_int(0);
+        for (y, c) in ys.iter().zip(Self::lagrange_coefficients(xs, point)) {
+            res += *y * c;
+        }
+        res
+    }
+
+    /// 经过点 `(xs[i], ys[i])` 的次数小于 `xs.len()` 的多项式，`xs` 中的元素必须互不相同
+    pub fn interpolate(xs: &[T], ys: &[T]) -> Polynomial<T> {
+        assert_eq!(xs.len(), ys.len());
+        let mut res = vec![T::from_int(0); xs.len()];
+        for i in 0..xs.len() {
+            // 基多项式 Π_{j != i} (x - xs[j]) / (xs[i] - xs[j]) 的系数
+            let mut basis = vec![T::from_int(1)];
+            let mut denominator = T::from_int(1);
+            for j in (0..xs.len()).filter(|j| *j != i) {
+                let mut next = vec![T::from_int(0); basis.len() + 1];
+                for (k, c) in basis.iter().enumerate() {
+                    next[k + 1] += *c;
+                    next[k] -= *c * xs[j];
+                }
+                basis = next;
+                denominator *= xs[i] - xs[j];
+            }
+            let scale = ys[i] * denominator.inverse();
+            for (r, c) in res.iter_mut().zip(basis) {
+                *r += c * scale;
+            }
+        }
+        Polynomial::new(res)
+    }
+
+    /// 拉格朗日系数：第 i 项为以 `xs` 为插值点的第 i 个基多项式在 `point` 处的值
+    /// 多项式在 `point` 处的值为这些系数与它在 `xs` 上的取值的内积
+    pub fn lagrange_coefficients(xs: &[T], point: T) -> Vec<T> {
+        (0..xs.len())
+            .map(|i| {
+                let mut numerator = T::from_int(1);
+                let mut denominator = T::from_int(1);
+                for j in 0..xs.len() {
+                    if i != j {
+                        numerator *= point - xs[j];
+                        denominator *= xs[i] - xs[j];
+                    }
+                }
+                numerator * denominator.inverse()
+            })
+            .collect()
+    }
+
+    /// 由份额 `[x, y]` 重构 t 次多项式在 0 处的值，份额必须来自不同的参与方
+    /// 以 Berlekamp-Welch 译码纠正至多 (份额数 - t - 1) / 2 个错误，
+    /// 存在不一致的份额时返回 `ReconstructError::Inconsistent`，其中为错误的份额在 `shares` 中的下标
+    pub fn reconstruct(shares: &[Vec<T>], t: usize) -> Result<T, ReconstructError> {
+        Self::reconstruct_at(shares, t, T::from_int(0))
+    }
+
+    /// 打包秘密分享：重构 t 次多项式在 `points` 中每个点处的值
+    pub fn reconstruct_packed(shares: &[Vec<T>], t: usize, points: &[T]) -> Result<Vec<T>, ReconstructError> {
+        let polynomial = Self::decode(shares, t)?;
+        Ok(points.iter().map(|p| polynomial.evaluation_at(*p)).collect())
+    }
+
+    /// 与 `reconstruct` 相同，但计算 t 次多项式在 `point` 处的值
+    pub fn reconstruct_at(shares: &[Vec<T>], t: usize, point: T) -> Result<T, ReconstructError> {
+        Ok(Self::decode(shares, t)?.evaluation_at(point))
+    }
+
+    /// 由份额译码 t 次多项式，所有份额都与它一致时返回该多项式
+    fn decode(shares: &[Vec<T>], t: usize) -> Result<Polynomial<T>, ReconstructError> {
+        if shares.len() <= t {
+            return Err(ReconstructError::NotEnoughShares);
+        }
+        let duplicates: Vec<usize> = (0..shares.len())
+            .filter(|i| shares[..*i].iter().any(|s| s[0] == shares[*i][0]))
+            .collect();
+        if !duplicates.is_empty() {
+            return Err(ReconstructError::DuplicatePoints(duplicates));
+        }
+        let points: Vec<(T, T)> = shares.iter().map(|s| (s[0], s[1])).collect();
+        match berlekamp_welch(&points, t, (shares.len() - t - 1) / 2) {
+            Some((polynomial, errors)) if errors.is_empty() => Ok(polynomial),
+            Some((_, errors)) => Err(ReconstructError::Inconsistent(errors)),
+            None => Err(ReconstructError::TooManyErrors),
+        }
+    }
+}
+
+/// 由份额重构秘密失败的原因
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReconstructError {
+    /// 份额少于 t + 1 个
+    NotEnoughShares,
+    /// 这些下标的份额与之前的份额位于同一个求值点
+    DuplicatePoints(Vec<usize>),
+    /// 纠错后与多项式不一致的份额的下标
+    Inconsistent(Vec<usize>),
+    /// 错误的份额过多，无法确定多项式
+    TooManyErrors,
 }
 
 #[derive(Debug, Clone)]
@@ -126,6 +231,15 @@ impl<T: Field> MultilinearPolynomial<T> {
         }
     }
 
+    /// 随机多线性多项式，但在 `point` 处的取值为 `value`
+    /// 常数项的系数在任意点处的单项式都为 1，因此只需调整常数项
+    pub fn random_with_value(variable_num: usize, point: &Vec<T>, value: T) -> Self {
+        let mut res = Self::random_polynomial(variable_num);
+        let v = res.evaluate(point);
+        res.coefficients[0] += value - v;
+        res
+    }
+
     /// 计算多项式在给定点的值
     pub fn evaluate(&self, point: &Vec<T>) -> T {
         let len = self.coefficients.len();
@@ -153,6 +267,29 @@ impl<T: Field> MultilinearPolynomial<T> {
     pub fn variable_num(&self) -> usize {
         self.coefficients.len().ilog2() as usize
     }
+
+    /// 在第 `position` 个变量之前插入掩码变量 u_0, ..., u_{k-1}，k 为 `points` 的长度
+    /// 结果为 F + Σ (u_j - c_j) R_j，其中 c_j 为 `points[j]`，R_j 为不含 u_j 的随机多线性多项式
+    /// 因此在 u_j = c_j 处的取值与原多项式相同
+    pub fn blind(&self, position: usize, points: &[T]) -> Self {
+        let k = points.len();
+        let low = (1 << position) - 1;
+        let mut coefficients = vec![T::from_int(0); self.coefficients.len() << k];
+        for (i, c) in self.coefficients.iter().enumerate() {
+            coefficients[(i & low) | ((i & !low) << k)] = *c;
+        }
+        for (j, point) in points.iter().enumerate() {
+            let bit = 1 << (position + j);
+            for i in 0..coefficients.len() {
+                if i & bit == 0 {
+                    let r = T::random_element();
+                    coefficients[i | bit] += r;
+                    coefficients[i] -= *point * r;
+                }
+            }
+        }
+        MultilinearPolynomial { coefficients }
+    }
 }
 
 impl<T: Field> MultilinearPolynomial<T> {
@@ -223,4 +360,75 @@ mod test {
         let v = a + b + z * (a - b) * beta.inverse();
         assert_eq!(v * Mersenne61Ext::from_int(2).inverse(), c);
     }
+
+    #[test]
+    fn lagrange() {
+        let poly = Polynomial::random_polynomial(5);
+        let xs: Vec<Mersenne61Ext> = (0..5).map(|_| Mersenne61Ext::random_element()).collect();
+        let ys: Vec<Mersenne61Ext> = xs.iter().map(|x| poly.evaluation_at(*x)).collect();
+        let point = Mersenne61Ext::random_element();
+        assert_eq!(
+            Polynomial::lagrange_evaluate(&xs, &ys, point),
+            poly.evaluation_at(point)
+        );
+        assert_eq!(
+            Polynomial::lagrange_evaluate(&xs, &ys, Mersenne61Ext::from_int(0)),
+            poly.coefficients()[0]
+        );
+        let coefficients = Polynomial::lagrange_coefficients(&xs, point);
+        let mut sum = Mersenne61Ext::from_int(0);
+        for (c, y) in coefficients.iter().zip(&ys) {
+            sum += *c * *y;
+        }
+        assert_eq!(sum, poly.evaluation_at(point));
+        assert_eq!(Polynomial::interpolate(&xs, &ys).coefficients(), poly.coefficients());
+    }
+
+    #[test]
+    fn reconstruct() {
+        let t = 3;
+        let poly: Polynomial<Mersenne61Ext> = Polynomial::random_polynomial(t + 1);
+        let mut shares: Vec<Vec<Mersenne61Ext>> = (0..10)
+            .map(|_| {
+                let x = Mersenne61Ext::random_element();
+                vec![x, poly.evaluation_at(x)]
+            })
+            .collect();
+        assert_eq!(Polynomial::reconstruct(&shares, t), Ok(poly.coefficients()[0]));
+        assert_eq!(Polynomial::reconstruct(&shares[5..], t), Ok(poly.coefficients()[0]));
+
+        // 篡改的份额被报告出来，包括参与插值的前 t + 1 个份额
+        shares[1][1] += Mersenne61Ext::from_int(1);
+        shares[7][1] += Mersenne61Ext::from_int(1);
+        assert_eq!(Polynomial::reconstruct(&shares, t), Err(ReconstructError::Inconsistent(vec![1, 7])));
+        assert_eq!(Polynomial::reconstruct(&shares[2..], t), Err(ReconstructError::Inconsistent(vec![5])));
+
+        // 错误不在开头时同样报告正确的下标
+        shares[1][1] -= Mersenne61Ext::from_int(1);
+        shares[3][1] += Mersenne61Ext::from_int(1);
+        assert_eq!(Polynomial::reconstruct(&shares, t), Err(ReconstructError::Inconsistent(vec![3, 7])));
+
+        // 错误过多、份额不足或求值点重复时返回错误而不是 panic
+        assert_eq!(Polynomial::reconstruct(&shares[3..], t), Err(ReconstructError::TooManyErrors));
+        assert_eq!(Polynomial::reconstruct(&shares[..t], t), Err(ReconstructError::NotEnoughShares));
+        let duplicated = [shares[4..].to_vec(), vec![shares[5].clone()]].concat();
+        assert_eq!(Polynomial::reconstruct(&duplicated, t), Err(ReconstructError::DuplicatePoints(vec![6])));
+    }
+
+    #[test]
+    fn blind() {
+        let poly = MultilinearPolynomial::random_polynomial(5);
+        let points: Vec<Mersenne61Ext> = (0..3).map(|_| Mersenne61Ext::random_element()).collect();
+        let blinded = poly.blind(2, &points);
+        assert_eq!(blinded.variable_num(), 8);
+
+        // 掩码变量取 c_j 时与原多项式相同，取其他值时被随机多项式掩盖
+        let point: Vec<Mersenne61Ext> = (0..5).map(|_| Mersenne61Ext::random_element()).collect();
+        let mut blinded_point = point[..2].to_vec();
+        blinded_point.extend(&points);
+        blinded_point.extend(&point[2..]);
+        assert_eq!(blinded.evaluate(&blinded_point), poly.evaluate(&point));
+        blinded_point[3] = Mersenne61Ext::random_element();
+        assert_ne!(blinded.evaluate(&blinded_point), poly.evaluate(&point));
+    }
 }
@@ -0,0 +1,245 @@
+use super::{coset::Coset, field::Field, polynomial::Polynomial};
+
+/// 译码的结果，`errors` 为与译码得到的多项式不一致的求值点在求值域中的下标
+#[derive(Debug, Clone)]
+pub struct Decoded<T: Field> {
+    pub polynomial: Polynomial<T>,
+    pub errors: Vec<usize>,
+}
+
+/// 求值域为余元 `domain`、多项式次数不超过 `degree` 的 Reed-Solomon 码
+#[derive(Debug, Clone)]
+pub struct ReedSolomon<T: Field> {
+    domain: Coset<T>,
+    degree: usize,
+}
+
+impl<T: Field> ReedSolomon<T> {
+    pub fn new(domain: &Coset<T>, degree: usize) -> Self {
+        assert!(degree < domain.size());
+        ReedSolomon {
+            domain: domain.clone(),
+            degree,
+        }
+    }
+
+    pub fn degree(&self) -> usize {
+        self.degree
+    }
+
+    /// 多项式在求值域上的全部取值
+    pub fn encode(&self, polynomial: &Polynomial<T>) -> Vec<T> {
+        assert!(polynomial.degree() <= self.degree);
+        polynomial.evaluation_over_coset(&self.domain)
+    }
+
+    /// Berlekamp-Welch 译码，`received` 为若干个 `(下标, 取值)`，至多纠正 `max_errors` 个错误
+    /// 要求收到的取值个数不少于 degree + 1 + 2 * max_errors，错误过多时返回 None
+    pub fn decode(&self, received: &[(usize, T)], max_errors: usize) -> Option<Decoded<T>> {
+        if received.len() < self.degree + 1 + 2 * max_errors {
+            panic!("not enough evaluations to correct {} errors", max_errors);
+        }
+        let points: Vec<(T, T)> = received
+            .iter()
+            .map(|(i, y)| (self.domain.element_at(*i), *y))
+            .collect();
+        let (polynomial, errors) = berlekamp_welch(&points, self.degree, max_errors)?;
+        Some(Decoded {
+            polynomial,
+            errors: errors.iter().map(|j| received[*j].0).collect(),
+        })
+    }
+}
+
+/// 在互不相同的求值点 `points = [(x, y)]` 上译码次数不超过 `degree` 的多项式，至多纠正 `max_errors` 个错误
+/// 调用者保证点数不少于 degree + 1 + 2 * max_errors。返回多项式和与它不一致的点在 `points` 中的下标，
+/// 错误过多时返回 None
+pub fn berlekamp_welch<T: Field>(
+    points: &[(T, T)],
+    degree: usize,
+    max_errors: usize,
+) -> Option<(Polynomial<T>, Vec<usize>)> {
+    let k = degree + 1;
+    let e = max_errors;
+    assert!(points.len() >= k + 2 * e);
+
+    // 未知量为 Q 的 k + e 个系数和首一的错误定位多项式 E 的 e 个低次系数
+    // 每个取值给出一个方程 Q(x) - y * E(x) = 0
+    let columns = k + 2 * e;
+    let rows: Vec<Vec<T>> = points
+        .iter()
+        .map(|(x, y)| {
+            let mut row = Vec::with_capacity(columns + 1);
+            let mut power = T::from_int(1);
+            for _ in 0..k + e {
+                row.push(power);
+                power *= *x;
+            }
+            let mut power = T::from_int(1);
+            for _ in 0..e {
+                row.push(-*y * power);
+                power *= *x;
+            }
+            row.push(*y * power);
+            row
+        })
+        .collect();
+    let solution = solve(rows, columns)?;
+
+    let q = Polynomial::new(solution[..k + e].to_vec());
+    let mut error_locator = solution[k + e..].to_vec();
+    error_locator.push(T::from_int(1));
+    let (polynomial, remainder) = divide(&q, &Polynomial::new(error_locator));
+    if !remainder.coefficients().is_empty() || polynomial.degree() > degree {
+        return None;
+    }
+    let errors: Vec<usize> = (0..points.len())
+        .filter(|j| polynomial.evaluation_at(points[*j].0) != points[*j].1)
+        .collect();
+    if errors.len() > e {
+        return None;
+    }
+    Some((polynomial, errors))
+}
+
+/// 在线纠错：逐个接收取值，收到足够多的取值后尝试译码
+/// 最多有 `max_errors` 个错误时，译码结果与至少 degree + 1 + max_errors 个取值一致才被接受，
+/// 其中至少 degree + 1 个是正确的取值，因此结果一定正确
+pub struct OnlineDecoder<T: Field> {
+    code: ReedSolomon<T>,
+    max_errors: usize,
+    received: Vec<(usize, T)>,
+}
+
+impl<T: Field> OnlineDecoder<T> {
+    pub fn new(code: ReedSolomon<T>, max_errors: usize) -> Self {
+        OnlineDecoder {
+            code,
+            max_errors,
+            received: vec![],
+        }
+    }
+
+    pub fn received(&self) -> &Vec<(usize, T)> {
+        &self.received
+    }
+
+    /// 收到求值域中第 `index` 个点上的取值，能够确定正确的多项式时返回译码结果
+    pub fn receive(&mut self, index: usize, value: T) -> Option<Decoded<T>> {
+        if self.received.iter().any(|(i, _)| *i == index) {
+            return None;
+        }
+        self.received.push((index, value));
+        let quorum = self.code.degree() + 1 + self.max_errors;
+        if self.received.len() < quorum {
+            return None;
+        }
+        // 收到 quorum + r 个取值时至多纠正 r 个错误
+        let r = std::cmp::min(self.received.len() - quorum, self.max_errors);
+        let decoded = self.code.decode(&self.received, r)?;
+        if self.received.len() - decoded.errors.len() >= quorum {
+            Some(decoded)
+        } else {
+            None
+        }
+    }
+}
+
+/// 高斯消元求线性方程组的任意一个解，每行的最后一个元素为常数项，无解时返回 None
+fn solve<T: Field>(mut rows: Vec<Vec<T>>, columns: usize) -> Option<Vec<T>> {
+    let mut pivots = vec![];
+    let mut rank = 0;
+    for column in 0..columns {
+        let pivot = match (rank..rows.len()).find(|i| !rows[*i][column].is_zero()) {
+            Some(pivot) => pivot,
+            None => continue,
+        };
+        rows.swap(rank, pivot);
+        let inv = rows[rank][column].inverse();
+        for v in rows[rank].iter_mut() {
+            *v *= inv;
+        }
+        let pivot_row = rows[rank].clone();
+        for (i, row) in rows.iter_mut().enumerate() {
+            if i != rank && !row[column].is_zero() {
+                let factor = row[column];
+                for (v, p) in row[column..].iter_mut().zip(&pivot_row[column..]) {
+                    *v -= factor * *p;
+                }
+            }
+        }
+        pivots.push(column);
+        rank += 1;
+    }
+    if rows[rank..].iter().any(|row| !row[columns].is_zero()) {
+        return None;
+    }
+    // 自由变量取 0
+    let mut solution = vec![T::from_int(0); columns];
+    for (row, column) in pivots.iter().enumerate() {
+        solution[*column] = rows[row][columns];
+    }
+    Some(solution)
+}
+
+/// 多项式带余除法，返回商和余数
+fn divide<T: Field>(numerator: &Polynomial<T>, denominator: &Polynomial<T>) -> (Polynomial<T>, Polynomial<T>) {
+    let d = denominator.coefficients();
+    let lead_inv = d.last().unwrap().inverse();
+    let mut remainder = numerator.coefficients().clone();
+    if remainder.len() < d.len() {
+        return (Polynomial::new(vec![]), Polynomial::new(remainder));
+    }
+    let mut quotient = vec![T::from_int(0); remainder.len() - d.len() + 1];
+    for i in (0..quotient.len()).rev() {
+        let c = remainder[i + d.len() - 1] * lead_inv;
+        quotient[i] = c;
+        for (j, v) in d.iter().enumerate() {
+            remainder[i + j] -= c * *v;
+        }
+    }
+    (Polynomial::new(quotient), Polynomial::new(remainder))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algebra::field::mersenne61_ext::Mersenne61Ext;
+
+    #[test]
+    fn decode() {
+        let domain = Coset::new(16, Mersenne61Ext::random_element());
+        let code = ReedSolomon::new(&domain, 4);
+        let poly = Polynomial::random_polynomial(5);
+        let codeword = code.encode(&poly);
+        let mut received: Vec<_> = (0..11).map(|i| (i, codeword[i])).collect();
+        received[2].1 += Mersenne61Ext::from_int(1);
+        received[9].1 = Mersenne61Ext::random_element();
+
+        let decoded = code.decode(&received, 3).unwrap();
+        assert_eq!(decoded.polynomial.coefficients(), poly.coefficients());
+        assert_eq!(decoded.errors, vec![2, 9]);
+
+        // 错误个数超过译码能力时失败
+        assert!(code.decode(&received[..10], 1).is_none());
+    }
+
+    #[test]
+    fn online() {
+        let domain = Coset::new(16, Mersenne61Ext::random_element());
+        let poly = Polynomial::random_polynomial(3);
+        let codeword = ReedSolomon::new(&domain, 2).encode(&poly);
+        let mut decoder = OnlineDecoder::new(ReedSolomon::new(&domain, 2), 2);
+
+        // 先收到两个错误的取值，需要再收到 5 个正确的取值才能确定多项式
+        let wrong = Mersenne61Ext::from_int(5);
+        assert!(decoder.receive(3, codeword[3] + wrong).is_none());
+        assert!(decoder.receive(7, codeword[7] + wrong).is_none());
+        for (i, v) in codeword.iter().enumerate().take(14).skip(10) {
+            assert!(decoder.receive(i, *v).is_none());
+        }
+        let decoded = decoder.receive(14, codeword[14]).unwrap();
+        assert_eq!(decoded.polynomial.evaluation_at(Mersenne61Ext::from_int(0)), poly.coefficients()[0]);
+        assert_eq!(decoded.errors, vec![3, 7]);
+    }
+}
@@ -0,0 +1,154 @@
+/// `Committee` 描述参与协议的节点集合，每个节点带有一个权重
+/// `weights[i]` 为节点 `i` 的权重，`f` 为恶意节点权重之和的上界
+/// `t` 为秘密分享的门限：权重之和超过 t 的节点可以重构秘密，而权重之和不超过 t 的节点得不到秘密的任何信息
+/// 所有节点权重为 1 时退化为传统的 n 个相同节点、至多 f 个恶意节点的情形
+#[derive(Debug, Clone)]
+pub struct Committee {
+    weights: Vec<usize>,
+    f: usize,
+    t: usize,
+}
+
+impl Committee {
+    /// 创建委员会，要求 3f + 1 <= n 且 f <= t < n - f，其中 n 为权重之和
+    pub fn new(weights: Vec<usize>, f: usize, t: usize) -> Self {
+        if weights.is_empty() || weights.contains(&0) {
+            panic!("weight of every node must be positive");
+        }
+        let total: usize = weights.iter().sum();
+        if 3 * f + 1 > total {
+            panic!("must have 3f+1 <= total weight");
+        }
+        if t < f || t + f >= total {
+            panic!("must have f <= t < n-f");
+        }
+        if t == 0 {
+            panic!("must have t >= 1");
+        }
+        Committee { weights, f, t }
+    }
+
+    /// 每个节点权重均为 1 的委员会
+    pub fn uniform(n: usize, f: usize, t: usize) -> Self {
+        Self::new(vec![1; n], f, t)
+    }
+
+    /// 节点的数量
+    pub fn n(&self) -> usize {
+        self.weights.len()
+    }
+
+    /// 恶意节点权重之和的上界
+    pub fn f(&self) -> usize {
+        self.f
+    }
+
+    /// 秘密分享的门限，秘密多项式的次数
+    pub fn t(&self) -> usize {
+        self.t
+    }
+
+    pub fn weights(&self) -> &Vec<usize> {
+        &self.weights
+    }
+
+    pub fn weight(&self, id: usize) -> usize {
+        self.weights[id]
+    }
+
+    /// 所有节点的权重之和
+    pub fn total_weight(&self) -> usize {
+        self.weights.iter().sum()
+    }
+
+    /// 节点集合 `ids` 的权重之和，重复的节点只计算一次
+    pub fn weight_of(&self, ids: &[usize]) -> usize {
+        let mut ids = ids.to_vec();
+        ids.sort();
+        ids.dedup();
+        ids.iter().map(|id| self.weights[*id]).sum()
+    }
+
+    /// 权重达到 f + 1 的集合中至少包含一个诚实节点
+    pub fn exceeds_faulty(&self, ids: &[usize]) -> bool {
+        self.weight_of(ids) > self.f
+    }
+
+    /// 权重达到 t + 1 的集合可以重构秘密
+    pub fn exceeds_threshold(&self, ids: &[usize]) -> bool {
+        self.weight_of(ids) > self.t
+    }
+
+    /// 权重达到 n - f 的集合，对应于等权情形下的 n - f 个节点
+    pub fn reaches_quorum(&self, ids: &[usize]) -> bool {
+        self.weight_of(ids) + self.f >= self.total_weight()
+    }
+
+    /// 为每个节点分配求值点的下标，权重为 w 的节点分配 w 个连续的下标
+    /// 返回值的第 i 项为节点 i 的全部下标，下标的总数为 `total_weight()`
+    pub fn allocate(&self) -> Vec<Vec<usize>> {
+        let mut res = vec![];
+        let mut start = 0;
+        for w in &self.weights {
+            res.push((start..start + w).collect());
+            start += w;
+        }
+        res
+    }
+
+    /// 在不超过 f 的前提下，从编号最小的节点开始标记为恶意节点
+    /// 返回值的第 i 项表示节点 i 是否为恶意节点，用于模拟运行
+    pub fn faulty_nodes(&self) -> Vec<bool> {
+        let mut weight = 0;
+        self.weights
+            .iter()
+            .map(|w| {
+                if weight + w <= self.f {
+                    weight += w;
+                    true
+                } else {
+                    false
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn thresholds() {
+        let committee = Committee::new(vec![1, 3, 2, 1, 3], 3, 4);
+        assert_eq!(committee.total_weight(), 10);
+        assert!(!committee.exceeds_faulty(&[0, 2]));
+        assert!(committee.exceeds_faulty(&[1, 3]));
+        assert!(!committee.exceeds_threshold(&[1, 3]));
+        assert!(committee.exceeds_threshold(&[1, 2]));
+        assert!(!committee.reaches_quorum(&[1, 4]));
+        assert!(committee.reaches_quorum(&[1, 2, 4]));
+        assert_eq!(committee.weight_of(&[1, 1, 1]), 3);
+        assert_eq!(
+            committee.faulty_nodes(),
+            vec![true, false, true, false, false]
+        );
+    }
+
+    #[test]
+    fn allocate() {
+        let committee = Committee::new(vec![2, 1, 3, 1], 2, 2);
+        let points = committee.allocate();
+        assert_eq!(points, vec![vec![0, 1], vec![2], vec![3, 4, 5], vec![6]]);
+
+        let uniform = Committee::uniform(7, 2, 2);
+        assert_eq!(uniform.faulty_nodes().iter().filter(|x| **x).count(), 2);
+        assert!(uniform.faulty_nodes()[0] && uniform.faulty_nodes()[1]);
+    }
+
+    #[test]
+    #[should_panic(expected = "must have f <= t < n-f")]
+    fn threshold_too_large() {
+        Committee::uniform(7, 2, 5);
+    }
+}
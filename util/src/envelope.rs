@@ -0,0 +1,93 @@
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::ChaCha20Poly1305;
+
+use crate::kem::{DecapsulationKey, EncapsulationKey, KemParams};
+
+/// 由 KEM 的共享密钥派生 AEAD 密钥时的 blake3 上下文
+const KEY_CONTEXT: &str = "PQC-ADKG share envelope";
+
+/// 以接收者的 ML-KEM 公钥加密的消息：封装的共享密钥派生 ChaCha20-Poly1305 的密钥，
+/// 每个信封的密钥都是新的，因此使用固定的 nonce
+/// `aad` 为公开的关联数据，例如发送者和接收者的编号以及发送者的承诺，解密时必须相同
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Envelope {
+    pub encapsulation: Vec<u8>,
+    pub body: Vec<u8>,
+}
+
+impl Envelope {
+    /// 加密 `plaintext`，接收者的公钥格式不正确时返回 None
+    pub fn seal(params: &KemParams, ek: &EncapsulationKey, aad: &[u8], plaintext: &[u8]) -> Option<Envelope> {
+        let (secret, encapsulation) = params.encapsulate(ek)?;
+        let body = cipher(&secret)
+            .encrypt(&Default::default(), Payload { msg: plaintext, aad })
+            .ok()?;
+        Some(Envelope { encapsulation, body })
+    }
+
+    /// 解密，密文被篡改、关联数据不同或者不是发给 `dk` 的接收者时返回 None
+    pub fn open(&self, params: &KemParams, dk: &DecapsulationKey, aad: &[u8]) -> Option<Vec<u8>> {
+        let secret = params.decapsulate(dk, &self.encapsulation)?;
+        cipher(&secret)
+            .decrypt(&Default::default(), Payload { msg: &self.body, aad })
+            .ok()
+    }
+
+    /// 公开封装时的随机数，用于投诉：任何人可以由它和接收者的公钥打开信封，得到同样的明文
+    /// 信封不是发给 `dk` 的接收者时返回 None
+    pub fn reveal(&self, params: &KemParams, dk: &DecapsulationKey) -> Option<[u8; 32]> {
+        params.decapsulate_message(dk, &self.encapsulation)
+    }
+
+    /// 以公开的随机数 `m` 和接收者的公钥 `ek` 重新封装得到的密文与信封中的相同，即信封确实是发给 `ek` 的
+    pub fn matches(&self, params: &KemParams, ek: &EncapsulationKey, m: &[u8; 32]) -> bool {
+        params
+            .encapsulate_with(ek, m)
+            .is_some_and(|(_, encapsulation)| encapsulation == self.encapsulation)
+    }
+
+    /// 由接收者公开的随机数 `m` 解密，要求 `matches`，因此解密出的明文确实是发送者加密给 `ek` 的明文
+    /// 随机数不一致或者信封无法解密时返回 None
+    pub fn open_revealed(&self, params: &KemParams, ek: &EncapsulationKey, m: &[u8; 32], aad: &[u8]) -> Option<Vec<u8>> {
+        let (secret, encapsulation) = params.encapsulate_with(ek, m)?;
+        if encapsulation != self.encapsulation {
+            return None;
+        }
+        cipher(&secret)
+            .decrypt(&Default::default(), Payload { msg: &self.body, aad })
+            .ok()
+    }
+}
+
+fn cipher(secret: &[u8; 32]) -> ChaCha20Poly1305 {
+    ChaCha20Poly1305::new(&blake3::derive_key(KEY_CONTEXT, secret).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seal_and_open() {
+        let params = KemParams::ML_KEM_768;
+        let (ek, dk) = params.keygen();
+        let (_, other) = params.keygen();
+        let envelope = Envelope::seal(&params, &ek, b"dealer 1 to party 3", b"share").unwrap();
+        assert_eq!(envelope.open(&params, &dk, b"dealer 1 to party 3"), Some(b"share".to_vec()));
+        assert_eq!(envelope.open(&params, &dk, b"dealer 1 to party 4"), None);
+        assert_eq!(envelope.open(&params, &other, b"dealer 1 to party 3"), None);
+
+        let mut tampered = envelope.clone();
+        tampered.body[0] ^= 1;
+        assert_eq!(tampered.open(&params, &dk, b"dealer 1 to party 3"), None);
+
+        // 接收者公开随机数后任何人都能打开信封，但不能换成别的随机数或别的接收者
+        let m = envelope.reveal(&params, &dk).unwrap();
+        assert_eq!(envelope.open_revealed(&params, &ek, &m, b"dealer 1 to party 3"), Some(b"share".to_vec()));
+        assert!(envelope.matches(&params, &ek, &m));
+        assert!(!envelope.matches(&params, &ek, &[0; 32]));
+        assert_eq!(envelope.open_revealed(&params, &ek, &[0; 32], b"dealer 1 to party 3"), None);
+        assert_eq!(envelope.open_revealed(&params, &other.encapsulation_key(), &m, b"dealer 1 to party 3"), None);
+        assert_eq!(envelope.reveal(&params, &other), None);
+    }
+}
@@ -0,0 +1,541 @@
+use rand::RngCore;
+use sha3::digest::{ExtendableOutput, Update, XofReader};
+use sha3::{Digest, Sha3_256, Sha3_512, Shake128, Shake256};
+use subtle::{Choice, ConditionallySelectable, ConstantTimeEq};
+
+/// ML-KEM 的模数 q 和多项式的次数 n
+const Q: u32 = 3329;
+const N: usize = 256;
+/// 128 ^ -1 mod q，逆 NTT 之后乘以它
+const N_INV: u32 = 3303;
+
+/// NTT 中的单位根 17 ^ BitRev7(i)
+const ZETAS: [u32; 128] = roots(0);
+/// NTT 域上乘法的参数 17 ^ (2·BitRev7(i) + 1)
+const GAMMAS: [u32; 128] = roots(1);
+
+const fn roots(odd: usize) -> [u32; 128] {
+    let mut res = [0u32; 128];
+    let mut i = 0;
+    while i < 128 {
+        let mut rev = 0;
+        let mut j = 0;
+        while j < 7 {
+            rev |= ((i >> j) & 1) << (6 - j);
+            j += 1;
+        }
+        let e = if odd == 1 { 2 * rev + 1 } else { rev };
+        let mut r = 1u32;
+        let mut j = 0;
+        while j < e {
+            r = r * 17 % Q;
+            j += 1;
+        }
+        res[i] = r;
+        i += 1;
+    }
+    res
+}
+
+type Poly = [u32; N];
+
+/// FIPS 203 中 ML-KEM 的参数：模的秩 k，私钥和噪声的中心二项分布参数 eta1、eta2，密文的压缩位数 du、dv
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KemParams {
+    k: usize,
+    eta1: usize,
+    eta2: usize,
+    du: usize,
+    dv: usize,
+}
+
+/// 解封装的中间结果：随机数 m、由 m 得到的共享密钥、隐式拒绝的密钥，以及重新加密的密文是否与收到的一致
+struct Decrypted {
+    m: [u8; 32],
+    key: [u8; 32],
+    rejected: [u8; 32],
+    valid: Choice,
+}
+
+/// 封装公钥，共 384k + 32 字节
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncapsulationKey(pub Vec<u8>);
+
+/// 解封装私钥，共 768k + 96 字节，包含封装公钥
+#[derive(Clone, PartialEq, Eq)]
+pub struct DecapsulationKey(Vec<u8>);
+
+impl KemParams {
+    pub const ML_KEM_512: KemParams = KemParams { k: 2, eta1: 3, eta2: 2, du: 10, dv: 4 };
+    pub const ML_KEM_768: KemParams = KemParams { k: 3, eta1: 2, eta2: 2, du: 10, dv: 4 };
+    pub const ML_KEM_1024: KemParams = KemParams { k: 4, eta1: 2, eta2: 2, du: 11, dv: 5 };
+
+    pub fn encapsulation_key_size(&self) -> usize {
+        384 * self.k + 32
+    }
+
+    pub fn decapsulation_key_size(&self) -> usize {
+        768 * self.k + 96
+    }
+
+    pub fn ciphertext_size(&self) -> usize {
+        32 * (self.du * self.k + self.dv)
+    }
+
+    /// 生成随机的密钥对
+    pub fn keygen(&self) -> (EncapsulationKey, DecapsulationKey) {
+        let mut rng = rand::thread_rng();
+        let (mut d, mut z) = ([0u8; 32], [0u8; 32]);
+        rng.fill_bytes(&mut d);
+        rng.fill_bytes(&mut z);
+        self.keygen_from_seed(&d, &z)
+    }
+
+    /// 由种子 d 和隐式拒绝的种子 z 确定地生成密钥对
+    pub fn keygen_from_seed(&self, d: &[u8; 32], z: &[u8; 32]) -> (EncapsulationKey, DecapsulationKey) {
+        let (ek, dk_pke) = self.pke_keygen(d);
+        let mut dk = dk_pke;
+        dk.extend(&ek);
+        dk.extend(Sha3_256::digest(&ek));
+        dk.extend(z);
+        (EncapsulationKey(ek), DecapsulationKey(dk))
+    }
+
+    /// 封装随机的共享密钥，返回共享密钥和密文，公钥的格式不正确时返回 None
+    pub fn encapsulate(&self, ek: &EncapsulationKey) -> Option<([u8; 32], Vec<u8>)> {
+        let mut m = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut m);
+        self.encapsulate_with(ek, &m)
+    }
+
+    /// 由随机数 m 确定地封装
+    pub fn encapsulate_with(&self, ek: &EncapsulationKey, m: &[u8; 32]) -> Option<([u8; 32], Vec<u8>)> {
+        if !self.check_encapsulation_key(ek) {
+            return None;
+        }
+        let mut input = m.to_vec();
+        input.extend(Sha3_256::digest(&ek.0));
+        let (key, r) = g(&input);
+        Some((key, self.pke_encrypt(&ek.0, m, &r)))
+    }
+
+    /// 解封装，密文不是由对应的公钥封装时返回由 z 和密文得到的伪随机的密钥（隐式拒绝）
+    /// 私钥或密文的长度不正确时返回 None
+    pub fn decapsulate(&self, dk: &DecapsulationKey, ciphertext: &[u8]) -> Option<[u8; 32]> {
+        let d = self.decrypt(dk, ciphertext)?;
+        // 选择密钥不依赖于秘密的分支，不泄露密文是否被拒绝
+        Some(std::array::from_fn(|i| u8::conditional_select(&d.rejected[i], &d.key[i], d.valid)))
+    }
+
+    /// 解封装并返回封装时的随机数 m，密文不是由对应的公钥封装时返回 None
+    /// 公开 m 只泄露这一个密文的共享密钥，任何人以 m 和公钥调用 `encapsulate_with` 得到同一个密文和共享密钥
+    pub fn decapsulate_message(&self, dk: &DecapsulationKey, ciphertext: &[u8]) -> Option<[u8; 32]> {
+        let d = self.decrypt(dk, ciphertext)?;
+        bool::from(d.valid).then_some(d.m)
+    }
+
+    /// 解密出随机数 m 并重新加密
+    fn decrypt(&self, dk: &DecapsulationKey, ciphertext: &[u8]) -> Option<Decrypted> {
+        let k = self.k;
+        if dk.0.len() != self.decapsulation_key_size() || ciphertext.len() != self.ciphertext_size() {
+            return None;
+        }
+        let (dk_pke, rest) = dk.0.split_at(384 * k);
+        let (ek, rest) = rest.split_at(384 * k + 32);
+        let (h, z) = rest.split_at(32);
+        if Sha3_256::digest(ek).as_slice() != h {
+            return None;
+        }
+        let m = self.pke_decrypt(dk_pke, ciphertext);
+        let mut input = m.to_vec();
+        input.extend(h);
+        let (key, r) = g(&input);
+        let mut rejected = [0u8; 32];
+        let mut hasher = Shake256::default();
+        hasher.update(z);
+        hasher.update(ciphertext);
+        hasher.finalize_xof().read(&mut rejected);
+        // 比较重新加密的密文不依赖于秘密的分支
+        let valid = self.pke_encrypt(ek, &m, &r).as_slice().ct_eq(ciphertext);
+        Some(Decrypted { m, key, rejected, valid })
+    }
+
+    /// 公钥的长度正确，且 t 的每个系数都小于 q
+    fn check_encapsulation_key(&self, ek: &EncapsulationKey) -> bool {
+        let k = self.k;
+        if ek.0.len() != self.encapsulation_key_size() {
+            return false;
+        }
+        ek.0[..384 * k]
+            .chunks(384)
+            .all(|c| byte_encode(&byte_decode(c, 12), 12) == c)
+    }
+
+    /// 公开矩阵 A 的第 i 行第 j 列由种子 rho 和 j、i 展开
+    fn matrix(&self, rho: &[u8]) -> Vec<Vec<Poly>> {
+        (0..self.k)
+            .map(|i| (0..self.k).map(|j| sample_ntt(rho, j as u8, i as u8)).collect())
+            .collect()
+    }
+
+    fn pke_keygen(&self, d: &[u8; 32]) -> (Vec<u8>, Vec<u8>) {
+        let k = self.k;
+        let mut input = d.to_vec();
+        input.push(k as u8);
+        let (rho, sigma) = g(&input);
+        let a = self.matrix(&rho);
+        let mut counter = 0u8;
+        let mut sample = |eta| {
+            let mut p = cbd(&prf(eta, &sigma, counter), eta);
+            counter += 1;
+            ntt(&mut p);
+            p
+        };
+        let s: Vec<_> = (0..k).map(|_| sample(self.eta1)).collect();
+        let e: Vec<_> = (0..k).map(|_| sample(self.eta1)).collect();
+        let mut ek = vec![];
+        for i in 0..k {
+            let mut t = e[i];
+            for j in 0..k {
+                add(&mut t, &multiply_ntts(&a[i][j], &s[j]));
+            }
+            ek.extend(byte_encode(&t, 12));
+        }
+        ek.extend(rho);
+        let dk = s.iter().flat_map(|s| byte_encode(s, 12)).collect();
+        (ek, dk)
+    }
+
+    fn pke_encrypt(&self, ek: &[u8], m: &[u8; 32], r: &[u8; 32]) -> Vec<u8> {
+        let k = self.k;
+        let t: Vec<_> = ek[..384 * k].chunks(384).map(|c| byte_decode(c, 12)).collect();
+        let a = self.matrix(&ek[384 * k..]);
+        let mut counter = 0u8;
+        let mut sample = |eta| {
+            let p = cbd(&prf(eta, r, counter), eta);
+            counter += 1;
+            p
+        };
+        let y: Vec<_> = (0..k)
+            .map(|_| {
+                let mut y = sample(self.eta1);
+                ntt(&mut y);
+                y
+            })
+            .collect();
+        let e1: Vec<_> = (0..k).map(|_| sample(self.eta2)).collect();
+        let e2 = sample(self.eta2);
+        let mut c = vec![];
+        for i in 0..k {
+            let mut u = [0u32; N];
+            for j in 0..k {
+                add(&mut u, &multiply_ntts(&a[j][i], &y[j]));
+            }
+            inverse_ntt(&mut u);
+            add(&mut u, &e1[i]);
+            c.extend(byte_encode(&compress(&u, self.du), self.du));
+        }
+        let mut v = [0u32; N];
+        for j in 0..k {
+            add(&mut v, &multiply_ntts(&t[j], &y[j]));
+        }
+        inverse_ntt(&mut v);
+        add(&mut v, &e2);
+        add(&mut v, &decompress(&byte_decode(m, 1), 1));
+        c.extend(byte_encode(&compress(&v, self.dv), self.dv));
+        c
+    }
+
+    fn pke_decrypt(&self, dk: &[u8], c: &[u8]) -> [u8; 32] {
+        let (c1, c2) = c.split_at(32 * self.du * self.k);
+        let mut w = decompress(&byte_decode(c2, self.dv), self.dv);
+        let mut product = [0u32; N];
+        for (s, u) in dk.chunks(384).zip(c1.chunks(32 * self.du)) {
+            let mut u = decompress(&byte_decode(u, self.du), self.du);
+            ntt(&mut u);
+            add(&mut product, &multiply_ntts(&byte_decode(s, 12), &u));
+        }
+        inverse_ntt(&mut product);
+        for (w, p) in w.iter_mut().zip(product) {
+            *w = (*w + Q - p) % Q;
+        }
+        byte_encode(&compress(&w, 1), 1).try_into().unwrap()
+    }
+}
+
+impl DecapsulationKey {
+    /// 私钥中包含的封装公钥
+    pub fn encapsulation_key(&self) -> EncapsulationKey {
+        let k = (self.0.len() - 96) / 768;
+        EncapsulationKey(self.0[384 * k..768 * k + 32].to_vec())
+    }
+}
+
+/// 私钥不输出内容
+impl std::fmt::Debug for DecapsulationKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "DecapsulationKey({} bytes)", self.0.len())
+    }
+}
+
+/// G = SHA3-512，输出分为两个 32 字节
+fn g(input: &[u8]) -> ([u8; 32], [u8; 32]) {
+    let out = Sha3_512::digest(input);
+    (out[..32].try_into().unwrap(), out[32..].try_into().unwrap())
+}
+
+/// PRF_eta(s, b) = SHAKE256(s || b)，输出 64·eta 字节
+fn prf(eta: usize, s: &[u8; 32], b: u8) -> Vec<u8> {
+    let mut out = vec![0u8; 64 * eta];
+    let mut hasher = Shake256::default();
+    hasher.update(s);
+    hasher.update(&[b]);
+    hasher.finalize_xof().read(&mut out);
+    out
+}
+
+/// 由 SHAKE128(rho || j || i) 拒绝采样得到 NTT 域上均匀分布的多项式
+fn sample_ntt(rho: &[u8], j: u8, i: u8) -> Poly {
+    let mut hasher = Shake128::default();
+    hasher.update(rho);
+    hasher.update(&[j, i]);
+    let mut reader = hasher.finalize_xof();
+    let mut a = [0u32; N];
+    let mut k = 0;
+    while k < N {
+        let mut b = [0u8; 3];
+        reader.read(&mut b);
+        let d1 = b[0] as u32 + 256 * (b[1] as u32 & 15);
+        let d2 = (b[1] as u32 >> 4) + 16 * b[2] as u32;
+        for d in [d1, d2] {
+            if d < Q && k < N {
+                a[k] = d;
+                k += 1;
+            }
+        }
+    }
+    a
+}
+
+/// 参数为 eta 的中心二项分布，每个系数为 eta 个比特之和减去另外 eta 个比特之和
+fn cbd(bytes: &[u8], eta: usize) -> Poly {
+    let bit = |i: usize| ((bytes[i / 8] >> (i % 8)) & 1) as u32;
+    let mut f = [0u32; N];
+    for (i, f) in f.iter_mut().enumerate() {
+        let x: u32 = (0..eta).map(|j| bit(2 * i * eta + j)).sum();
+        let y: u32 = (0..eta).map(|j| bit(2 * i * eta + eta + j)).sum();
+        *f = (x + Q - y) % Q;
+    }
+    f
+}
+
+fn ntt(f: &mut Poly) {
+    let mut i = 1;
+    let mut len = 128;
+    while len >= 2 {
+        for start in (0..N).step_by(2 * len) {
+            let zeta = ZETAS[i];
+            i += 1;
+            for j in start..start + len {
+                let t = zeta * f[j + len] % Q;
+                f[j + len] = (f[j] + Q - t) % Q;
+                f[j] = (f[j] + t) % Q;
+            }
+        }
+        len /= 2;
+    }
+}
+
+fn inverse_ntt(f: &mut Poly) {
+    let mut i = 127;
+    let mut len = 2;
+    while len <= 128 {
+        for start in (0..N).step_by(2 * len) {
+            let zeta = ZETAS[i];
+            i -= 1;
+            for j in start..start + len {
+                let t = f[j];
+                f[j] = (t + f[j + len]) % Q;
+                f[j + len] = zeta * ((f[j + len] + Q - t) % Q) % Q;
+            }
+        }
+        len *= 2;
+    }
+    for x in f.iter_mut() {
+        *x = *x * N_INV % Q;
+    }
+}
+
+/// NTT 域上的乘法：128 个模 X^2 - gamma 的一次多项式分别相乘
+fn multiply_ntts(f: &Poly, g: &Poly) -> Poly {
+    let mut h = [0u32; N];
+    for i in 0..128 {
+        let (a0, a1, b0, b1) = (f[2 * i], f[2 * i + 1], g[2 * i], g[2 * i + 1]);
+        h[2 * i] = (a0 * b0 + a1 * b1 % Q * GAMMAS[i]) % Q;
+        h[2 * i + 1] = (a0 * b1 + a1 * b0) % Q;
+    }
+    h
+}
+
+fn add(f: &mut Poly, g: &Poly) {
+    for (f, g) in f.iter_mut().zip(g) {
+        *f = (*f + g) % Q;
+    }
+}
+
+/// 把每个系数压缩为 d 比特：round(2 ^ d · x / q) mod 2 ^ d
+fn compress(f: &Poly, d: usize) -> Poly {
+    let mut res = [0u32; N];
+    for (r, x) in res.iter_mut().zip(f) {
+        *r = ((((*x as u64) << (d + 1)) + Q as u64) / (2 * Q as u64)) as u32 & ((1 << d) - 1);
+    }
+    res
+}
+
+/// `compress` 的近似逆：round(q · y / 2 ^ d)
+fn decompress(f: &Poly, d: usize) -> Poly {
+    let mut res = [0u32; N];
+    for (r, y) in res.iter_mut().zip(f) {
+        *r = (Q * y + (1 << (d - 1))) >> d;
+    }
+    res
+}
+
+/// 每个系数取低 d 比特，按小端序依次排列，共 32·d 字节
+fn byte_encode(f: &Poly, d: usize) -> Vec<u8> {
+    let mut out = vec![0u8; 32 * d];
+    for (i, a) in f.iter().enumerate() {
+        for j in 0..d {
+            let bit = i * d + j;
+            out[bit / 8] |= (((a >> j) & 1) as u8) << (bit % 8);
+        }
+    }
+    out
+}
+
+/// `byte_encode` 的逆，d = 12 时系数对 q 取模
+fn byte_decode(bytes: &[u8], d: usize) -> Poly {
+    let mut f = [0u32; N];
+    for (i, a) in f.iter_mut().enumerate() {
+        for j in 0..d {
+            let bit = i * d + j;
+            *a |= (((bytes[bit / 8] >> (bit % 8)) & 1) as u32) << j;
+        }
+        if d == 12 {
+            *a %= Q;
+        }
+    }
+    f
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ntt_multiplication() {
+        // NTT 域上的乘法等于 Z_q[X] / (X ^ 256 + 1) 中的乘法
+        let f: Poly = std::array::from_fn(|i| (i as u32 * 7 + 3) % Q);
+        let g: Poly = std::array::from_fn(|i| (i as u32 * i as u32 + 11) % Q);
+        let mut expected = [0u32; N];
+        for (i, a) in f.iter().enumerate() {
+            for (j, b) in g.iter().enumerate() {
+                let v = a * b % Q;
+                let k = (i + j) % N;
+                expected[k] = if i + j < N { (expected[k] + v) % Q } else { (expected[k] + Q - v) % Q };
+            }
+        }
+        let (mut f_hat, mut g_hat) = (f, g);
+        ntt(&mut f_hat);
+        ntt(&mut g_hat);
+        let mut h = multiply_ntts(&f_hat, &g_hat);
+        inverse_ntt(&mut h);
+        assert_eq!(h, expected);
+        inverse_ntt(&mut f_hat);
+        assert_eq!(f_hat, f);
+    }
+
+    #[test]
+    fn encapsulate() {
+        for params in [KemParams::ML_KEM_512, KemParams::ML_KEM_768, KemParams::ML_KEM_1024] {
+            let (ek, dk) = params.keygen();
+            assert_eq!(ek.0.len(), params.encapsulation_key_size());
+            assert_eq!(dk.encapsulation_key(), ek);
+            let (key, mut ciphertext) = params.encapsulate(&ek).unwrap();
+            assert_eq!(ciphertext.len(), params.ciphertext_size());
+            assert_eq!(params.decapsulate(&dk, &ciphertext), Some(key));
+
+            // 篡改的密文得到隐式拒绝的密钥，对同一个密文是确定的
+            ciphertext[5] ^= 1;
+            let rejected = params.decapsulate(&dk, &ciphertext).unwrap();
+            assert_ne!(rejected, key);
+            assert_eq!(params.decapsulate(&dk, &ciphertext), Some(rejected));
+            assert_eq!(params.decapsulate(&dk, &ciphertext[1..]), None);
+        }
+    }
+
+    #[test]
+    fn deterministic() {
+        let params = KemParams::ML_KEM_768;
+        let (ek, dk) = params.keygen_from_seed(&[1; 32], &[2; 32]);
+        assert_eq!(params.keygen_from_seed(&[1; 32], &[2; 32]).0, ek);
+        assert_eq!(params.encapsulate_with(&ek, &[3; 32]), params.encapsulate_with(&ek, &[3; 32]));
+        let (key, ciphertext) = params.encapsulate_with(&ek, &[3; 32]).unwrap();
+        assert_eq!(params.decapsulate(&dk, &ciphertext), Some(key));
+        assert_eq!(params.decapsulate_message(&dk, &ciphertext), Some([3; 32]));
+        let mut tampered = ciphertext.clone();
+        tampered[0] ^= 1;
+        assert_eq!(params.decapsulate_message(&dk, &tampered), None);
+
+        // t 的系数不小于 q 的公钥被拒绝
+        let mut invalid = ek.clone();
+        invalid.0[0] = 0xff;
+        invalid.0[1] |= 0x0f;
+        assert_eq!(params.encapsulate(&invalid), None);
+    }
+
+    /// 已知答案：种子 d = 0..32，z = 32..64，封装的随机数 m = 64..96，篡改的密文为翻转第一个字节的最低位
+    /// 期望值由 OpenSSL 3.5 的 FIPS 203 实现计算，公钥、私钥和密文给出 SHA3-256 摘要，共享密钥给出原值
+    #[test]
+    fn known_answers() {
+        let vectors = [
+            (
+                KemParams::ML_KEM_512,
+                "82f101ff648063b376e2bb6c5b7455f655a50c2feadade150efa0e0e6f365aea",
+                "0bd3f5df01098ac9c29d687c7f1bd0588a5573feeef8f1e3b4573fa7f6ab57c8",
+                "e3fdddb90255869185c07cdf1c1880b2efe08b6f04da4997b693c0dea61503bd",
+                "14cace3e48771b316676afad2cfcfe8488daaa4fad954e57236caa3f24a42cf7",
+                "32ee1fb3f7bd2915218e9c1b2d0d2da88f0edce6804278bab3a6123c5bb64fc4",
+            ),
+            (
+                KemParams::ML_KEM_768,
+                "a24e16d8f8f9383a95b77050f4d9fd2f5733eec1d63ef3c23ebf9918173669a7",
+                "1149f17c3c4ac6ab1e3e2d9d8bd0171355ac0fa31bb8855c48ceade874c0864b",
+                "b4cfbd24cef67afd3764276c6980e0f88f8e9ca57f59b7f12fe1a9c1e72f4710",
+                "9cddd089ffe70e3996e76f7c8d06746df34d07e8657bc0fcf2bb0e1c3084aea1",
+                "dcfc80c6db46ff7028e3a4398651c063ae7a42c107a6dc8cb07141861698ab92",
+            ),
+            (
+                KemParams::ML_KEM_1024,
+                "61349e5c131a7e116a0463861d7d18663c5627c38c7147ddaadfd48acd7a4535",
+                "f0db5d938027fcd9bad87847d52c14cf0c4abcf0703b749793f212111ffb303b",
+                "c1579fa02c614f3762b2a799b51e41cebb8f820f34fa736af02c56de2460ce3c",
+                "0ad8d1ea1b8dd788979b4379581218df9321bdce5567eca42ae6be7d395f1a54",
+                "8f2c880890996c587aa500cf8b6da03372de706a9f96075744bb0956ea6fbaac",
+            ),
+        ];
+        let seed = |start: u8| std::array::from_fn(|i| start + i as u8);
+        let digest = |bytes: &[u8]| hex::encode(Sha3_256::digest(bytes));
+        for (params, ek_digest, dk_digest, c_digest, key, rejected) in vectors {
+            let (ek, dk) = params.keygen_from_seed(&seed(0), &seed(32));
+            assert_eq!(digest(&ek.0), ek_digest);
+            assert_eq!(digest(&dk.0), dk_digest);
+            let (shared, mut ciphertext) = params.encapsulate_with(&ek, &seed(64)).unwrap();
+            assert_eq!(digest(&ciphertext), c_digest);
+            assert_eq!(hex::encode(shared), key);
+            assert_eq!(hex::encode(params.decapsulate(&dk, &ciphertext).unwrap()), key);
+            ciphertext[0] ^= 1;
+            assert_eq!(hex::encode(params.decapsulate(&dk, &ciphertext).unwrap()), rejected);
+        }
+    }
+}
@@ -1,14 +1,26 @@
 pub mod algebra {
     pub mod coset;
     pub mod field;
+    pub mod lattice;
     pub mod polynomial;
+    pub mod reed_solomon;
 }
+pub mod committee;
+pub mod envelope;
+pub mod kem;
 pub mod merkle_tree;
+pub mod params;
+pub mod pow;
 pub mod query_result;
 pub mod random_oracle;
+pub mod security;
+pub mod share;
+pub mod signature;
 pub mod vec_check;
 
+/// 缺省的码率的对数，码率为 2 ^ -CODE_RATE
 pub const CODE_RATE: usize = 3;
+/// 缺省的目标安全级别
 pub const SECURITY_BITS: usize = 100;
 
 /// 将 `n` 分成若干个 2 的幂次，返回这些幂次的集合
@@ -26,3 +38,39 @@ pub fn split_n(mut n: usize) -> Vec<usize> {
     res.sort_by(|x, y| y.trailing_zeros().cmp(&x.trailing_zeros()));
     res
 }
+
+/// 计算 log2(n) 并向上取整，即满足 2 ^ i >= n 的最小的 i
+pub fn log_2_n(n: usize) -> usize {
+    let mut i = 0;
+    while (1 << i) < n {
+        i += 1;
+    }
+    i
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn log2() {
+        assert_eq!(log_2_n(1), 0);
+        assert_eq!(log_2_n(4), 2);
+        assert_eq!(log_2_n(7), 3);
+        assert_eq!(log_2_n(8), 3);
+        assert_eq!(log_2_n(9), 4);
+        for i in 3..70 {
+            assert!(1 << log_2_n(i) >= i);
+            assert!(1 << (log_2_n(i) - 1) < i);
+        }
+    }
+
+    #[test]
+    fn split() {
+        for t in 1..40 {
+            let v = split_n(t);
+            assert_eq!(v.iter().sum::<usize>(), t);
+            assert_eq!(v.last().unwrap() % 2, 1);
+        }
+    }
+}
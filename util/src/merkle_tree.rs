@@ -12,6 +12,8 @@ impl Hasher for Blake3Algorithm {
 }
 
 pub const MERKLE_ROOT_SIZE: usize = 32;
+/// 零知识模式下每个叶子节点的随机盐的字节数
+pub const SALT_SIZE: usize = 16;
 #[derive(Clone)]
 pub struct MerkleTreeProver {
     pub merkle_tree: MerkleTree<Blake3Algorithm>,
@@ -64,7 +66,10 @@ impl MerkleTreeVerifier {
         indices: &Vec<usize>,
         leaves: &Vec<Vec<u8>>,
     ) -> bool {
-        let proof = MerkleProof::<Blake3Algorithm>::try_from(proof_bytes).unwrap();
+        let proof = match MerkleProof::<Blake3Algorithm>::try_from(proof_bytes) {
+            Ok(proof) => proof,
+            Err(_) => return false,
+        };
         let leaves_to_prove: Vec<[u8; MERKLE_ROOT_SIZE]> =
             leaves.iter().map(|x| Blake3Algorithm::hash(x)).collect();
         proof.verify(
@@ -0,0 +1,575 @@
+use crate::algebra::{coset::Coset, field::Field, polynomial::{MultilinearPolynomial, Polynomial}};
+use crate::committee::Committee;
+use crate::merkle_tree::{MERKLE_ROOT_SIZE, SALT_SIZE};
+use crate::random_oracle::RandomOracle;
+use crate::security::SecurityConfig;
+use crate::{log_2_n, split_n};
+use std::mem::size_of;
+
+/// 验证时一次哈希的开销，以域上乘法的次数计
+pub const HASH_COST: usize = 50;
+
+/// 低次测试中单个参与方的开销估计，由 `ProtocolParams::proof_cost` 计算
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProofCost {
+    /// 参与方最终收到的多项式的变量数
+    pub terminate_round: usize,
+    /// 参与方收到的证明的字节数，包括默克尔树根、查询结果、默克尔路径和明文发送的最终多项式
+    pub proof_bytes: usize,
+    /// 验证时需要计算的哈希次数
+    pub hashes: usize,
+    /// 验证时需要进行的域上乘法次数
+    pub field_ops: usize,
+}
+
+impl ProofCost {
+    /// 验证者的总工作量，以域上乘法的次数计
+    pub fn verifier_work(&self) -> usize {
+        self.hashes * HASH_COST + self.field_ops
+    }
+}
+
+/// `ProtocolParams` 由委员会 (n, f, t) 和安全配置推导出协议的全部参数
+/// 包括求值域的大小、折叠的轮数、查询的次数以及各个余元集合的偏移量
+/// 同一次运行中的所有组件（Dealer、参与方、ADKG、VABA、Gather）共享同一份参数
+#[derive(Debug, Clone)]
+pub struct ProtocolParams<T: Field> {
+    committee: Committee,
+    security: SecurityConfig,
+    log_n: usize,
+    split: Vec<usize>,
+    terminate_round: usize,
+    /// 打包秘密分享时一个多项式中的秘密个数
+    packing: usize,
+    blinding_points: Vec<T>,
+    interpolate_shift: T,
+    x_shift: T,
+    y_shift: T,
+}
+
+impl<T: Field> ProtocolParams<T> {
+    /// `security_bits` 为低次测试的目标安全级别，其余安全配置取缺省值
+    pub fn new(committee: &Committee, security_bits: usize) -> Self {
+        Self::with_security(committee, SecurityConfig::new(security_bits))
+    }
+
+    /// 按照给定的安全配置推导参数，实际达到的安全级别低于目标时拒绝该配置
+    pub fn with_security(committee: &Committee, security: SecurityConfig) -> Self {
+        let params = ProtocolParams {
+            committee: committee.clone(),
+            security,
+            log_n: log_2_n(committee.total_weight()),
+            split: split_n(committee.t()),
+            terminate_round: 1,
+            packing: 1,
+            blinding_points: vec![],
+            interpolate_shift: T::random_element(),
+            x_shift: T::random_element(),
+            y_shift: T::random_element(),
+        };
+        params.check();
+        params
+    }
+
+    /// 检查求值域的大小和实际达到的安全级别
+    fn check(&self) {
+        let log_domain = self.num_variables() + self.log_rate();
+        if log_domain > T::LOG_ORDER as usize || self.log_n > T::LOG_ORDER as usize {
+            panic!("evaluation domain is too large for the field");
+        }
+        self.security
+            .check(T::FIELD_BITS, log_domain, self.total_round());
+    }
+
+    pub fn committee(&self) -> &Committee {
+        &self.committee
+    }
+
+    /// 秘密分享的门限，秘密多项式在 x 和 y 上的次数
+    pub fn t(&self) -> usize {
+        self.committee.t()
+    }
+
+    pub fn security(&self) -> &SecurityConfig {
+        &self.security
+    }
+
+    /// 目标安全级别
+    pub fn security_bits(&self) -> usize {
+        self.security.target_bits()
+    }
+
+    /// 估计实际达到的安全级别
+    pub fn achieved_bits(&self) -> f64 {
+        self.security
+            .estimate(T::FIELD_BITS, self.num_variables() + self.log_rate(), self.total_round())
+    }
+
+    /// 码率的对数，求值域的大小为多项式次数的 2 ^ log_rate 倍
+    pub fn log_rate(&self) -> usize {
+        self.security.log_rate()
+    }
+
+    /// 每个方向上求值点数量的对数，求值点的数量不小于委员会的权重之和
+    pub fn log_n(&self) -> usize {
+        self.log_n
+    }
+
+    /// 参与方的数量，即 x 方向与 y 方向求值点数量的乘积
+    pub fn party_num(&self) -> usize {
+        1 << (self.log_n * 2)
+    }
+
+    /// 多项式的变量数，x 和 y 方向各按照 `split_n(t)` 拆分为若干个变量
+    pub fn log_d(&self) -> usize {
+        self.split.len() * 2
+    }
+
+    /// Dealer 承诺的多项式的变量数，零知识模式下包括掩码变量
+    pub fn num_variables(&self) -> usize {
+        self.log_d() + self.blinding_points.len()
+    }
+
+    /// 参与方最终收到的多项式的变量数
+    pub fn terminate_round(&self) -> usize {
+        self.terminate_round
+    }
+
+    /// 设置参与方最终收到的多项式的变量数，要求 1 <= terminate_round < log_d
+    /// terminate_round 每增加 1，折叠的轮数减少 1，而 Dealer 以明文发送的最终多项式的大小翻倍
+    pub fn with_terminate_round(mut self, terminate_round: usize) -> Self {
+        if terminate_round == 0 || terminate_round >= self.log_d() {
+            panic!("must have 1 <= terminate_round < log_d");
+        }
+        self.terminate_round = terminate_round;
+        if self.zero_knowledge() {
+            self.blinding_points = self.new_blinding_points();
+        }
+        self.check();
+        self
+    }
+
+    /// 开启零知识模式：Dealer 在折叠到份额多项式之前插入若干个掩码变量，并对默克尔树的叶子节点加盐
+    /// 掩码变量在折叠时取公开的随机常数 c_j，参与方得到的份额不变
+    pub fn with_zero_knowledge(mut self) -> Self {
+        self.blinding_points = self.new_blinding_points();
+        self.check();
+        self
+    }
+
+    /// 委员会和求值点不变、门限改为 `t` 的参数，用于分发次数更高的多项式，例如乘法中 2t 次的份额
+    /// 余元集合的偏移量保持不变，因此两份参数下同一个节点的份额位于相同的求值点上
+    pub fn with_threshold(mut self, t: usize) -> Self {
+        let committee = &self.committee;
+        self.committee = Committee::new(committee.weights().clone(), committee.f(), t);
+        self.split = split_n(t);
+        if self.zero_knowledge() {
+            self.blinding_points = self.new_blinding_points();
+        }
+        self.check();
+        self
+    }
+
+    /// 打包秘密分享：一个多项式在 `(x_0, e_i)` 处隐藏 `packing` 个秘密，其中 `e_i = i`
+    /// 重构仍需要 t + 1 个份额，而保密性降低为 t + 1 - packing 个份额，要求它不小于 f
+    pub fn with_packing(mut self, packing: usize) -> Self {
+        if packing == 0 || packing + self.committee.f() > self.t() + 1 {
+            panic!("must have 1 <= packing <= t - f + 1");
+        }
+        self.packing = packing;
+        let coset_y = self.coset_y().all_elements();
+        if self.secret_points().iter().any(|e| coset_y.contains(e)) {
+            panic!("secret points must not be share points");
+        }
+        self
+    }
+
+    pub fn packing(&self) -> usize {
+        self.packing
+    }
+
+    /// 不泄露秘密的任何信息的份额个数的上界，不打包时为 t
+    pub fn privacy(&self) -> usize {
+        self.t() + 1 - self.packing
+    }
+
+    /// 秘密在 y 方向的坐标 `e_i = i`，不打包时只有 0
+    pub fn secret_points(&self) -> Vec<T> {
+        (0..self.packing).map(|i| T::from_int(i as u64)).collect()
+    }
+
+    /// 掩码变量的个数取满足下面条件的最小值：掩码多项式的随机系数不少于单个参与方看到的取值，
+    /// 且最后 terminate_round + k 个变量的掩码足以覆盖每一轮打开的取值
+    fn new_blinding_points(&self) -> Vec<T> {
+        let query_num = self.query_num();
+        let r = self.terminate_round;
+        let rounds = self.log_d() - r;
+        let k = (1..)
+            .find(|k| {
+                (1usize << (self.log_d() + k - 1)) >= 4 * query_num * (rounds + k) + (2 << r)
+                    && (1usize << (r + k)) >= 4 * query_num
+            })
+            .unwrap();
+        (0..k).map(|_| T::random_element()).collect()
+    }
+
+    pub fn zero_knowledge(&self) -> bool {
+        !self.blinding_points.is_empty()
+    }
+
+    /// 掩码变量折叠时所取的常数 c_j
+    pub fn blinding_points(&self) -> &Vec<T> {
+        &self.blinding_points
+    }
+
+    /// 掩码变量插入的位置，在它们之前是 log_d - terminate_round 个按参与方开点折叠的变量
+    pub fn blinding_position(&self) -> usize {
+        self.log_d() - self.terminate_round
+    }
+
+    /// 明文发送的最终多项式的次数上界，最终多项式的次数小于 2 ^ terminate_round
+    pub fn final_degree(&self) -> usize {
+        1 << self.terminate_round
+    }
+
+    /// 估计 terminate_round 取给定值时单个参与方的证明大小和验证开销
+    /// 默克尔路径的长度按照每个查询单独打开计算，是实际开销的上界
+    pub fn proof_cost(&self, terminate_round: usize) -> ProofCost {
+        let query_num = self.query_num();
+        let element = size_of::<T>();
+        let total_round = self.num_variables() - terminate_round;
+        let mut proof_bytes = 0;
+        let mut hashes = 0;
+        let mut field_ops = 0;
+        let mut opened = 0;
+        for i in 0..total_round {
+            let log_leaves = self.num_variables() + self.log_rate() - i - 1;
+            opened = std::cmp::min(query_num, 1 << log_leaves);
+            let path = opened * log_leaves;
+            // 第 0 轮只有多项式本身的查询结果，之后每轮还有折叠的查询结果
+            let results = if i == 0 { 1 } else { 2 };
+            let salts = if self.zero_knowledge() { opened * SALT_SIZE } else { 0 };
+            proof_bytes += results
+                * (MERKLE_ROOT_SIZE + opened * 2 * element + path * MERKLE_ROOT_SIZE + salts);
+            hashes += results * (opened + path);
+            field_ops += opened * if i == 0 { 4 } else { 8 };
+        }
+        // 最终多项式和参与方的份额多项式各有 2 ^ terminate_round 个系数，每个查询点都要分别求值
+        let final_len = 1 << terminate_round;
+        proof_bytes += 2 * final_len * element;
+        if self.security.grinding_bits() > 0 {
+            proof_bytes += size_of::<u64>();
+        }
+        field_ops += opened * 2 * final_len;
+        ProofCost {
+            terminate_round,
+            proof_bytes,
+            hashes,
+            field_ops,
+        }
+    }
+
+    /// 所有合法的 terminate_round 对应的开销
+    pub fn proof_costs(&self) -> Vec<ProofCost> {
+        (1..self.log_d()).map(|r| self.proof_cost(r)).collect()
+    }
+
+    /// 证明最小的 terminate_round，证明大小相同时选择验证开销较小的
+    pub fn optimal_terminate_round(&self) -> usize {
+        self.proof_costs()
+            .iter()
+            .min_by_key(|c| (c.proof_bytes, c.verifier_work()))
+            .unwrap()
+            .terminate_round
+    }
+
+    /// 折叠的轮数
+    pub fn total_round(&self) -> usize {
+        self.num_variables() - self.terminate_round
+    }
+
+    /// 低次测试的查询次数
+    pub fn query_num(&self) -> usize {
+        self.security.query_num()
+    }
+
+    /// 为一次分发生成随机数
+    pub fn oracle(&self) -> RandomOracle<T> {
+        let mut oracle = RandomOracle::new(self.total_round(), self.query_num());
+        oracle.grinding_bits = self.security.grinding_bits();
+        oracle
+    }
+
+    /// 插值所用的余元集合，第一个余元的阶为 2 ^ (num_variables + log_rate)，后面的余元是前一个余元的平方
+    pub fn interpolate_cosets(&self) -> Vec<Coset<T>> {
+        let mut interpolate_cosets = vec![Coset::new(
+            1 << (self.num_variables() + self.log_rate()),
+            self.interpolate_shift,
+        )];
+        for i in 1..self.num_variables() {
+            interpolate_cosets.push(interpolate_cosets[i - 1].pow(2));
+        }
+        interpolate_cosets
+    }
+
+    /// x 方向的求值点，阶为 2 ^ log_n
+    pub fn coset_x(&self) -> Coset<T> {
+        Coset::new(1 << self.log_n, self.x_shift)
+    }
+
+    /// y 方向的求值点，阶为 2 ^ log_n
+    pub fn coset_y(&self) -> Coset<T> {
+        Coset::new(1 << self.log_n, self.y_shift)
+    }
+
+    /// 第 i 列的求值点 `y_i`，权重分配得到的第 i 个求值点上的份额为 `f(x_0, y_i)`
+    pub fn share_point(&self, i: usize) -> T {
+        self.coset_y().element_at(i)
+    }
+
+    /// 多轮的折叠参数，前一半为 coset_x 的 split 次方，后一半为 coset_y 的 split 次方
+    /// coset_y 的每个元素都重复 2 ^ log_n 次，使得第 i 个参与方对应 `(x_{i % n}, y_{i / n})`
+    /// 零知识模式下在 `blinding_position` 处为每个掩码变量插入一轮常数折叠参数
+    pub fn folding_parameter(&self) -> Vec<Vec<T>> {
+        let coset_x = self.coset_x();
+        let coset_y = self.coset_y();
+        let mut folding_parameter = vec![];
+        for i in &self.split {
+            folding_parameter.push(coset_x.pow(*i).all_elements());
+        }
+        let last_len = folding_parameter.last().unwrap().len();
+        for i in &self.split {
+            folding_parameter.push(
+                coset_y
+                    .pow(*i)
+                    .all_elements()
+                    .iter()
+                    .flat_map(|x| (0..last_len).map(|_| *x))
+                    .collect(),
+            );
+        }
+        let position = self.blinding_position();
+        let len = folding_parameter[position - 1].len();
+        folding_parameter.splice(
+            position..position,
+            self.blinding_points.iter().map(|c| vec![*c; len]),
+        );
+        folding_parameter
+    }
+
+    /// 秘密所在的点 `(x_0, 0)`，x 方向的变量为 x_0 的 split 次方，y 方向的变量都为 0
+    pub fn secret_point(&self) -> Vec<T> {
+        self.point_at(T::from_int(0))
+    }
+
+    /// 点 `(x_0, y)` 对应的多线性多项式的变量，每个方向的变量为坐标的 split 次方
+    pub fn point_at(&self, y: T) -> Vec<T> {
+        let x_0 = self.coset_x().element_at(0);
+        let mut point: Vec<T> = self.split.iter().map(|i| x_0.pow(*i)).collect();
+        point.extend(self.split.iter().map(|i| y.pow(*i)));
+        point
+    }
+
+    /// 秘密为 `secret` 的随机多项式，即 `f(x_0, 0) = secret`
+    pub fn secret_polynomial(&self, secret: T) -> MultilinearPolynomial<T> {
+        MultilinearPolynomial::random_with_value(self.log_d(), &self.secret_point(), secret)
+    }
+
+    /// 打包的秘密为 `secrets` 的随机多项式，即 `f(x_0, e_i) = secrets[i]`
+    /// 在随机多项式上加上只含 y 方向变量的修正项 h(y)，h 为经过 `(e_i, secrets[i] - f(x_0, e_i))` 的多项式
+    pub fn packed_polynomial(&self, secrets: &[T]) -> MultilinearPolynomial<T> {
+        assert_eq!(secrets.len(), self.packing);
+        let poly = MultilinearPolynomial::random_polynomial(self.log_d());
+        let points = self.secret_points();
+        let differences: Vec<_> = points
+            .iter()
+            .zip(secrets)
+            .map(|(e, s)| *s - poly.evaluate(&self.point_at(*e)))
+            .collect();
+        let h = Polynomial::interpolate(&points, &differences);
+        // y 的 m 次方对应 split 中和为 m 的若干个 y 方向变量的乘积
+        let len = self.split.len();
+        let mut coefficients = poly.coefficients().clone();
+        for (m, c) in h.coefficients().iter().enumerate() {
+            let mask = (0..1usize << len)
+                .find(|mask| (0..len).filter(|k| mask >> k & 1 == 1).map(|k| self.split[k]).sum::<usize>() == m)
+                .unwrap();
+            coefficients[mask << len] += *c;
+        }
+        MultilinearPolynomial::new(coefficients)
+    }
+
+    /// 第 i 个参与方的开点
+    pub fn open_point(&self, i: usize) -> Vec<T> {
+        self.folding_parameter()
+            .iter()
+            .map(|v| v[i % v.len()])
+            .collect()
+    }
+
+    /// 所有参与方的开点，每个参与方有 num_variables 个开点，每个开点都是折叠参数的一个元素
+    pub fn open_points(&self) -> Vec<Vec<T>> {
+        let folding_parameter = self.folding_parameter();
+        (0..self.party_num())
+            .map(|i| {
+                folding_parameter
+                    .iter()
+                    .map(|v| v[i % v.len()])
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algebra::field::{fp64::Fp64, mersenne61_ext::Mersenne61Ext};
+    use crate::security::Regime;
+    use crate::CODE_RATE;
+
+    #[test]
+    fn derive() {
+        let params: ProtocolParams<Mersenne61Ext> =
+            ProtocolParams::new(&Committee::uniform(7, 2, 2), 100);
+        assert_eq!(params.log_n(), 3);
+        assert_eq!(params.party_num(), 64);
+        assert_eq!(params.log_d(), 4);
+        assert_eq!(params.total_round(), 3);
+        assert_eq!(params.query_num(), 100usize.div_ceil(CODE_RATE));
+        assert!(params.achieved_bits() >= 100.0);
+        assert_eq!(params.interpolate_cosets().len(), params.log_d());
+        assert_eq!(params.interpolate_cosets()[0].size(), 1 << (4 + CODE_RATE));
+
+        // 第 i 个参与方的开点对应 (x_{i % n}, y_{i / n})
+        let open_points = params.open_points();
+        let n = 1 << params.log_n();
+        for i in [0, 5, 17, 63] {
+            assert_eq!(open_points[i][1], params.coset_x().element_at(i % n));
+            assert_eq!(open_points[i][3], params.share_point(i / n));
+        }
+
+        // 权重之和决定求值域的大小
+        let weighted: ProtocolParams<Mersenne61Ext> =
+            ProtocolParams::new(&Committee::new(vec![1, 3, 1, 2, 1, 1, 2], 3, 3), 100);
+        assert_eq!(weighted.log_n(), 4);
+    }
+
+    #[test]
+    fn terminate_round() {
+        let params: ProtocolParams<Mersenne61Ext> =
+            ProtocolParams::new(&Committee::uniform(35, 11, 11), 100);
+        assert_eq!(params.log_d(), 8);
+        let params = params.with_terminate_round(3);
+        assert_eq!(params.total_round(), 5);
+        assert_eq!(params.final_degree(), 8);
+        assert_eq!(params.oracle().folding_challenges.len(), 5);
+
+        // 轮数越少，哈希越少，但明文发送的最终多项式越大
+        let costs = params.proof_costs();
+        assert_eq!(costs.len(), params.log_d() - 1);
+        for i in 1..costs.len() {
+            assert!(costs[i].hashes < costs[i - 1].hashes);
+        }
+        let optimal = params.optimal_terminate_round();
+        assert!(costs
+            .iter()
+            .all(|c| c.proof_bytes >= params.proof_cost(optimal).proof_bytes));
+    }
+
+    #[test]
+    fn security() {
+        let committee = Committee::uniform(7, 2, 2);
+        let security = SecurityConfig::new(80)
+            .with_log_rate(2)
+            .with_regime(Regime::UniqueDecoding);
+        let params: ProtocolParams<Mersenne61Ext> =
+            ProtocolParams::with_security(&committee, security.clone());
+        assert_eq!(params.query_num(), security.query_num());
+        assert_eq!(params.interpolate_cosets()[0].size(), 1 << (4 + 2));
+        assert_eq!(params.oracle().query_list.len(), params.query_num());
+    }
+
+    #[test]
+    fn zero_knowledge() {
+        let params: ProtocolParams<Mersenne61Ext> =
+            ProtocolParams::new(&Committee::uniform(7, 2, 2), 100).with_zero_knowledge();
+        let k = params.blinding_points().len();
+        assert!(k > 0);
+        assert_eq!(params.num_variables(), params.log_d() + k);
+        assert_eq!(params.total_round(), params.log_d() + k - 1);
+
+        // 掩码变量位于最后一个变量之前，所有参与方在掩码变量上的开点相同
+        let open_points = params.open_points();
+        let position = params.blinding_position();
+        for point in &open_points {
+            assert_eq!(point.len(), params.num_variables());
+            assert_eq!(&point[position..position + k], &params.blinding_points()[..]);
+        }
+        let n = 1 << params.log_n();
+        assert_eq!(open_points[17][params.num_variables() - 1], params.share_point(17 / n));
+    }
+
+    #[test]
+    fn secret() {
+        let params: ProtocolParams<Mersenne61Ext> =
+            ProtocolParams::new(&Committee::uniform(7, 2, 2), 100);
+        let secret = Mersenne61Ext::random_element();
+        let poly = params.secret_polynomial(secret);
+        assert_eq!(poly.variable_num(), params.log_d());
+        assert_eq!(poly.evaluate(&params.secret_point()), secret);
+    }
+
+    #[test]
+    fn packed() {
+        let params: ProtocolParams<Mersenne61Ext> =
+            ProtocolParams::new(&Committee::uniform(7, 1, 3), 100).with_packing(3);
+        assert_eq!(params.privacy(), 1);
+        let secrets: Vec<_> = (0..3).map(|_| Mersenne61Ext::random_element()).collect();
+        let poly = params.packed_polynomial(&secrets);
+        for (e, s) in params.secret_points().iter().zip(&secrets) {
+            assert_eq!(poly.evaluate(&params.point_at(*e)), *s);
+        }
+        assert_eq!(poly.evaluate(&params.secret_point()), secrets[0]);
+
+        // 份额位于同一个 t 次多项式上，由 t + 1 个份额重构全部秘密
+        let n = 1 << params.log_n();
+        let shares: Vec<_> = (0..4)
+            .map(|i| vec![params.share_point(i), poly.evaluate(&params.open_point(i * n)[..params.log_d()].to_vec())])
+            .collect();
+        assert_eq!(Polynomial::reconstruct_packed(&shares, params.t(), &params.secret_points()), Ok(secrets));
+    }
+
+    #[test]
+    #[should_panic(expected = "must have 1 <= packing <= t - f + 1")]
+    fn packing_too_large() {
+        let _: ProtocolParams<Mersenne61Ext> = ProtocolParams::new(&Committee::uniform(7, 2, 2), 100).with_packing(2);
+    }
+
+    #[test]
+    fn threshold() {
+        let params: ProtocolParams<Mersenne61Ext> =
+            ProtocolParams::new(&Committee::uniform(7, 1, 2), 100);
+        let double = params.clone().with_threshold(4);
+        assert_eq!(double.t(), 4);
+        assert_eq!(double.log_d(), 6);
+        assert_eq!(double.coset_x().element_at(0), params.coset_x().element_at(0));
+        for i in 0..7 {
+            assert_eq!(double.share_point(i), params.share_point(i));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "security level is below the target")]
+    fn security_small_field() {
+        let _: ProtocolParams<Fp64> = ProtocolParams::new(&Committee::uniform(7, 2, 2), 100);
+    }
+
+    #[test]
+    #[should_panic(expected = "must have 1 <= terminate_round < log_d")]
+    fn terminate_round_too_large() {
+        let params: ProtocolParams<Mersenne61Ext> =
+            ProtocolParams::new(&Committee::uniform(7, 2, 2), 100);
+        params.with_terminate_round(4);
+    }
+}
@@ -0,0 +1,80 @@
+use crate::algebra::field::{as_bytes_vec, Field};
+use crate::merkle_tree::MERKLE_ROOT_SIZE;
+
+/// 工作量证明所绑定的记录，由承诺全部记录（各轮默克尔树根和最终多项式）的根和所有折叠的挑战值计算得到
+pub fn transcript_seed<T: Field>(
+    root: &[u8; MERKLE_ROOT_SIZE],
+    challenges: &[T],
+) -> [u8; MERKLE_ROOT_SIZE] {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(root);
+    hasher.update(&as_bytes_vec(challenges));
+    hasher.finalize().into()
+}
+
+fn pow_hash(seed: &[u8; MERKLE_ROOT_SIZE], nonce: u64) -> [u8; MERKLE_ROOT_SIZE] {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(seed);
+    hasher.update(&nonce.to_le_bytes());
+    hasher.finalize().into()
+}
+
+/// 哈希值开头的 0 的位数
+fn leading_zeros(hash: &[u8]) -> usize {
+    let mut res = 0;
+    for byte in hash {
+        res += byte.leading_zeros() as usize;
+        if *byte != 0 {
+            break;
+        }
+    }
+    res
+}
+
+/// 寻找一个 nonce，使得 `blake3(seed || nonce)` 的开头至少有 `bits` 位 0，期望需要 2 ^ bits 次哈希
+pub fn grind(seed: &[u8; MERKLE_ROOT_SIZE], bits: usize) -> u64 {
+    (0..u64::MAX)
+        .find(|nonce| leading_zeros(&pow_hash(seed, *nonce)) >= bits)
+        .unwrap()
+}
+
+pub fn verify(seed: &[u8; MERKLE_ROOT_SIZE], nonce: u64, bits: usize) -> bool {
+    leading_zeros(&pow_hash(seed, nonce)) >= bits
+}
+
+/// 由记录和 nonce 导出 `query_num` 个查询的下标，Dealer 和参与方得到相同的下标
+pub fn query_indices(seed: &[u8; MERKLE_ROOT_SIZE], nonce: u64, query_num: usize) -> Vec<usize> {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(b"query");
+    hasher.update(seed);
+    hasher.update(&nonce.to_le_bytes());
+    let mut reader = hasher.finalize_xof();
+    (0..query_num)
+        .map(|_| {
+            let mut bytes = [0u8; 8];
+            reader.fill(&mut bytes);
+            u64::from_le_bytes(bytes) as usize
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algebra::field::mersenne61_ext::Mersenne61Ext;
+
+    #[test]
+    fn grind_and_verify() {
+        let challenges: Vec<_> = (0..4).map(|_| Mersenne61Ext::random_element()).collect();
+        let seed = transcript_seed(&[7u8; MERKLE_ROOT_SIZE], &challenges);
+        let nonce = grind(&seed, 10);
+        assert!(verify(&seed, nonce, 10));
+        assert!(leading_zeros(&pow_hash(&seed, nonce)) >= 10);
+
+        // 记录改变后原来的 nonce 以很大的概率不再有效，查询的下标也随之改变
+        let other = transcript_seed(&[8u8; MERKLE_ROOT_SIZE], &challenges);
+        assert_ne!(query_indices(&seed, nonce, 20), query_indices(&other, nonce, 20));
+        assert_eq!(query_indices(&seed, nonce, 20), query_indices(&seed, nonce, 20));
+        assert_eq!(leading_zeros(&[0, 0, 0x10, 0xff]), 19);
+    }
+}
@@ -3,38 +3,71 @@ use crate::merkle_tree::MerkleTreeVerifier;
 use std::collections::HashMap;
 use std::mem::size_of;
 
-#[derive(Clone)]
+#[derive(Debug, Clone)]
 pub struct QueryResult<T: Field> {
     pub proof_bytes: Vec<u8>,
     pub proof_values: HashMap<usize, T>,
+    /// 零知识模式下被打开的叶子节点的随机盐，叶子节点的哈希为 `hash(values || salt)`
+    pub salts: HashMap<usize, Vec<u8>>,
+    /// 批量承诺时每个多项式被打开的取值，叶子节点依次包含每个多项式在 x 和 -x 处的取值
+    pub batch_values: Vec<HashMap<usize, T>>,
 }
 
 impl<T: Field> QueryResult<T> {
     /// `verify_merkle_tree` 用于验证默克尔树
     /// 参数 `leaf_indices` 为叶子节点的索引，`merkle_verifier` 为对应的默克尔树的验证器
+    /// 缺少被打开的取值或证明格式错误时返回 false
     pub fn verify_merkle_tree(
         &self,
         leaf_indices: &Vec<usize>,
         merkle_verifier: &MerkleTreeVerifier,
     ) -> bool {
-        let leaves: Vec<Vec<u8>> = leaf_indices
+        let leaves: Option<Vec<Vec<u8>>> = leaf_indices
             .iter()
             .map(|x| {
-                as_bytes_vec(&[
-                    self.proof_values.get(x).unwrap().clone(),
-                    self.proof_values
-                        .get(&(x + merkle_verifier.leave_number))
-                        .unwrap()
-                        .clone(),
-                ])
+                let y = x + merkle_verifier.leave_number;
+                let mut leaf = if self.batch_values.is_empty() {
+                    as_bytes_vec(&[*self.proof_values.get(x)?, *self.proof_values.get(&y)?])
+                } else {
+                    let values: Option<Vec<T>> = self
+                        .batch_values
+                        .iter()
+                        .flat_map(|v| [v.get(x).copied(), v.get(&y).copied()])
+                        .collect();
+                    as_bytes_vec(&values?)
+                };
+                if let Some(salt) = self.salts.get(x) {
+                    leaf.extend(salt);
+                }
+                Some(leaf)
             })
             .collect();
-        let res = merkle_verifier.verify(self.proof_bytes.clone(), leaf_indices, &leaves);
-        assert!(res);
+        match leaves {
+            Some(leaves) => merkle_verifier.verify(self.proof_bytes.clone(), leaf_indices, &leaves),
+            None => false,
+        }
+    }
+
+    /// 被打开的取值，批量承诺时为各个多项式的取值以 `rlc` 的幂次为系数的线性组合
+    pub fn values(&self, rlc: T) -> HashMap<usize, T> {
+        if self.batch_values.is_empty() {
+            return self.proof_values.clone();
+        }
+        let mut res = HashMap::new();
+        let mut power = T::from_int(1);
+        for values in &self.batch_values {
+            for (k, v) in values {
+                *res.entry(*k).or_insert(T::from_int(0)) += power * *v;
+            }
+            power *= rlc;
+        }
         res
     }
 
     pub fn proof_size(&self) -> usize {
-        self.proof_bytes.len() + self.proof_values.len() * size_of::<T>()
+        self.proof_bytes.len()
+            + self.proof_values.len() * size_of::<T>()
+            + self.batch_values.iter().map(|v| v.len()).sum::<usize>() * size_of::<T>()
+            + self.salts.values().map(|s| s.len()).sum::<usize>()
     }
 }
@@ -7,6 +7,8 @@ pub struct RandomOracle<T: Field> {
     pub rlc: T,
     pub folding_challenges: Vec<T>,
     pub query_list: Vec<usize>,
+    /// 工作量证明的位数，大于 0 时查询的下标由 Dealer 的记录和 nonce 导出，而不使用 `query_list`
+    pub grinding_bits: usize,
 }
 
 impl<T: Field> RandomOracle<T> {
@@ -22,6 +24,7 @@ impl<T: Field> RandomOracle<T> {
                 .into_iter()
                 .map(|_| rand::thread_rng().gen())
                 .collect(),
+            grinding_bits: 0,
         }
     }
 }
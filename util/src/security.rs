@@ -0,0 +1,163 @@
+use crate::{CODE_RATE, SECURITY_BITS};
+
+/// 低次测试的可靠性分析所采用的假设
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Regime {
+    /// 唯一解码半径内可证明的安全性，每次查询的错误概率为 (1 + ρ) / 2
+    UniqueDecoding,
+    /// 列表解码半径内猜想的安全性（ethSTARK 猜想），每次查询的错误概率为 ρ
+    ListDecoding,
+}
+
+/// `SecurityConfig` 描述低次测试的安全配置，Dealer 和参与方共享同一份配置
+/// 码率为 ρ = 2 ^ -log_rate，查询次数缺省时取达到目标安全级别所需的最小值
+#[derive(Debug, Clone, PartialEq)]
+pub struct SecurityConfig {
+    target_bits: usize,
+    log_rate: usize,
+    query_num: Option<usize>,
+    grinding_bits: usize,
+    regime: Regime,
+}
+
+impl Default for SecurityConfig {
+    fn default() -> Self {
+        Self::new(SECURITY_BITS)
+    }
+}
+
+impl SecurityConfig {
+    /// 以 `target_bits` 为目标安全级别，码率为 2 ^ -CODE_RATE，不做工作量证明，采用列表解码假设
+    pub fn new(target_bits: usize) -> Self {
+        if target_bits == 0 {
+            panic!("security level is too low");
+        }
+        SecurityConfig {
+            target_bits,
+            log_rate: CODE_RATE,
+            query_num: None,
+            grinding_bits: 0,
+            regime: Regime::ListDecoding,
+        }
+    }
+
+    pub fn with_log_rate(mut self, log_rate: usize) -> Self {
+        if log_rate == 0 {
+            panic!("must have log_rate >= 1");
+        }
+        self.log_rate = log_rate;
+        self
+    }
+
+    /// 指定查询次数，不再由目标安全级别推导
+    pub fn with_query_num(mut self, query_num: usize) -> Self {
+        if query_num == 0 {
+            panic!("must have query_num >= 1");
+        }
+        self.query_num = Some(query_num);
+        self
+    }
+
+    /// Dealer 在查询之前进行 `grinding_bits` 位的工作量证明
+    pub fn with_grinding_bits(mut self, grinding_bits: usize) -> Self {
+        self.grinding_bits = grinding_bits;
+        self
+    }
+
+    pub fn with_regime(mut self, regime: Regime) -> Self {
+        self.regime = regime;
+        self
+    }
+
+    pub fn target_bits(&self) -> usize {
+        self.target_bits
+    }
+
+    pub fn log_rate(&self) -> usize {
+        self.log_rate
+    }
+
+    pub fn grinding_bits(&self) -> usize {
+        self.grinding_bits
+    }
+
+    pub fn regime(&self) -> Regime {
+        self.regime
+    }
+
+    /// 每次查询提供的安全位数
+    pub fn bits_per_query(&self) -> f64 {
+        match self.regime {
+            Regime::UniqueDecoding => 1.0 - (1.0 + 0.5f64.powi(self.log_rate as i32)).log2(),
+            Regime::ListDecoding => self.log_rate as f64,
+        }
+    }
+
+    /// 查询次数，缺省时为使查询部分达到目标安全级别的最小值
+    pub fn query_num(&self) -> usize {
+        self.query_num.unwrap_or_else(|| {
+            let bits = self.target_bits.saturating_sub(self.grinding_bits) as f64;
+            std::cmp::max(1, (bits / self.bits_per_query()).ceil() as usize)
+        })
+    }
+
+    /// 查询部分的安全位数，包括工作量证明
+    pub fn query_bits(&self) -> f64 {
+        self.query_num() as f64 * self.bits_per_query() + self.grinding_bits as f64
+    }
+
+    /// 承诺部分的安全位数，每轮折叠的错误概率约为 |D| / |F|，其中 D 为该轮的求值域
+    pub fn commit_bits(&self, field_bits: usize, log_domain: usize, rounds: usize) -> f64 {
+        field_bits as f64 - log_domain as f64 - (rounds.max(1) as f64).log2()
+    }
+
+    /// 估计实际达到的安全位数，`field_bits` 为域的大小的对数，`log_domain` 为第一轮求值域大小的对数
+    pub fn estimate(&self, field_bits: usize, log_domain: usize, rounds: usize) -> f64 {
+        self.query_bits()
+            .min(self.commit_bits(field_bits, log_domain, rounds))
+    }
+
+    /// 实际达到的安全位数低于目标时拒绝该配置
+    pub fn check(&self, field_bits: usize, log_domain: usize, rounds: usize) {
+        let achieved = self.estimate(field_bits, log_domain, rounds);
+        if achieved < self.target_bits as f64 {
+            panic!(
+                "security level is below the target: {:.1} < {}",
+                achieved, self.target_bits
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algebra::field::{fp64::Fp64, mersenne61_ext::Mersenne61Ext, Field};
+
+    #[test]
+    fn estimate() {
+        let config = SecurityConfig::new(100);
+        assert_eq!(config.query_num(), 34);
+        assert!(config.query_bits() >= 100.0);
+
+        // 同样的求值域，扩域 Mersenne61Ext 能达到目标，而 Fp64 受限于域的大小
+        let achieved = config.estimate(Mersenne61Ext::FIELD_BITS, 11, 7);
+        assert!(achieved >= 100.0);
+        assert!(config.estimate(Fp64::FIELD_BITS, 11, 7) < 64.0);
+
+        // 唯一解码假设下每次查询的安全位数更少，需要更多的查询
+        let unique = SecurityConfig::new(100).with_regime(Regime::UniqueDecoding);
+        assert!(unique.query_num() > 3 * config.query_num());
+        assert!(unique.query_bits() >= 100.0);
+
+        // 工作量证明可以减少查询次数
+        let grinding = SecurityConfig::new(100).with_grinding_bits(16);
+        assert_eq!(grinding.query_num(), 28);
+    }
+
+    #[test]
+    #[should_panic(expected = "security level is below the target")]
+    fn below_target() {
+        SecurityConfig::new(100).check(Fp64::FIELD_BITS, 11, 7);
+    }
+}
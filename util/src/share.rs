@@ -0,0 +1,230 @@
+use crate::algebra::field::Field;
+use crate::params::ProtocolParams;
+use std::ops::{Add, Mul, Neg, Sub};
+
+/// 份额所在的求值域：y 方向 `size` 个求值点组成的余元集合 `shift·ω^i`，以及分享的门限
+/// 门限为 t 和 2t 的参数（`ProtocolParams::with_threshold`）求值点相同，它们的份额可以组合，组合后的门限取较大者
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Domain<T: Field> {
+    size: usize,
+    shift: T,
+    threshold: usize,
+}
+
+impl<T: Field> Domain<T> {
+    /// `params` 的份额所在的求值域
+    pub fn new(params: &ProtocolParams<T>) -> Self {
+        Domain {
+            size: 1 << params.log_n(),
+            shift: params.coset_y().shift(),
+            threshold: params.t(),
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    /// 第 i 个求值点 `y_i`，与 `ProtocolParams::share_point` 相同
+    pub fn point(&self, i: usize) -> T {
+        self.shift * T::get_generator(self.size).pow(i)
+    }
+
+    /// 两个求值域的求值点相同
+    pub fn same_points(&self, other: &Self) -> bool {
+        self.size == other.size && self.shift == other.shift
+    }
+}
+
+/// 秘密分享的一个份额：求值域 `domain` 中第 `index` 个求值点 `y_index` 上的取值 `value`
+/// `origin` 为份额来自的 Dealer，线性组合后为所有参与组合的 Dealer，按编号排列且不重复
+/// 只有同一组求值点中同一个求值点上的份额才能组合，`checked_add` 和 `checked_sub` 在其它情况下返回 None，运算符则 panic
+#[derive(Debug, Clone, PartialEq)]
+pub struct Share<T: Field> {
+    domain: Domain<T>,
+    index: usize,
+    value: T,
+    origin: Vec<usize>,
+}
+
+impl<T: Field> Share<T> {
+    pub fn new(domain: Domain<T>, index: usize, value: T, mut origin: Vec<usize>) -> Self {
+        if index >= domain.size {
+            panic!("share index must be in the domain");
+        }
+        origin.sort();
+        origin.dedup();
+        Share { domain, index, value, origin }
+    }
+
+    /// Dealer `dealer` 分发的份额
+    pub fn dealt(domain: Domain<T>, index: usize, value: T, dealer: usize) -> Self {
+        Self::new(domain, index, value, vec![dealer])
+    }
+
+    pub fn domain(&self) -> &Domain<T> {
+        &self.domain
+    }
+
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn value(&self) -> T {
+        self.value
+    }
+
+    pub fn origin(&self) -> &Vec<usize> {
+        &self.origin
+    }
+
+    /// 份额所在的求值点 `y_index`
+    pub fn point(&self) -> T {
+        self.domain.point(self.index)
+    }
+
+    /// 用于插值的份额 `[y_index, value]`
+    pub fn interpolate(&self) -> Vec<T> {
+        vec![self.point(), self.value]
+    }
+
+    /// 两个份额位于同一组求值点中的同一个求值点上，可以组合
+    pub fn same_domain(&self, other: &Self) -> bool {
+        self.domain.same_points(&other.domain) && self.index == other.index
+    }
+
+    /// 两个份额之和，不在同一个求值点上时返回 None
+    pub fn checked_add(self, other: Self) -> Option<Self> {
+        self.combine(other, |x, y| x + y)
+    }
+
+    /// 两个份额之差，不在同一个求值点上时返回 None
+    pub fn checked_sub(self, other: Self) -> Option<Self> {
+        self.combine(other, |x, y| x - y)
+    }
+
+    /// 同一个求值点上若干份额的线性组合 Σ c_i·s_i
+    pub fn linear_combination(shares: &[Share<T>], coefficients: &[T]) -> Self {
+        if shares.is_empty() || shares.len() != coefficients.len() {
+            panic!("must give one coefficient for each share");
+        }
+        shares
+            .iter()
+            .zip(coefficients)
+            .map(|(s, c)| s.clone() * *c)
+            .reduce(|x, y| x + y)
+            .unwrap()
+    }
+
+    /// 同一个求值点上若干份额之和
+    pub fn sum(shares: &[Share<T>]) -> Self {
+        shares.iter().cloned().reduce(|x, y| x + y).expect("must give at least one share")
+    }
+
+    fn combine(self, other: Self, op: impl Fn(T, T) -> T) -> Option<Self> {
+        if !self.same_domain(&other) {
+            return None
+        }
+        let mut origin = self.origin;
+        origin.extend(&other.origin);
+        let mut domain = self.domain;
+        domain.threshold = domain.threshold.max(other.domain.threshold);
+        Some(Share::new(domain, self.index, op(self.value, other.value), origin))
+    }
+}
+
+impl<T: Field> Add for Share<T> {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        self.checked_add(other).expect("shares must be on the same evaluation point of the same domain")
+    }
+}
+
+impl<T: Field> Sub for Share<T> {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        self.checked_sub(other).expect("shares must be on the same evaluation point of the same domain")
+    }
+}
+
+impl<T: Field> Neg for Share<T> {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Share { value: -self.value, ..self }
+    }
+}
+
+/// 乘以公开的常数
+impl<T: Field> Mul<T> for Share<T> {
+    type Output = Self;
+
+    fn mul(self, c: T) -> Self {
+        Share { value: self.value * c, ..self }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algebra::field::mersenne61_ext::Mersenne61Ext;
+    use crate::algebra::polynomial::Polynomial;
+    use crate::committee::Committee;
+
+    #[test]
+    fn linear() {
+        let params: ProtocolParams<Mersenne61Ext> = ProtocolParams::new(&Committee::uniform(7, 2, 2), 100);
+        let domain = Domain::new(&params);
+        let f = Polynomial::random_polynomial(3);
+        let g = Polynomial::random_polynomial(3);
+        let c = Mersenne61Ext::random_element();
+
+        // 份额的线性组合是秘密的线性组合的份额
+        let shares: Vec<_> = (0..7)
+            .map(|i| {
+                let y = params.share_point(i);
+                assert_eq!(domain.point(i), y);
+                let f = Share::dealt(domain, i, f.evaluation_at(y), 3);
+                let g = Share::dealt(domain, i, g.evaluation_at(y), 1);
+                Share::linear_combination(&[f.clone(), g.clone()], &[Mersenne61Ext::from_int(1), c]) - f * c
+            })
+            .collect();
+        assert_eq!(shares[0].origin(), &vec![1, 3]);
+        let points: Vec<_> = shares.iter().map(|s| s.interpolate()).collect();
+        let secret = f.coefficients()[0] + (g.coefficients()[0] - f.coefficients()[0]) * c;
+        assert_eq!(Polynomial::reconstruct(&points, params.t()), Ok(secret));
+        assert_eq!(-shares[2].clone() + shares[2].clone(), shares[2].clone() * Mersenne61Ext::from_int(0));
+    }
+
+    #[test]
+    fn domains() {
+        let one = Mersenne61Ext::from_int(1);
+        let params: ProtocolParams<Mersenne61Ext> = ProtocolParams::new(&Committee::uniform(7, 2, 2), 100);
+        let (single, double) = (Domain::new(&params), Domain::new(&params.clone().with_threshold(4)));
+
+        // 门限 t 和 2t 的份额求值点相同，差为门限 2t 的份额
+        let diff = Share::dealt(double, 3, one, 0).checked_sub(Share::dealt(single, 3, one, 1)).unwrap();
+        assert_eq!(diff.domain().threshold(), 4);
+        assert!(diff.value().is_zero());
+
+        // 不同求值点上的份额、不同求值域中的份额都不能组合
+        assert!(Share::dealt(single, 0, one, 0).checked_add(Share::dealt(single, 1, one, 0)).is_none());
+        let other = Domain::new(&ProtocolParams::<Mersenne61Ext>::new(&Committee::uniform(16, 5, 5), 100));
+        assert!(!other.same_points(&single));
+        assert!(Share::dealt(single, 0, one, 0).checked_add(Share::dealt(other, 0, one, 0)).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "shares must be on the same evaluation point of the same domain")]
+    fn different_points() {
+        let one = Mersenne61Ext::from_int(1);
+        let domain = Domain::new(&ProtocolParams::new(&Committee::uniform(7, 2, 2), 100));
+        let _ = Share::dealt(domain, 0, one, 0) + Share::dealt(domain, 1, one, 0);
+    }
+}
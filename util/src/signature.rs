@@ -0,0 +1,105 @@
+use rand::RngCore;
+
+/// 签名的消息先哈希成 256 比特，每个比特对应签名密钥中的一对随机数
+const BITS: usize = 256;
+
+/// 基于 blake3 的 Lamport 一次性签名：签名密钥为 256 对随机数，验证密钥为它们的哈希，
+/// 签名公开消息的哈希的每个比特对应的那个随机数。每个签名密钥只能签名一条消息，第二次签名会泄露更多的随机数
+#[derive(Clone)]
+pub struct SigningKey {
+    preimages: Vec<[[u8; 32]; 2]>,
+    used: bool,
+}
+
+/// 验证密钥，共 2 * 256 个哈希值
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifyingKey {
+    images: Vec<[[u8; 32]; 2]>,
+}
+
+/// 签名，共 256 个随机数
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    preimages: Vec<[u8; 32]>,
+}
+
+/// 生成一对验证密钥和签名密钥
+pub fn keygen() -> (VerifyingKey, SigningKey) {
+    let mut rng = rand::thread_rng();
+    let preimages: Vec<[[u8; 32]; 2]> = (0..BITS)
+        .map(|_| {
+            let mut pair = [[0u8; 32]; 2];
+            rng.fill_bytes(&mut pair[0]);
+            rng.fill_bytes(&mut pair[1]);
+            pair
+        })
+        .collect();
+    let images = preimages.iter().map(|pair| [image(&pair[0]), image(&pair[1])]).collect();
+    (VerifyingKey { images }, SigningKey { preimages, used: false })
+}
+
+fn image(preimage: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(b"PQC-ADKG lamport image");
+    hasher.update(preimage);
+    hasher.finalize().into()
+}
+
+/// 消息的哈希的第 i 个比特
+fn bits(message: &[u8]) -> impl Iterator<Item = usize> {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(b"PQC-ADKG lamport message");
+    hasher.update(message);
+    let digest: [u8; 32] = hasher.finalize().into();
+    (0..BITS).map(move |i| ((digest[i / 8] >> (i % 8)) & 1) as usize)
+}
+
+impl SigningKey {
+    /// 签名 `message`，密钥已经签名过其它消息时返回 None
+    pub fn sign(&mut self, message: &[u8]) -> Option<Signature> {
+        if self.used {
+            return None
+        }
+        self.used = true;
+        let preimages = self.preimages.iter().zip(bits(message)).map(|(pair, b)| pair[b]).collect();
+        Some(Signature { preimages })
+    }
+}
+
+impl VerifyingKey {
+    /// 检查 `signature` 是 `message` 的签名
+    pub fn verify(&self, message: &[u8], signature: &Signature) -> bool {
+        signature.preimages.len() == BITS
+            && self.images.len() == BITS
+            && self
+                .images
+                .iter()
+                .zip(bits(message))
+                .zip(&signature.preimages)
+                .all(|((pair, b), preimage)| image(preimage) == pair[b])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::keygen;
+
+    #[test]
+    fn sign_and_verify() {
+        let (vk, mut sk) = keygen();
+        let signature = sk.sign(b"sum").unwrap();
+        assert!(vk.verify(b"sum", &signature));
+        assert!(!vk.verify(b"other", &signature));
+        assert!(sk.sign(b"other").is_none());
+
+        // 其它密钥的签名和被修改的签名都不能通过验证
+        let (other, mut other_sk) = keygen();
+        assert!(!other.verify(b"sum", &signature));
+        assert!(!vk.verify(b"sum", &other_sk.sign(b"sum").unwrap()));
+        let mut tampered = signature.clone();
+        tampered.preimages[0][0] ^= 1;
+        assert!(!vk.verify(b"sum", &tampered));
+        tampered.preimages.pop();
+        assert!(!vk.verify(b"sum", &tampered));
+    }
+}